@@ -0,0 +1,431 @@
+//! keyframed changes to a scene over time, and rendering them out frame by frame
+//!
+//! an `Animation` doesn't own the scene, it is applied to a copy of a `World` and `Camera` for each
+//! frame, so the originals are left as the rest pose
+use std::ops::Range;
+
+use crate::error::{Result, RtcError};
+use crate::{camera::Camera, canvas::Canvas, coord::Coord, matrix::Matrix, tex::color::Color};
+
+pub use crate::material::MaterialParam;
+use crate::world::{Integrator, ObjectId, World};
+
+/// how values move between two keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    /// constant speed
+    Linear,
+    /// starts slow and speeds up
+    EaseIn,
+    /// starts fast and slows down
+    EaseOut,
+    /// slow at both keys (smoothstep)
+    EaseInOut,
+    /// holds each key until the next one
+    Step,
+}
+
+impl Easing {
+    /// maps the fraction `f` in [0, 1] of the way between two keys
+    pub fn apply(&self, f: f32) -> f32 {
+        match self {
+            Easing::Linear => f,
+            Easing::EaseIn => f * f,
+            Easing::EaseOut => f * (2.0 - f),
+            Easing::EaseInOut => f * f * (3.0 - 2.0 * f),
+            Easing::Step => if f >= 1.0 { 1.0 } else { 0.0 },
+        }
+    }
+}
+
+/// values that can be blended between keys, f = 0 gives self and f = 1 gives other
+pub trait Lerp: Clone {
+    fn lerp(&self, other: &Self, f: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, other: &Self, f: f32) -> Self {
+        self + (other - self) * f
+    }
+}
+
+impl Lerp for Coord {
+    fn lerp(&self, other: &Self, f: f32) -> Self {
+        *self + (*other - *self) * f
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(&self, other: &Self, f: f32) -> Self {
+        *self * (1.0 - f) + *other * f
+    }
+}
+
+/// elementwise, see `Motion` for the caveat with rotations
+impl Lerp for Matrix {
+    fn lerp(&self, other: &Self, f: f32) -> Self {
+        crate::motion::lerp(self, other, f)
+    }
+}
+
+/// a value keyed at times (in seconds), held at the first or last key outside of the keyed range
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T: Lerp> {
+    keys: Vec<(f32, T)>,    // sorted by time
+    easing: Easing,
+}
+
+#[allow(dead_code)]
+impl<T: Lerp> Track<T> {
+    /// returns an error if there are no keys
+    pub fn new(mut keys: Vec<(f32, T)>, easing: Easing) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(RtcError::NoKeyframes);
+        }
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { keys, easing })
+    }
+
+    /// the same value at all times
+    pub fn constant(value: T) -> Self {
+        Self { keys: vec![(0.0, value)], easing: Easing::Linear }
+    }
+
+    pub fn get_keys(&self) -> &Vec<(f32, T)> {
+        &self.keys
+    }
+
+    pub fn get_easing(&self) -> Easing {
+        self.easing
+    }
+
+    /// value at `time`, held at the first or last key outside of the keyed range (and at the last
+    /// for a NaN time)
+    pub fn at(&self, time: f32) -> T {
+        let first = &self.keys[0];
+        let last = &self.keys[self.keys.len() - 1];
+        if time <= first.0 {
+            return first.1.clone();
+        }
+        if time >= last.0 {
+            return last.1.clone();
+        }
+        let next = match self.keys.iter().position(|(t, _)| *t > time) {
+            Some(val) => val,
+            None => return last.1.clone(),
+        };
+        let (t0, a) = &self.keys[next - 1];
+        let (t1, b) = &self.keys[next];
+        a.lerp(b, self.easing.apply((time - t0) / (t1 - t0)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CameraTrack {
+    from: Track<Coord>,
+    to: Track<Coord>,
+    up: Coord,
+}
+
+/// a set of tracks driving parts of a scene, and the frame rate and length to render them at
+///
+/// object transformations replace the object's own transformation rather than adding to it, and
+/// the camera is placed with `Matrix::view_transformation`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    fps: f32,
+    frame_count: usize,
+    transformations: Vec<(ObjectId, Track<Matrix>)>,
+    materials: Vec<(ObjectId, MaterialParam, Track<f32>)>,
+    colors: Vec<(ObjectId, Track<Color>)>,
+    lights: Vec<(usize, Track<Coord>)>,
+    camera: Option<CameraTrack>,
+}
+
+#[allow(dead_code)]
+impl Animation {
+    /// returns an error if fps isn't positive
+    pub fn new(fps: f32, frame_count: usize) -> Result<Self> {
+        if fps.is_nan() || fps <= 0.0 {
+            return Err(RtcError::NonPositiveValue { field: "fps", value: fps });
+        }
+        Ok(Self {
+            fps,
+            frame_count,
+            transformations: Vec::new(),
+            materials: Vec::new(),
+            colors: Vec::new(),
+            lights: Vec::new(),
+            camera: None,
+        })
+    }
+
+    pub fn get_fps(&self) -> f32 {
+        self.fps
+    }
+
+    pub fn get_frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// time in seconds of the start of `frame`, frame 0 is at time 0
+    pub fn frame_time(&self, frame: usize) -> f32 {
+        frame as f32 / self.fps
+    }
+
+    pub fn animate_transformation(&mut self, id: ObjectId, track: Track<Matrix>) {
+        self.transformations.push((id, track));
+    }
+
+    pub fn animate_material(&mut self, id: ObjectId, param: MaterialParam, track: Track<f32>) {
+        self.materials.push((id, param, track));
+    }
+
+    /// sets the material to a flat color, replacing any pattern
+    pub fn animate_color(&mut self, id: ObjectId, track: Track<Color>) {
+        self.colors.push((id, track));
+    }
+
+    /// moves the light at `index` in `World::get_light`
+    pub fn animate_light(&mut self, index: usize, track: Track<Coord>) {
+        self.lights.push((index, track));
+    }
+
+    /// camera at `from` looking at `to`
+    pub fn animate_camera(&mut self, from: Track<Coord>, to: Track<Coord>, up: Coord) {
+        self.camera = Some(CameraTrack { from, to, up });
+    }
+
+    /// poses `world` and `cam` as they are at `time`
+    ///
+    /// returns an error if an animated object or light doesn't exist, or a keyed value is invalid
+    pub fn apply(&self, world: &mut World, cam: &mut Camera, time: f32) -> Result<()> {
+        for (id, track) in &self.transformations {
+            world.set_obj_transformation(*id, track.at(time))?;
+        }
+        for (id, track) in &self.colors {
+            let mut mat = world.get_obj(*id).ok_or(RtcError::UnknownObject(*id))?.get_material();
+            mat.set_color(track.at(time));
+            world.set_obj_material(*id, mat)?;
+        }
+        for (id, param, track) in &self.materials {
+            let mut mat = world.get_obj(*id).ok_or(RtcError::UnknownObject(*id))?.get_material();
+            mat.set_param(*param, track.at(time))?;
+            world.set_obj_material(*id, mat)?;
+        }
+        for (index, track) in &self.lights {
+            let mut light = *world.get_light().get(*index).ok_or(RtcError::UnknownLight(*index))?;
+            light.set_pos(track.at(time))?;
+            world.replace_light(*index, light)?;
+        }
+        if let Some(c) = &self.camera {
+            cam.set_transformation(Matrix::view_transformation(c.from.at(time), c.to.at(time), c.up))?;
+        }
+        Ok(())
+    }
+
+    /// copies of `world` and `cam` posed for `frame`
+    pub fn pose(&self, world: &World, cam: &Camera, frame: usize) -> Result<(World, Camera)> {
+        let mut world = world.clone();
+        let mut cam = cam.clone();
+        self.apply(&mut world, &mut cam, self.frame_time(frame))?;
+        Ok((world, cam))
+    }
+
+    pub fn render_frame(&self, world: &World, cam: &Camera, frame: usize, integrator: &dyn Integrator) -> Result<Canvas> {
+        let (world, cam) = self.pose(world, cam, frame)?;
+        Ok(world.render_with(&cam, integrator))
+    }
+
+    /// renders each frame in `frames`, or all of them when None
+    pub fn render_frames(&self, world: &World, cam: &Camera, frames: Option<Range<usize>>, integrator: &dyn Integrator) -> Result<Vec<Canvas>> {
+        frames.unwrap_or(0..self.frame_count)
+            .map(|frame| self.render_frame(world, cam, frame, integrator))
+            .collect()
+    }
+
+    /// writes each frame to `{prefix}_{frame:04}.ppm`, rendering and saving one frame at a time
+    pub fn render_to_files(&self, world: &World, cam: &Camera, frames: Option<Range<usize>>, integrator: &dyn Integrator, prefix: &str) -> Result<()> {
+        for frame in frames.unwrap_or(0..self.frame_count) {
+            let canvas = self.render_frame(world, cam, frame, integrator)?;
+            canvas.to_file(&format!("{prefix}_{frame:04}.ppm"))?;
+        }
+        Ok(())
+    }
+
+    /// renders the frames into a looping animated gif at `path`, colors are quantized per frame.
+    /// returns an error if the camera is more than 65535 pixels wide or high, the most a gif can hold
+    #[cfg(feature = "gif")]
+    pub fn render_to_gif(&self, world: &World, cam: &Camera, frames: Option<Range<usize>>, integrator: &dyn Integrator, path: &str) -> Result<()> {
+        let gif_err = |e: gif::EncodingError| RtcError::Io(e.to_string());
+        let (width, height) = match (u16::try_from(cam.get_hsize()), u16::try_from(cam.get_vsize())) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(RtcError::ImageTooLarge { width: cam.get_hsize(), height: cam.get_vsize(), max: u16::MAX as usize }),
+        };
+        let file = std::fs::File::create(path)?;
+        let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(gif_err)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_err)?;
+        let delay = (100.0 / self.fps).round() as u16;  // in hundredths of a second
+        for frame in frames.unwrap_or(0..self.frame_count) {
+            let canvas = self.render_frame(world, cam, frame, integrator)?;
+            let mut out = gif::Frame::from_rgb(width, height, &canvas.to_rgb8());
+            out.delay = delay;
+            encoder.write_frame(&out).map_err(gif_err)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{light::Light, primitives::sphere::Sphere, renderable::Renderable, world::DebugIntegrator, world::DebugMode};
+
+    use super::*;
+
+    #[test]
+    fn test_easing() {
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+        }
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(Easing::EaseInOut.apply(0.1) < 0.1);
+        assert_eq!(Easing::Step.apply(0.99), 0.0);
+    }
+
+    #[test]
+    fn test_track() {
+        let t = Track::new(vec![(2.0, 10.0), (0.0, 0.0), (1.0, 4.0)], Easing::Linear).unwrap();
+        assert_eq!(t.at(-1.0), 0.0);
+        assert_eq!(t.at(0.5), 2.0);
+        assert_eq!(t.at(1.5), 7.0);
+        assert_eq!(t.at(3.0), 10.0);
+        assert_eq!(t.at(f32::NAN), 10.0);
+        let t = Track::new(vec![(0.0, 0.0), (1.0, 4.0)], Easing::EaseIn).unwrap();
+        assert_eq!(t.at(0.5), 1.0);
+        assert_eq!(Track::<f32>::new(vec![], Easing::Linear), Err(RtcError::NoKeyframes));
+        assert_eq!(Track::constant(Color::red()).at(5.0), Color::red());
+    }
+
+    #[test]
+    fn test_track_types() {
+        let t = Track::new(vec![(0.0, Coord::point(0.0, 0.0, 0.0)), (1.0, Coord::point(2.0, 4.0, 0.0))], Easing::Linear).unwrap();
+        assert_eq!(t.at(0.5), Coord::point(1.0, 2.0, 0.0));
+        let t = Track::new(vec![(0.0, Color::black()), (1.0, Color::white())], Easing::Linear).unwrap();
+        assert_eq!(t.at(0.5), Color::new(0.5, 0.5, 0.5, 0.0));
+        let t = Track::new(vec![(0.0, Matrix::identity(4)), (1.0, Matrix::translation(2.0, 0.0, 0.0))], Easing::Linear).unwrap();
+        assert_eq!(t.at(0.5), Matrix::translation(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_new() {
+        let a = Animation::new(24.0, 48).unwrap();
+        assert_eq!(a.frame_time(12), 0.5);
+        assert_eq!(Animation::new(0.0, 10), Err(RtcError::NonPositiveValue { field: "fps", value: 0.0 }));
+        assert_eq!(Animation::new(-24.0, 10), Err(RtcError::NonPositiveValue { field: "fps", value: -24.0 }));
+        assert!(Animation::new(f32::NAN, 10).is_err());
+    }
+
+    fn scene() -> (World, Camera, ObjectId) {
+        let mut w = World::new();
+        w.add_light(Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white()).unwrap());
+        let id = w.add_obj(Arc::new(<Sphere as Renderable>::default()));
+        let cam = Camera::new(11, 11, std::f32::consts::PI / 2.0);
+        (w, cam, id)
+    }
+
+    #[test]
+    fn test_apply() {
+        let (w, cam, id) = scene();
+        let mut a = Animation::new(10.0, 11).unwrap();
+        a.animate_transformation(id, Track::new(vec![(0.0, Matrix::identity(4)), (1.0, Matrix::translation(0.0, 2.0, 0.0))], Easing::Linear).unwrap());
+        a.animate_material(id, MaterialParam::Reflection, Track::new(vec![(0.0, 0.0), (1.0, 1.0)], Easing::Linear).unwrap());
+        a.animate_color(id, Track::new(vec![(0.0, Color::black()), (1.0, Color::white())], Easing::Linear).unwrap());
+        a.animate_light(0, Track::new(vec![(0.0, Coord::point(0.0, 10.0, 0.0)), (1.0, Coord::point(10.0, 10.0, 0.0))], Easing::Linear).unwrap());
+        let from = Track::new(vec![(0.0, Coord::point(0.0, 0.0, -5.0)), (1.0, Coord::point(4.0, 0.0, -5.0))], Easing::Linear).unwrap();
+        a.animate_camera(from, Track::constant(Coord::point(0.0, 0.0, 0.0)), Coord::vec(0.0, 1.0, 0.0));
+
+        let (w2, cam2) = a.pose(&w, &cam, 5).unwrap();
+        let obj = w2.get_obj(id).unwrap();
+        assert_eq!(obj.get_transformation(), Matrix::translation(0.0, 1.0, 0.0));
+        assert_eq!(obj.get_material().get_reflection(), 0.5);
+        assert_eq!(obj.get_material().get_color(), Color::new(0.5, 0.5, 0.5, 0.0));
+        assert_eq!(w2.get_light()[0].get_pos(), Coord::point(5.0, 10.0, 0.0));
+        assert_eq!(cam2.get_transformation(), Matrix::view_transformation(
+            Coord::point(2.0, 0.0, -5.0),
+            Coord::point(0.0, 0.0, 0.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        ));
+        // the originals are untouched
+        assert_eq!(w.get_obj(id).unwrap().get_transformation(), Matrix::identity(4));
+        assert_eq!(cam.get_transformation(), Matrix::identity(4));
+    }
+
+    #[test]
+    fn test_apply_unknown_targets() {
+        let (w, cam, _) = scene();
+        let mut a = Animation::new(10.0, 1).unwrap();
+        a.animate_light(3, Track::constant(Coord::point(0.0, 0.0, 0.0)));
+        assert_eq!(a.pose(&w, &cam, 0).err(), Some(RtcError::UnknownLight(3)));
+
+        let mut w = w;
+        let gone = w.add_obj(Arc::new(<Sphere as Renderable>::default()));
+        w.remove_obj(gone).unwrap();
+        let mut a = Animation::new(10.0, 1).unwrap();
+        a.animate_material(gone, MaterialParam::Ambient, Track::constant(0.5));
+        assert_eq!(a.pose(&w, &cam, 0).err(), Some(RtcError::UnknownObject(gone)));
+    }
+
+    #[test]
+    fn test_render_frames() {
+        let (w, mut cam, id) = scene();
+        cam.set_transformation(Matrix::view_transformation(
+            Coord::point(0.0, 0.0, -5.0),
+            Coord::point(0.0, 0.0, 0.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        )).unwrap();
+        // the sphere moves out of the center of the image after the first frame
+        let mut a = Animation::new(1.0, 3).unwrap();
+        a.animate_transformation(id, Track::new(vec![(0.0, Matrix::identity(4)), (1.0, Matrix::translation(10.0, 0.0, 0.0))], Easing::Step).unwrap());
+        let integrator = DebugIntegrator::new(DebugMode::Albedo);
+        let frames = a.render_frames(&w, &cam, None, &integrator).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].get_pixel(5, 5), Color::white());
+        assert_eq!(frames[1].get_pixel(5, 5), Color::black());
+        assert_eq!(a.render_frames(&w, &cam, Some(1..2), &integrator).unwrap(), vec![frames[1].clone()]);
+    }
+
+    #[test]
+    fn test_render_to_files() {
+        let (w, cam, _) = scene();
+        let a = Animation::new(1.0, 2).unwrap();
+        let prefix = std::env::temp_dir().join("rtc_animation_test");
+        let prefix = prefix.to_str().unwrap();
+        a.render_to_files(&w, &cam, None, &DebugIntegrator::new(DebugMode::Albedo), prefix).unwrap();
+        assert!(std::path::Path::new(&format!("{prefix}_0000.ppm")).exists());
+        assert!(std::path::Path::new(&format!("{prefix}_0001.ppm")).exists());
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn test_render_to_gif() {
+        let (w, cam, _) = scene();
+        let a = Animation::new(10.0, 2).unwrap();
+        let path = std::env::temp_dir().join("rtc_animation_test.gif");
+        let path = path.to_str().unwrap();
+        a.render_to_gif(&w, &cam, None, &DebugIntegrator::new(DebugMode::Albedo), path).unwrap();
+        let data = std::fs::read(path).unwrap();
+        assert_eq!(&data[..6], b"GIF89a");
+
+        let wide = Camera::new(70_000, 1, std::f32::consts::PI / 2.0);
+        assert_eq!(
+            a.render_to_gif(&w, &wide, None, &DebugIntegrator::new(DebugMode::Albedo), path),
+            Err(RtcError::ImageTooLarge { width: 70_000, height: 1, max: 65_535 })
+        );
+    }
+}
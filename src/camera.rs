@@ -1,4 +1,5 @@
 use crate::impl_getters_setters;
+use crate::error::{Result, RtcError};
 use crate::{coord::Coord, matrix::Matrix, ray::Ray};
 use std::ops;
 
@@ -10,9 +11,12 @@ pub struct Camera {
     vsize: f32,
     field_of_view: f32,
     transformation: Matrix,
+    inverse: Matrix,    // cached so rays don't need to invert the transformation per pixel
     pixel_size: f32,
     half_width: f32,
-    half_height: f32
+    half_height: f32,
+    shutter_open: f32,  // rays are sent at times spread over [shutter_open, shutter_close]
+    shutter_close: f32
 }
 
 impl_getters_setters!(Camera, 
@@ -40,26 +44,53 @@ impl Camera {
         }
         // TODO: experiment with different vsize and hsize
         let pixel_size: f32 = (half_width * 2.0) / hsize; // assumes pixels are square, so no need to account for vsize
-        Self { hsize, vsize, field_of_view, transformation: Matrix::identity(4), pixel_size, half_height, half_width }
+        Self { hsize, vsize, field_of_view, transformation: Matrix::identity(4), inverse: Matrix::identity(4), pixel_size, half_height, half_width, shutter_open: 0.0, shutter_close: 0.0 }
     }
 
-    fn new_transformed(hsize: usize, vsize: usize, field_of_view: f32, transformation: Matrix) -> Self {
+    fn new_transformed(hsize: usize, vsize: usize, field_of_view: f32, transformation: Matrix) -> Result<Self> {
         let mut out = Self::new(hsize, vsize, field_of_view);
-        out.set_transformation(transformation);
-        out
+        out.set_transformation(transformation)?;
+        Ok(out)
     }
 
-    pub fn set_transformation(&mut self, transformation: Matrix) {
-        assert_eq!(transformation.get_size(), 4);
+    /// returns an error if the transformation isn't 4x4 or can't be inverted
+    pub fn set_transformation(&mut self, transformation: Matrix) -> Result<()> {
+        self.inverse = transformation.transformation_inverse()?;
         self.transformation = transformation;
+        Ok(())
     }
 
-    fn get_transformation(&self) -> Matrix {
+    pub fn get_transformation(&self) -> Matrix {
         self.transformation.clone()
     }
 
-    pub fn transform(&mut self, transformation: Matrix) {
-        self.set_transformation(self.get_transformation() * transformation);
+    pub fn transform(&mut self, transformation: Matrix) -> Result<()> {
+        if transformation.get_size() != 4 {
+            return Err(RtcError::InvalidMatrixSize(transformation.get_size()));
+        }
+        self.set_transformation(self.get_transformation() * transformation)
+    }
+
+    /// sets the interval rays are sent over, moving objects blur across it
+    ///
+    /// times are in the same units as the objects' `Motion` keyframes, returns an error if close is
+    /// before open or either isn't finite
+    pub fn set_shutter(&mut self, open: f32, close: f32) -> Result<()> {
+        if !open.is_finite() || !close.is_finite() || close < open {
+            return Err(RtcError::InvalidInterval { field: "shutter", start: open, end: close });
+        }
+        self.shutter_open = open;
+        self.shutter_close = close;
+        Ok(())
+    }
+
+    pub fn get_shutter(&self) -> (f32, f32) {
+        (self.shutter_open, self.shutter_close)
+    }
+
+    /// time `u` of the way through the shutter interval, u in [0, 1]
+    pub fn shutter_time(&self, u: f32) -> f32 {
+        self.shutter_open + (self.shutter_close - self.shutter_open) * u
     }
 
     pub fn set_hsize(&mut self, hsize: usize) {
@@ -80,8 +111,13 @@ impl Camera {
 
     pub fn ray_for_pixel(&self, x: usize, y: usize) -> Ray {
         // get center pf px
-        let x_offset = (x as f32 + 0.5) * self.pixel_size;
-        let y_offset = (y as f32 + 0.5) * self.pixel_size;
+        self.ray_for_pixel_offset(x, y, 0.5, 0.5)
+    }
+
+    /// ray through a point inside the pixel, dx and dy are in [0, 1) with (0.5, 0.5) being the center
+    pub fn ray_for_pixel_offset(&self, x: usize, y: usize, dx: f32, dy: f32) -> Ray {
+        let x_offset = (x as f32 + dx) * self.pixel_size;
+        let y_offset = (y as f32 + dy) * self.pixel_size;
 
         // calc world space loc of px
         let world_x = self.get_half_width() - x_offset;
@@ -89,30 +125,34 @@ impl Camera {
 
         // transform canvas point and origin to compute ray's dir
         // canvas always at z = -1
-        let transform = self.get_transformation().inverse().unwrap();
-        let pixel = transform.clone() * Coord::point(world_x, world_y, -1.0);
-        let origin = transform * Coord::point(0.0, 0.0, 0.0);
+        let pixel = self.inverse.clone() * Coord::point(world_x, world_y, -1.0);
+        let origin = self.inverse.clone() * Coord::point(0.0, 0.0, 0.0);
         let dir = (pixel - origin).normalized();
 
-        Ray::new(origin, dir)
+        Ray::new(origin, dir).with_time(self.shutter_open)
     }
 }
 
 impl ops::Mul<Matrix> for Camera {
-    type Output = Camera;
+    type Output = Result<Camera>;
     
     fn mul(self, rhs: Matrix) -> Self::Output {
-        assert_eq!(rhs.get_size(), 4);
+        if rhs.get_size() != 4 {
+            return Err(RtcError::InvalidMatrixSize(rhs.get_size()));
+        }
         let hsize = self.get_hsize();
         let vsize = self.get_vsize();
         let fov = self.get_field_of_view();
+        let shutter = self.get_shutter();
         let mat = self.transformation.mul(rhs);
-        Self::new_transformed(
+        let mut out = Self::new_transformed(
             hsize,
             vsize, 
             fov,
             mat
-        )
+        )?;
+        out.set_shutter(shutter.0, shutter.1)?;
+        Ok(out)
     }
 }
 
@@ -167,9 +207,27 @@ mod tests {
     #[test]
     fn test_ray_after_cam_transformation() {
         let mut cam = Camera::new(201, 101, PI/2.0);
-        cam.set_transformation(Matrix::rotate_y(PI/4.0) * Matrix::translation(0.0, -2.0, 5.0));
+        cam.set_transformation(Matrix::rotation_y(PI/4.0) * Matrix::translation(0.0, -2.0, 5.0)).unwrap();
         let ray: Ray = cam.ray_for_pixel(100, 50);
         Coord::assert_roughly_eq(&ray.get_origin(), &Coord::point(0.0, 2.0, -5.0), EPSILON);
         Coord::assert_roughly_eq(&ray.get_direction(), &Coord::vec(2.0_f32.sqrt()/2.0, 0.0, -2.0_f32.sqrt()/2.0), EPSILON);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_shutter() {
+        let mut cam = Camera::new(201, 101, PI/2.0);
+        assert_eq!(cam.get_shutter(), (0.0, 0.0));
+        cam.set_shutter(0.5, 1.5).unwrap();
+        assert_eq!(cam.shutter_time(0.0), 0.5);
+        assert_eq!(cam.shutter_time(0.5), 1.0);
+        assert_eq!(cam.ray_for_pixel(100, 50).get_time(), 0.5);
+        assert_eq!(
+            cam.set_shutter(1.0, 0.0),
+            Err(RtcError::InvalidInterval { field: "shutter", start: 1.0, end: 0.0 })
+        );
+        assert!(cam.set_shutter(f32::NAN, 1.0).is_err());
+        assert!(cam.set_shutter(0.0, f32::NAN).is_err());
+        assert!(cam.set_shutter(0.0, f32::INFINITY).is_err());
+        assert_eq!(cam.get_shutter(), (0.5, 1.5));
+    }
+}
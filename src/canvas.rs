@@ -1,4 +1,5 @@
 use crate::impl_getters_setters;
+use crate::error::{Result, RtcError};
 use crate::tex::color::Color;
 use std::fs::File;
 use std::io::prelude::*;
@@ -75,6 +76,71 @@ impl Canvas {
         new_out
     } 
 
+    /// pixels as packed 8 bit rgb, row by row, clamped the same way as the ppm output
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels.iter().flatten().flat_map(|c| {
+            let c = c.clamped();
+            [c.get_r(), c.get_g(), c.get_b()].map(|val| (val * 255.0) as u8)
+        }).collect()
+    }
+
+    /// reads a plain (P3) or binary (P6) ppm file
+    pub fn from_ppm(filename: &str) -> Result<Canvas> {
+        Self::from_ppm_data(&std::fs::read(filename)?)
+    }
+
+    /// decodes ppm data, values are scaled by the file's max value so they end up in [0, 1]
+    pub fn from_ppm_data(data: &[u8]) -> Result<Canvas> {
+        let invalid = |msg: &str| RtcError::InvalidImage(msg.to_string());
+        // header fields are whitespace separated and '#' starts a comment to the end of the line
+        let mut pos = 0;
+        let mut fields = Vec::new();
+        while fields.len() < 4 {
+            while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+                if data[pos] == b'#' {
+                    while pos < data.len() && data[pos] != b'\n' {
+                        pos += 1;
+                    }
+                } else {
+                    pos += 1;
+                }
+            }
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(invalid("header ended early"));
+            }
+            fields.push(String::from_utf8_lossy(&data[start..pos]).to_string());
+        }
+        let number = |s: &str| s.parse::<usize>().map_err(|_| invalid(&format!("expected a number, got {s:?}")));
+        let (width, height, max) = (number(&fields[1])?, number(&fields[2])?, number(&fields[3])?);
+        if max == 0 || max > 255 {
+            return Err(invalid("only max values from 1 to 255 are supported"));
+        }
+
+        let values: Vec<usize> = match fields[0].as_str() {
+            "P3" => std::str::from_utf8(&data[pos..]).map_err(|_| invalid("pixel data isn't text"))?
+                .split_ascii_whitespace()
+                .map(number)
+                .collect::<Result<_>>()?,
+            // exactly one whitespace byte separates the header from binary data
+            "P6" => data.get(pos + 1..).unwrap_or(&[]).iter().map(|b| *b as usize).collect(),
+            other => return Err(invalid(&format!("unsupported format {other}"))),
+        };
+        if values.len() < width * height * 3 {
+            return Err(invalid("not enough pixel data"));
+        }
+
+        let mut out = Canvas::new(width, height);
+        for (i, px) in values.chunks(3).take(width * height).enumerate() {
+            let [r, g, b] = [px[0], px[1], px[2]].map(|val| val as f32 / max as f32);
+            out.set_pixel(i % width, i / width, Color::new(r, g, b, 0.0));
+        }
+        Ok(out)
+    }
+
     pub fn to_file(&self, filename: &str) -> std::io::Result<()> {
         let mut file = File::create(filename)?;
         file.write(self.get_header().as_bytes())?;
@@ -161,4 +227,47 @@ mod tests{
         let c = Canvas::new(500, 500);
         c.to_file("test.ppm").unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_to_rgb8() {
+        let mut c = Canvas::new(2, 1);
+        c.set_pixel(0, 0, Color::new(1.5, 0.5, -0.5, 0.0));
+        c.set_pixel(1, 0, Color::new(0.0, 1.0, 0.0, 0.0));
+        assert_eq!(c.to_rgb8(), vec![255, 127, 0, 0, 255, 0]);
+    }
+
+    #[test]
+    fn test_from_ppm_plain() {
+        let data = b"P3\n# a comment\n2 1\n255\n255 0 0\n0 127\n255\n";
+        let c = Canvas::from_ppm_data(data).unwrap();
+        assert_eq!(c.get_width(), 2);
+        assert_eq!(c.get_height(), 1);
+        assert_eq!(c.get_pixel(0, 0), Color::red());
+        assert_eq!(c.get_pixel(1, 0), Color::new(0.0, 127.0 / 255.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_from_ppm_binary() {
+        let mut data = b"P6 1 2 255\n".to_vec();
+        data.extend_from_slice(&[0, 255, 0, 255, 255, 255]);
+        let c = Canvas::from_ppm_data(&data).unwrap();
+        assert_eq!(c.get_pixel(0, 0), Color::green());
+        assert_eq!(c.get_pixel(0, 1), Color::white());
+    }
+
+    #[test]
+    fn test_from_ppm_round_trip() {
+        let mut c = Canvas::new(3, 2);
+        c.set_pixel(2, 1, Color::new(1.0, 0.0, 1.0, 0.0));
+        let path = std::env::temp_dir().join("rtc_canvas_round_trip.ppm");
+        c.to_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(Canvas::from_ppm(path.to_str().unwrap()).unwrap(), c);
+    }
+
+    #[test]
+    fn test_from_ppm_invalid() {
+        assert!(Canvas::from_ppm_data(b"P5 1 1 255\n").is_err());
+        assert!(Canvas::from_ppm_data(b"P3 2 2 255\n0 0 0").is_err());
+        assert!(Canvas::from_ppm_data(b"P3 2").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use std::{error::Error, fmt};
+
+use crate::world::ObjectId;
+
+/// errors returned when user supplied values can't be used to build a scene
+#[derive(Debug, Clone, PartialEq)]
+pub enum RtcError {
+    /// a material or light value that must be >= 0 was negative
+    NegativeValue { field: &'static str, value: f32 },
+    /// a value that must be > 0, like a frame rate, was zero, negative or NaN
+    NonPositiveValue { field: &'static str, value: f32 },
+    /// a transformation has no inverse, so rays can't be moved into object space
+    SingularTransformation,
+    /// a transformation must be a 4x4 matrix
+    InvalidMatrixSize(usize),
+    /// a coord was expected to be a point but was a vec (or had some other w value)
+    ExpectedPoint,
+    /// a coord was expected to be a vec but was a point (or had some other w value)
+    ExpectedVector,
+    /// no object in the world has this id, it was never added or has been removed
+    UnknownObject(ObjectId),
+    /// object names in a world must be unique
+    DuplicateName(String),
+    /// no material in the world's library has this name
+    UnknownMaterial(String),
+    /// material names in a world's library must be unique
+    DuplicateMaterial(String),
+    /// a saved scene couldn't be read or written
+    InvalidScene(String),
+    /// a time interval, like a camera shutter, that ends before it starts
+    InvalidInterval { field: &'static str, start: f32, end: f32 },
+    /// an animation or motion needs at least one keyframe
+    NoKeyframes,
+    /// a value that must lie within [min, max] didn't
+    OutOfRange { field: &'static str, value: f32, min: f32, max: f32 },
+    /// there is no light at this index in the world
+    UnknownLight(usize),
+    /// writing an image or scene file failed
+    Io(String),
+    /// an image file couldn't be decoded
+    InvalidImage(String),
+    /// the image is bigger than the output format can store
+    ImageTooLarge { width: usize, height: usize, max: usize },
+    /// a grid of samples, like a heightfield, needs at least 2x2 samples and one per cell
+    InvalidGrid { width: usize, depth: usize, samples: usize },
+    /// a model file, like a set of bezier patches, couldn't be parsed
+    InvalidModel(String),
+}
+
+impl fmt::Display for RtcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RtcError::NegativeValue { field, value } => write!(f, "{field} must be >= 0, got {value}"),
+            RtcError::NonPositiveValue { field, value } => write!(f, "{field} must be > 0, got {value}"),
+            RtcError::SingularTransformation => write!(f, "transformation is not invertible"),
+            RtcError::InvalidMatrixSize(size) => write!(f, "transformation must be 4x4, got {size}x{size}"),
+            RtcError::ExpectedPoint => write!(f, "expected a point"),
+            RtcError::ExpectedVector => write!(f, "expected a vec"),
+            RtcError::UnknownObject(id) => write!(f, "no object with id {id} in the world"),
+            RtcError::DuplicateName(name) => write!(f, "an object named {name:?} already exists"),
+            RtcError::UnknownMaterial(name) => write!(f, "no material named {name:?} in the world"),
+            RtcError::DuplicateMaterial(name) => write!(f, "a material named {name:?} already exists"),
+            RtcError::InvalidScene(msg) => write!(f, "invalid scene: {msg}"),
+            RtcError::InvalidInterval { field, start, end } => write!(f, "{field} ends before it starts, got {start} to {end}"),
+            RtcError::NoKeyframes => write!(f, "at least one keyframe is needed"),
+            RtcError::OutOfRange { field, value, min, max } => write!(f, "{field} must be in [{min}, {max}], got {value}"),
+            RtcError::UnknownLight(index) => write!(f, "no light at index {index} in the world"),
+            RtcError::Io(msg) => write!(f, "io error: {msg}"),
+            RtcError::InvalidImage(msg) => write!(f, "invalid image: {msg}"),
+            RtcError::ImageTooLarge { width, height, max } => write!(f, "a {width}x{height} image is too large, at most {max} pixels a side"),
+            RtcError::InvalidGrid { width, depth, samples } => {
+                write!(f, "a {width}x{depth} grid needs at least 2x2 and exactly {} samples, got {samples}", width * depth)
+            },
+            RtcError::InvalidModel(msg) => write!(f, "invalid model: {msg}"),
+        }
+    }
+}
+
+impl Error for RtcError {}
+
+impl From<std::io::Error> for RtcError {
+    fn from(e: std::io::Error) -> Self {
+        RtcError::Io(e.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, RtcError>;
+
+/// returns `value` if it is >= 0, otherwise a `NegativeValue` error naming `field`
+pub(crate) fn non_negative(field: &'static str, value: f32) -> Result<f32> {
+    if value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(RtcError::NegativeValue { field, value })
+    }
+}
+
+/// returns `value` if it is within [min, max], otherwise an `OutOfRange` error naming `field`
+pub(crate) fn in_range(field: &'static str, value: f32, min: f32, max: f32) -> Result<f32> {
+    if (min..=max).contains(&value) {
+        Ok(value)
+    } else {
+        Err(RtcError::OutOfRange { field, value, min, max })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_negative() {
+        assert_eq!(non_negative("ambient", 0.0), Ok(0.0));
+        assert_eq!(non_negative("ambient", 1.5), Ok(1.5));
+        assert_eq!(non_negative("ambient", -0.1), Err(RtcError::NegativeValue { field: "ambient", value: -0.1 }));
+    }
+
+    #[test]
+    fn test_display() {
+        let e = RtcError::NegativeValue { field: "diffuse", value: -1.0 };
+        assert_eq!(e.to_string(), "diffuse must be >= 0, got -1");
+        assert_eq!(RtcError::InvalidMatrixSize(3).to_string(), "transformation must be 4x4, got 3x3");
+        let e = RtcError::OutOfRange { field: "roughness", value: 2.0, min: 0.0, max: 1.0 };
+        assert_eq!(e.to_string(), "roughness must be in [0, 1], got 2");
+    }
+}
@@ -1,12 +1,20 @@
 pub mod coord;
+pub mod error;
 pub mod matrix;
+pub mod microfacet;
+pub mod motion;
 pub mod canvas;
+pub mod animation;
 pub mod camera;
 pub mod light;
 pub mod material;
+pub mod medium;
 pub mod primitives;
 pub mod ray;
 pub mod renderable;
+pub mod sampling;
+#[cfg(feature = "serde")]
+pub mod scene;
 pub mod tex;
 pub mod world;
 use crate::coord::Coord;
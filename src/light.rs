@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
-use crate::{impl_getters, impl_setters};
-use crate::{coord::Coord, renderable::Renderable, tex::{Tex, color::Color}};
+use crate::impl_getters;
+use crate::error::{Result, RtcError, non_negative};
+use crate::{coord::Coord, material::{MaterialParam, ShadingModel}, microfacet, renderable::Renderable, tex::{Tex, color::Color}};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Light {
@@ -10,50 +11,72 @@ pub struct Light {
 }
 
 impl_getters!(Light, pos: Coord, intensity: Color);
-impl_setters!(Light, intensity: Color);
 
 #[allow(dead_code)]
 impl Light {
-    pub fn new(pos: Coord, intensity: Color) -> Self {
-        assert!(intensity.get_r() >= 0.0);
-        assert!(intensity.get_g() >= 0.0);
-        assert!(intensity.get_b() >= 0.0);
-        assert!(intensity.get_a() >= 0.0);
-        Self { pos, intensity }
+    /// returns an error if pos is not a point or any channel of intensity is negative
+    pub fn new(pos: Coord, intensity: Color) -> Result<Self> {
+        let mut out = Self { pos: Coord::point(0.0, 0.0, 0.0), intensity: check_intensity(intensity)? };
+        out.set_pos(pos)?;
+        Ok(out)
     }
     
     pub fn default() -> Self {
         Self { pos: Coord::point(0.0, 0.0, 0.0), intensity: Color::white() }
     }
 
-    // TODO: Dose negative intensity make sense or should this be bounded to >= 0?
-
-    pub fn set_pos(&mut self, pos: Coord) {
-        assert!(pos.is_point());
+    pub fn set_pos(&mut self, pos: Coord) -> Result<()> {
+        if !pos.is_point() {
+            return Err(RtcError::ExpectedPoint);
+        }
         self.pos = pos;
+        Ok(())
+    }
+
+    pub fn set_intensity(&mut self, intensity: Color) -> Result<()> {
+        self.intensity = check_intensity(intensity)?;
+        Ok(())
     }
 }
 
+fn check_intensity(intensity: Color) -> Result<Color> {
+    non_negative("intensity.r", intensity.get_r())?;
+    non_negative("intensity.g", intensity.get_g())?;
+    non_negative("intensity.b", intensity.get_b())?;
+    non_negative("intensity.a", intensity.get_a())?;
+    Ok(intensity)
+}
+
 // TODO: attach this to something, camera maybe?
 pub fn lighting(object: Arc<dyn Renderable>, light: Light, pos: Coord, camv: Coord, normal: Coord, in_shadow: bool) -> Color {
     let effective_color = object.get_color_at(pos) * light.get_intensity();
-    let material = object.get_material();
+    let material = object.get_shared_material();
+    let local_pos = object.get_inverse() * pos;
+    let param = |p| material.get_param_at(p, local_pos);
     let light_v = (light.get_pos() - pos).normalized();
-    let ambient = (effective_color * material.get_ambient()).get_color_at(pos);
+    let ambient = (effective_color * param(MaterialParam::Ambient)).get_color_at(pos);
+    if let ShadingModel::MetallicRoughness { .. } = material.get_model() {
+        if in_shadow {
+            return ambient;
+        }
+        let (metallic, roughness) = (param(MaterialParam::Metallic), param(MaterialParam::Roughness));
+        let reflected = microfacet::reflectance(object.get_color_at(pos), metallic, roughness, normal, camv, light_v);
+        return ambient + reflected * light.get_intensity();
+    }
     let light_dot_normal = light_v.dot(normal);
     //let mut diffuse = Color::black();
     //let mut specular = Color::black();
     if light_dot_normal < 0.0 || in_shadow {
         return ambient;
     }
-    let diffuse = (effective_color * material.get_diffuse() * light_dot_normal).get_color_at(pos);
+    let diffuse = (effective_color * param(MaterialParam::Diffuse) * light_dot_normal).get_color_at(pos);
     let reflect_v = (-light_v).reflect(normal);
     let reflect_dot_cam = reflect_v.dot(camv);
     if reflect_dot_cam < 0.0 {
         return ambient + diffuse;
     }
-    let factor = reflect_dot_cam.powf(material.get_shininess());
-    let specular = light.get_intensity() * material.get_specular() * factor;
+    let factor = reflect_dot_cam.powf(param(MaterialParam::Shininess));
+    let specular = light.get_intensity() * param(MaterialParam::Specular) * factor;
     ambient + diffuse + specular
 }
 #[cfg(test)]
@@ -68,7 +91,7 @@ mod tests {
         assert_eq!(l.pos, Coord::point(0.0, 0.0, 0.0));
         assert_eq!(l.intensity, Color::white());
 
-        let l = Light::new(Coord::point(1.0, 2.0, 3.0), Color::red());
+        let l = Light::new(Coord::point(1.0, 2.0, 3.0), Color::red()).unwrap();
         assert_eq!(l.pos, Coord::point(1.0, 2.0, 3.0));
         assert_eq!(l.intensity, Color::red());
     }
@@ -78,7 +101,7 @@ mod tests {
         let l = Light::default();
         assert_eq!(l.get_pos(), Coord::point(0.0, 0.0, 0.0));
 
-        let l = Light::new(Coord::point(1.0, 2.0, 3.0), Color::white());
+        let l = Light::new(Coord::point(1.0, 2.0, 3.0), Color::white()).unwrap();
         assert_eq!(l.get_pos(), Coord::point(1.0, 2.0, 3.0));
     }
 
@@ -87,7 +110,7 @@ mod tests {
         let l = Light::default();
         assert_eq!(l.get_intensity(), Color::white());
 
-        let l = Light::new(Coord::point(0.0, 0.0, 0.0), Color::green());
+        let l = Light::new(Coord::point(0.0, 0.0, 0.0), Color::green()).unwrap();
         assert_eq!(l.get_intensity(), Color::green());
     }
 
@@ -102,32 +125,32 @@ mod tests {
         // 1
         let camv = Coord::vec(0.0, 0.0, -1.0);
         let normal = Coord::vec(0.0, 0.0, -1.0);
-        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white());
+        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white()).unwrap();
         let r = lighting(s.clone(), light, pos, camv, normal, false);
         assert_eq!(r.get_color_at(pos), Color::new(1.9, 1.9, 1.9, 0.0));
 
         // 2
         let camv = Coord::vec(0.0, 2.0_f32.sqrt()/2.0, -(2.0_f32.sqrt()/2.0));
         let normal = Coord::vec(0.0, 0.0, -1.0);
-        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white());
+        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white()).unwrap();
         let r = lighting(s.clone(), light, pos, camv, normal, false);
         assert_eq!(r.get_color_at(pos), Color::new(1.0, 1.0, 1.0, 0.0));
 
         // 3
         let camv = Coord::vec(0.0, 0.0, -1.0);
         let normal = Coord::vec(0.0, 0.0, -1.0);
-        let light = Light::new(Coord::point(0.0, 10.0, -10.0), Color::white());
+        let light = Light::new(Coord::point(0.0, 10.0, -10.0), Color::white()).unwrap();
         let r = lighting(s.clone(), light, pos, camv, normal, false);
         assert_eq!(r.get_color_at(pos), Color::new(0.7363961, 0.7363961, 0.7363961, 0.0));
 
         // 4
         let camv = Coord::vec(0.0, -(2.0_f32.sqrt())/2.0, -(2.0_f32.sqrt()/2.0));
         let normal = Coord::vec(0.0, 0.0, -1.0);
-        let light = Light::new(Coord::point(0.0, 10.0, -10.0), Color::white());
+        let light = Light::new(Coord::point(0.0, 10.0, -10.0), Color::white()).unwrap();
         let r = lighting(s.clone(), light, pos, camv, normal, false);
         assert_eq!(r.get_color_at(pos), Color::new(1.6363853, 1.6363853, 1.6363853, 0.0));
 
-        let light = Light::new(Coord::point(0.0, 0.0, 10.0), Color::white());
+        let light = Light::new(Coord::point(0.0, 0.0, 10.0), Color::white()).unwrap();
         let r = lighting(s, light, pos, camv, normal,false);
         assert_eq!(r.get_color_at(pos), Color::new(0.1, 0.1, 0.1, 0.0));
     }
@@ -141,7 +164,7 @@ mod tests {
         let pos = Coord::point(0.0, 0.0, 0.0);
         let camv = Coord::vec(0.0, 0.0, -1.0);
         let normal = Coord::vec(0.0, 0.0, -1.0);
-        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white());
+        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white()).unwrap();
         let r = lighting(s, light, pos, camv, normal, true);
         assert_eq!(r.get_color_at(pos), Color::new(0.1, 0.1, 0.1, 0.0));
     }
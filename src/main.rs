@@ -3,6 +3,7 @@ use core::f32::consts::PI;
 
 use rtc::{
     canvas::Canvas,
+    error::RtcError,
     coord::Coord,
     tex::color::Color,
     light::{lighting, Light},
@@ -95,14 +96,18 @@ fn draw_clock(filename: &str) {
     let white = Color::new(1.0, 1.0, 1.0, 0.0);
     let step = f32::consts::PI/6.0;
     for spot in 0..12 {
-        let point = Matrix::translation(50.0, 50.0, 0.0) * Matrix::rotate_z(step * spot as f32) * Matrix::translation(25.0, 0.0, 0.0) * Coord::point(0.0, 0.0, 0.0);
+        let point = Matrix::identity(4)
+            .translate(25.0, 0.0, 0.0)
+            .rotate_z(step * spot as f32)
+            .translate(50.0, 50.0, 0.0)
+            * Coord::point(0.0, 0.0, 0.0);
         clockface.set_pixel(point.get_x() as usize, point.get_y() as usize, white);
         println!("{:?}", point);
     }
     let _ = clockface.to_file(filename);
 }
 
-fn draw_multiple_spheres(filename: &str, spheres: &[Sphere], per_row: usize, resolution: usize) {
+fn draw_multiple_spheres(filename: &str, spheres: &[Sphere], per_row: usize, resolution: usize) -> Result<(), RtcError> {
     let mut canvas = Canvas::new(resolution * per_row, (spheres.len() / per_row) * resolution);
     
     for (idx, orb) in spheres.iter().enumerate() {
@@ -113,8 +118,8 @@ fn draw_multiple_spheres(filename: &str, spheres: &[Sphere], per_row: usize, res
             filename, 
             resolution, 
             orb.clone(),
-            Light::new(Coord::point(10.0, 10.0, -10.0), Color::white())    
-        );
+            Light::new(Coord::point(10.0, 10.0, -10.0), Color::white())?    
+        )?;
         for x in 0..resolution {
             for y in 0..resolution {
                 canvas.set_pixel(
@@ -125,10 +130,11 @@ fn draw_multiple_spheres(filename: &str, spheres: &[Sphere], per_row: usize, res
         }
     }
     let _ = canvas.to_file(filename);
+    Ok(())
 }
 
 
-fn outline_sphere(filename: &str, resolution: usize, orb: Sphere, light: Light) -> Canvas {
+fn outline_sphere(filename: &str, resolution: usize, orb: Sphere, light: Light) -> Result<Canvas, RtcError> {
     let size = resolution;
     let mut canvas = Canvas::new(size, size);
 
@@ -177,12 +183,12 @@ fn outline_sphere(filename: &str, resolution: usize, orb: Sphere, light: Light)
     }
 
     let _ = canvas.to_file(filename);
-    canvas
+    Ok(canvas)
 }
 
 // TODO: make this a test case for lighting func
 #[allow(dead_code)]
-fn draw_test_spheres() {
+fn draw_test_spheres() -> Result<(), RtcError> {
     let orbs = vec![Sphere::default(); 6];
     let small_vals = vec![0.0, 0.1, 0.25, 0.5, 0.75, 1.0];
     let shiny_vals = vec![0.0, 5.0, 10.0, 50.0, 100.0, 500.0];
@@ -191,87 +197,88 @@ fn draw_test_spheres() {
     let mut t_orbs = orbs.clone();
     for i in 0..6 {
         let mut mat = Material::default();
-        mat.set_shininess(shiny_vals[i]);
-        mat.set_specular(small_vals[i]);
+        mat.set_shininess(shiny_vals[i])?;
+        mat.set_specular(small_vals[i])?;
         mat.set_color(Color::red());
         t_orbs[i].set_material(mat);
     }
-    draw_multiple_spheres("spec-pos.ppm", &t_orbs[0..6], 2, 400);
+    draw_multiple_spheres("spec-pos.ppm", &t_orbs[0..6], 2, 400)?;
 
 
     let mut t_orbs = orbs.clone();
     for i in 0..6 {
         let mut mat = Material::default();
-        mat.set_shininess(50.0);
-        mat.set_specular(-small_vals[i]);
+        mat.set_shininess(50.0)?;
+        mat.set_specular(-small_vals[i])?;
         mat.set_color(Color::red());
         t_orbs[i].set_material(mat);
     }
-    draw_multiple_spheres("spec-neg.ppm", &t_orbs[0..6], 2, 400);
+    draw_multiple_spheres("spec-neg.ppm", &t_orbs[0..6], 2, 400)?;
 
 
     let mut t_orbs = orbs.clone();
     for i in 0..6 {
         let mut mat = Material::default();
-        mat.set_diffuse(small_vals[i]);
+        mat.set_diffuse(small_vals[i])?;
         mat.set_color(Color::red());
         t_orbs[i].set_material(mat);
     }
-    draw_multiple_spheres("diff-pos.ppm", &t_orbs[0..6], 2, 400);
+    draw_multiple_spheres("diff-pos.ppm", &t_orbs[0..6], 2, 400)?;
 
 
     let mut t_orbs = orbs.clone();
     for i in 0..6 {
         let mut mat = Material::default();
-        mat.set_diffuse(-small_vals[i]);
+        mat.set_diffuse(-small_vals[i])?;
         mat.set_color(Color::red());
         t_orbs[i].set_material(mat);
     }
-    draw_multiple_spheres("diff-neg.ppm", &t_orbs[0..6], 2, 400);
+    draw_multiple_spheres("diff-neg.ppm", &t_orbs[0..6], 2, 400)?;
 
 
     let mut t_orbs = orbs.clone();
     for i in 0..6 {
         let mut mat = Material::default();
-        mat.set_ambient(small_vals[i]);
+        mat.set_ambient(small_vals[i])?;
         mat.set_color(Color::red());
         t_orbs[i].set_material(mat);
     }
-    draw_multiple_spheres("amb-pos.ppm", &t_orbs[0..6], 2, 400);
+    draw_multiple_spheres("amb-pos.ppm", &t_orbs[0..6], 2, 400)?;
     
     let mut t_orbs = orbs.clone();
     for i in 0..6 {
         let mut mat = Material::default();
-        mat.set_ambient(-small_vals[i]);
+        mat.set_ambient(-small_vals[i])?;
         mat.set_color(Color::red());
         t_orbs[i].set_material(mat);
     }
-    draw_multiple_spheres("amb-neg.ppm", &t_orbs[0..6], 2, 400);
+    draw_multiple_spheres("amb-neg.ppm", &t_orbs[0..6], 2, 400)?;
 
 
     let mut orb = Sphere::default();
     let mut mat = Material::default();
     mat.set_color(Color::red());
     orb.set_material(mat.clone());
-    let light = Light::new(Coord::point(10.0, 10.0, -10.0), Color::blue());
-    outline_sphere("color-add.ppm", 400, orb.clone(), light);
+    let light = Light::new(Coord::point(10.0, 10.0, -10.0), Color::blue())?;
+    outline_sphere("color-add.ppm", 400, orb.clone(), light)?;
 
     mat.set_color(Color::purple());
-    mat.set_diffuse(-0.5);
-    outline_sphere("color-sub.ppm", 400, orb.clone(), light);
+    mat.set_diffuse(-0.5)?;
+    outline_sphere("color-sub.ppm", 400, orb.clone(), light)?;
 
     mat.set_color(Color::red());
-    mat.set_diffuse(0.5);
-    mat.set_ambient(10.0);
-    outline_sphere("color-add-amb.ppm", 400, orb, light);
+    mat.set_diffuse(0.5)?;
+    mat.set_ambient(10.0)?;
+    outline_sphere("color-add-amb.ppm", 400, orb, light)?;
+    Ok(())
 }
 
-fn draw_scene() {
+fn draw_scene() -> Result<(), RtcError> {
     let mut floor = Plane::default();
     //floor.apply_transformation(Matrix::translation(0.0, 0.0, 0.0));
     let mut mat = Material::default();
     //mat.set_color(Color::new(1.0, 0.9, 0.9, 0.0));
-    mat.set_specular(0.0);
+    mat.set_specular(0.0)?;
     mat.set_texture(
         Arc::new(
             Pattern::new_checker(
@@ -279,90 +286,96 @@ fn draw_scene() {
                 Arc::new(Color::black()), 
                 Matrix::translation(-10.0, -10.0, 0.0) // BUG: this translation makes the checker significantly clearer then the identity
                 //Matrix::identity(4)
-            )
+            )?
         )
     );
-    mat.set_reflection(0.125);
+    mat.set_reflection(0.125)?;
     floor.set_material(mat);
 
     let mut left_wall = Plane::default();
     left_wall.set_transformation(
-        Matrix::translation(0.0, 0.0, 5.0) *
-        Matrix::rotate_y(-PI/4.0) *
-        Matrix::rotate_x(PI/2.0)
-    );
+        Matrix::identity(4)
+            .rotate_x(PI/2.0)
+            .rotate_y(-PI/4.0)
+            .translate(0.0, 0.0, 5.0)
+    )?;
     let mut mat = left_wall.get_material();
-    mat.set_texture(Arc::new(Pattern::new_stripe(Arc::new(Color::new(1.0, 0.8, 0.1, 0.0)), Arc::new(Color::white()), Matrix::rotate_y(PI/2.0))));
+    mat.set_texture(Arc::new(Pattern::new_stripe(Arc::new(Color::new(1.0, 0.8, 0.1, 0.0)), Arc::new(Color::white()), Matrix::rotation_y(PI/2.0))?));
     left_wall.set_material(mat.clone());
 
     let mut right_wall = Plane::default();
     right_wall.set_transformation(
-        Matrix::translation(0.0, 0.0, 5.0) *
-            Matrix::rotate_y_degrees(45.0) *
-            Matrix::rotate_x(PI/2.0) *
-            Matrix::rotate_z_degrees(180.0)
-    );
+        Matrix::identity(4)
+            .rotate_z_degrees(180.0)
+            .rotate_x(PI/2.0)
+            .rotate_y_degrees(45.0)
+            .translate(0.0, 0.0, 5.0)
+    )?;
     let mut mirror_mat = Material::default();
     mirror_mat.set_color(Color::white());
-    mirror_mat.set_reflection(1.0);
-    mirror_mat.set_diffuse(0.1);
-    mirror_mat.set_shininess(0.1);
-    mirror_mat.set_specular(0.1);
+    mirror_mat.set_reflection(1.0)?;
+    mirror_mat.set_diffuse(0.1)?;
+    mirror_mat.set_shininess(0.1)?;
+    mirror_mat.set_specular(0.1)?;
     //right_wall.set_material(mirror_mat.clone());
     //left_wall.set_material(mirror_mat);
 
 
     let mut middle = Sphere::default();
-    middle.set_transformation(Matrix::translation(-0.5, 1.0, 0.5));
+    middle.set_transformation(Matrix::translation(-0.5, 1.0, 0.5))?;
     let mut mat = Material::default();
-    mat.set_diffuse(0.7);
-    mat.set_specular(0.3);
+    mat.set_diffuse(0.7)?;
+    mat.set_specular(0.3)?;
     mat.set_texture(
         Arc::new(
             Pattern::new_stripe(
                 Arc::new(Color::red()),
                 Arc::new(Color::green()),
-                Matrix::scaling(0.05, 0.5, 0.5) 
-                    * Matrix::rotate_z(f32::consts::PI / 2.0)
-            )
+                Matrix::identity(4)
+                    .rotate_z(f32::consts::PI / 2.0)
+                    .scale(0.05, 0.5, 0.5)
+            )?
         )
     );
     middle.set_material(mat);
 
     let mut right = Sphere::default();
     right.set_transformation(
-        Matrix::translation(1.5, 0.5, -0.5) *
-        Matrix::scaling(0.5, 0.5, 0.5)
-    );
+        Matrix::identity(4)
+            .scale(0.5, 0.5, 0.5)
+            .translate(1.5, 0.5, -0.5)
+    )?;
     let mut mat = Material::default();
     //mat.set_color(Color::new(0.5, 1.0, 0.1, 0.0));
-    mat.set_diffuse(0.7);
-    mat.set_specular(0.3);
+    mat.set_diffuse(0.7)?;
+    mat.set_specular(0.3)?;
     mat.set_texture(
         Arc::new(
             Pattern::new_gradient(
                 Arc::new(Color::blue()), 
                 Arc::new(Color::red()), 
-                Matrix::scaling(2.0, 2.0, 2.0)
-                    * Matrix::translation(1.5, 1.5, 1.5)
-            )
+                Matrix::identity(4)
+                    .translate(1.5, 1.5, 1.5)
+                    .scale(2.0, 2.0, 2.0)
+            )?
         )
     );
     right.set_material(mat);
 
     let mut left = Sphere::default();
     left.set_transformation(
-        Matrix::translation(-1.5, 0.33, -0.75) *
-        Matrix::scaling(0.33, 0.33, 0.33)
-    );
+        Matrix::identity(4)
+            .scale(0.33, 0.33, 0.33)
+            .translate(-1.5, 0.33, -0.75)
+    )?;
     let mut mat = Material::default();
     mat.set_color(Color::new(1.0, 0.8, 0.1, 0.0));
-    mat.set_diffuse(0.7);
-    mat.set_specular(0.3);
+    mat.set_diffuse(0.7)?;
+    mat.set_specular(0.3)?;
     left.set_material(mat);
 
     let mut world = World::new();
-    let light = Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white());
+    let light = Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white())?;
     world.add_light(light);
 
     world.add_obj(Arc::new(floor));
@@ -376,80 +389,80 @@ fn draw_scene() {
     cam.transform(Matrix::view_transformation(
         Coord::point(0.0, 1.5, -5.0), 
         Coord::point(0.0, 1.0, 0.0), 
-    Coord::vec(0.0, 1.0, 0.0)));
+    Coord::vec(0.0, 1.0, 0.0)))?;
     world.set_max_depth(5);
     let canvas = world.render_world_multi(&cam);
     let _ = canvas.to_file("out.ppm");
-    
+    Ok(())
 }
 
 #[allow(dead_code)]
-fn draw_bubble_sphere() {
+fn draw_bubble_sphere() -> Result<(), RtcError> {
     let half = 5.0;
 
     let mut checker_mat = Material::default();
-    checker_mat.set_specular(0.0);
+    checker_mat.set_specular(0.0)?;
     checker_mat.set_texture(Arc::new(Pattern::new_checker(
         Arc::new(Color::white()),
         Arc::new(Color::black()),
         Matrix::identity(4),
-    )));
+    )?));
 
     // Box: six checker planes enclosing the origin.
     let mut floor = Plane::default();
     floor.set_material(checker_mat.clone());
-    floor.set_transformation(Matrix::translation(0.0, -half, 0.0));
+    floor.set_transformation(Matrix::translation(0.0, -half, 0.0))?;
 
     let mut ceiling = Plane::default();
     ceiling.set_material(checker_mat.clone());
-    ceiling.set_transformation(Matrix::translation(0.0, half, 0.0) * Matrix::rotate_x(PI));
+    ceiling.set_transformation(Matrix::identity(4).rotate_x(PI).translate(0.0, half, 0.0))?;
 
     let mut back = Plane::default();
     back.set_material(checker_mat.clone());
-    back.set_transformation(Matrix::translation(0.0, 0.0, half) * Matrix::rotate_x(PI / 2.0));
+    back.set_transformation(Matrix::identity(4).rotate_x(PI / 2.0).translate(0.0, 0.0, half))?;
 
     let mut front = Plane::default();
     front.set_material(checker_mat.clone());
-    front.set_transformation(Matrix::translation(0.0, 0.0, -half) * Matrix::rotate_x(-PI / 2.0));
+    front.set_transformation(Matrix::identity(4).rotate_x(-PI / 2.0).translate(0.0, 0.0, -half))?;
 
     let mut left_wall = Plane::default();
     left_wall.set_material(checker_mat.clone());
-    left_wall.set_transformation(Matrix::translation(-half, 0.0, 0.0) * Matrix::rotate_z(-PI / 2.0));
+    left_wall.set_transformation(Matrix::identity(4).rotate_z(-PI / 2.0).translate(-half, 0.0, 0.0))?;
 
     let mut right_wall = Plane::default();
     right_wall.set_material(checker_mat.clone());
-    right_wall.set_transformation(Matrix::translation(half, 0.0, 0.0) * Matrix::rotate_z(PI / 2.0));
+    right_wall.set_transformation(Matrix::identity(4).rotate_z(PI / 2.0).translate(half, 0.0, 0.0))?;
 
     // Glass sphere at the center of the box.
     let mut glass = Sphere::default();
     let mut glass_mat = Material::default();
     glass_mat.set_color(Color::black());
-    glass_mat.set_diffuse(0.1);
-    glass_mat.set_ambient(0.0);
-    glass_mat.set_specular(1.0);
-    glass_mat.set_shininess(300.0);
-    glass_mat.set_reflection(0.9);
-    glass_mat.set_transparency(1.0);
-    glass_mat.set_refractive_index(1.5);
+    glass_mat.set_diffuse(0.1)?;
+    glass_mat.set_ambient(0.0)?;
+    glass_mat.set_specular(1.0)?;
+    glass_mat.set_shininess(300.0)?;
+    glass_mat.set_reflection(0.9)?;
+    glass_mat.set_transparency(1.0)?;
+    glass_mat.set_refractive_index(1.5)?;
     glass.set_material(glass_mat);
 
     // Air bubble in the center of the glass sphere.
     let mut air = Sphere::default();
-    air.set_transformation(Matrix::scaling(0.5, 0.5, 0.5));
+    air.set_transformation(Matrix::scaling(0.5, 0.5, 0.5))?;
     let mut air_mat = Material::default();
     air_mat.set_color(Color::black());
-    air_mat.set_diffuse(0.1);
-    air_mat.set_ambient(0.0);
-    air_mat.set_specular(1.0);
-    air_mat.set_shininess(300.0);
-    air_mat.set_reflection(0.9);
-    air_mat.set_transparency(1.0);
-    air_mat.set_refractive_index(3.0);
+    air_mat.set_diffuse(0.1)?;
+    air_mat.set_ambient(0.0)?;
+    air_mat.set_specular(1.0)?;
+    air_mat.set_shininess(300.0)?;
+    air_mat.set_reflection(0.9)?;
+    air_mat.set_transparency(1.0)?;
+    air_mat.set_refractive_index(3.0)?;
     air.set_material(air_mat);
 
     let mut world = World::new();
     // Light above and to the left of the sphere, behind the camera.
-    world.add_light(Light::new(Coord::point(-4.0, 4.0, -4.5), Color::white()));
+    world.add_light(Light::new(Coord::point(-4.0, 4.0, -4.5), Color::white())?);
 
     //world.add_obj(Arc::new(floor));
     //world.add_obj(Arc::new(ceiling));
@@ -466,37 +479,38 @@ fn draw_bubble_sphere() {
         Coord::point(0.0, 0.0, -4.0),
         Coord::point(0.0, 0.0, 0.0),
         Coord::vec(0.0, 1.0, 0.0),
-    ));
+    ))?;
     world.set_max_depth(6);
     let canvas = world.render_world_multi(&cam);
     let _ = canvas.to_file("bubble.ppm");
+    Ok(())
 }
 
 
-fn test_cubes() {
+fn test_cubes() -> Result<(), RtcError> {
     let mut world = World::new();
-    world.add_light(Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white()));
+    world.add_light(Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white())?);
 
     // Checkered floor plane.
     let mut floor = Plane::default();
     let mut floor_mat = Material::default();
-    floor_mat.set_specular(0.0);
-    floor_mat.set_reflection(0.1);
+    floor_mat.set_specular(0.0)?;
+    floor_mat.set_reflection(0.1)?;
     floor_mat.set_texture(Arc::new(Pattern::new_checker(
         Arc::new(Color::white()),
         Arc::new(Color::black()),
         Matrix::identity(4),
-    )));
+    )?));
     floor.set_material(floor_mat);
     world.add_obj(Arc::new(floor));
 
     // Back wall plane to give the scene a backdrop.
     let mut back = Plane::default();
     let mut back_mat = Material::default();
-    back_mat.set_specular(0.0);
+    back_mat.set_specular(0.0)?;
     back_mat.set_color(Color::new(0.6, 0.6, 0.8, 0.0));
     back.set_material(back_mat);
-    back.set_transformation(Matrix::translation(0.0, 0.0, 6.0) * Matrix::rotate_x(PI / 2.0));
+    back.set_transformation(Matrix::identity(4).rotate_x(PI / 2.0).translate(0.0, 0.0, 6.0))?;
     world.add_obj(Arc::new(back));
 
     // Enclosing walls so reflective surfaces always have something to bounce.
@@ -505,103 +519,105 @@ fn test_cubes() {
     // Front wall, behind the camera.
     let mut front = Plane::default();
     let mut front_mat = Material::default();
-    front_mat.set_specular(0.0);
+    front_mat.set_specular(0.0)?;
     front_mat.set_color(Color::new(0.8, 0.7, 0.6, 0.0));
     front.set_material(front_mat);
-    front.set_transformation(Matrix::translation(0.0, 0.0, -12.0) * Matrix::rotate_x(PI / 2.0));
+    front.set_transformation(Matrix::identity(4).rotate_x(PI / 2.0).translate(0.0, 0.0, -12.0))?;
     world.add_obj(Arc::new(front));
 
     // Left wall.
     let mut left = Plane::default();
     let mut left_mat = Material::default();
-    left_mat.set_specular(0.0);
+    left_mat.set_specular(0.0)?;
     left_mat.set_color(Color::new(0.7, 0.5, 0.5, 0.0));
     left.set_material(left_mat);
-    left.set_transformation(Matrix::translation(-12.0, 0.0, 0.0) * Matrix::rotate_z(PI / 2.0));
+    left.set_transformation(Matrix::identity(4).rotate_z(PI / 2.0).translate(-12.0, 0.0, 0.0))?;
     world.add_obj(Arc::new(left));
 
     // Right wall.
     let mut right = Plane::default();
     let mut right_mat = Material::default();
-    right_mat.set_specular(0.0);
+    right_mat.set_specular(0.0)?;
     right_mat.set_color(Color::new(0.5, 0.7, 0.5, 0.0));
     right.set_material(right_mat);
-    right.set_transformation(Matrix::translation(12.0, 0.0, 0.0) * Matrix::rotate_z(PI / 2.0));
+    right.set_transformation(Matrix::identity(4).rotate_z(PI / 2.0).translate(12.0, 0.0, 0.0))?;
     world.add_obj(Arc::new(right));
 
     // Ceiling.
     let mut ceiling = Plane::default();
     let mut ceiling_mat = Material::default();
-    ceiling_mat.set_specular(0.0);
+    ceiling_mat.set_specular(0.0)?;
     ceiling_mat.set_color(Color::new(0.6, 0.6, 0.7, 0.0));
     ceiling.set_material(ceiling_mat);
-    ceiling.set_transformation(Matrix::translation(0.0, 12.0, 0.0));
+    ceiling.set_transformation(Matrix::translation(0.0, 12.0, 0.0))?;
     world.add_obj(Arc::new(ceiling));
 
     // Red cube, axis-aligned, sitting on the floor.
     let mut cube_a = Cube::default();
     let mut mat_a = Material::default();
     mat_a.set_color(Color::red());
-    mat_a.set_diffuse(0.7);
-    mat_a.set_specular(0.3);
+    mat_a.set_diffuse(0.7)?;
+    mat_a.set_specular(0.3)?;
     cube_a.set_material(mat_a);
     cube_a.set_transformation(
-        Matrix::translation(-2.5, 1.0, 0.5) * Matrix::scaling(1.0, 1.0, 1.0),
-    );
+        Matrix::identity(4).scale(1.0, 1.0, 1.0).translate(-2.5, 1.0, 0.5),
+    )?;
     world.add_obj(Arc::new(cube_a));
 
     // Green cube, rotated and squashed.
     let mut cube_b = Cube::default();
     let mut mat_b = Material::default();
     mat_b.set_color(Color::green());
-    mat_b.set_reflection(0.3);
+    mat_b.set_reflection(0.3)?;
     cube_b.set_material(mat_b);
     cube_b.set_transformation(
-        Matrix::translation(2.5, 0.75, 0.0)
-            * Matrix::rotate_y(PI / 4.0)
-            * Matrix::scaling(0.75, 0.75, 0.75),
-    );
+        Matrix::identity(4)
+            .scale(0.75, 0.75, 0.75)
+            .rotate_y(PI / 4.0)
+            .translate(2.5, 0.75, 0.0),
+    )?;
     world.add_obj(Arc::new(cube_b));
 
     // Striped tall cube in the back.
     let mut cube_c = Cube::default();
     let mut mat_c = Material::default();
-    mat_c.set_specular(0.3);
+    mat_c.set_specular(0.3)?;
     mat_c.set_texture(Arc::new(Pattern::new_stripe(
         Arc::new(Color::new(1.0, 0.8, 0.1, 0.0)),
         Arc::new(Color::white()),
         Matrix::scaling(0.25, 0.25, 0.25),
-    )));
+    )?));
     cube_c.set_material(mat_c);
     cube_c.set_transformation(
-        Matrix::translation(0.0, 1.5, 3.0)
-            * Matrix::rotate_y(PI / 6.0)
-            * Matrix::scaling(0.6, 1.5, 0.6),
-    );
+        Matrix::identity(4)
+            .scale(0.6, 1.5, 0.6)
+            .rotate_y(PI / 6.0)
+            .translate(0.0, 1.5, 3.0),
+    )?;
     world.add_obj(Arc::new(cube_c));
 
     // Blue sphere in front, between the cubes.
     let mut sphere_a = Sphere::default();
     let mut sphere_mat_a = Material::default();
     sphere_mat_a.set_color(Color::blue());
-    sphere_mat_a.set_diffuse(0.7);
-    sphere_mat_a.set_specular(0.3);
+    sphere_mat_a.set_diffuse(0.7)?;
+    sphere_mat_a.set_specular(0.3)?;
     sphere_a.set_material(sphere_mat_a);
-    sphere_a.set_transformation(Matrix::translation(0.0, 1.0, -1.5));
+    sphere_a.set_transformation(Matrix::translation(0.0, 1.0, -1.5))?;
     world.add_obj(Arc::new(sphere_a));
 
     // Small reflective sphere resting on the green cube's side.
     let mut sphere_b = Sphere::default();
     let mut sphere_mat_b = Material::default();
     sphere_mat_b.set_color(Color::purple());
-    sphere_mat_b.set_diffuse(0.7);
-    sphere_mat_b.set_specular(0.3);
-    sphere_mat_b.set_shininess(100.0);
-    sphere_mat_b.set_reflection(0.15);
+    sphere_mat_b.set_diffuse(0.7)?;
+    sphere_mat_b.set_specular(0.3)?;
+    sphere_mat_b.set_shininess(100.0)?;
+    sphere_mat_b.set_reflection(0.15)?;
     sphere_b.set_material(sphere_mat_b);
     sphere_b.set_transformation(
-        Matrix::translation(-2.5, 2.5, 0.5) * Matrix::scaling(0.5, 0.5, 0.5),
-    );
+        Matrix::identity(4).scale(0.5, 0.5, 0.5).translate(-2.5, 2.5, 0.5),
+    )?;
     world.add_obj(Arc::new(sphere_b));
 
     let mut cam = Camera::new(1200, 1200, PI / 3.0);
@@ -609,13 +625,14 @@ fn test_cubes() {
         Coord::point(0.0, 2.5, -7.0),
         Coord::point(0.0, 1.0, 0.0),
         Coord::vec(0.0, 1.0, 0.0),
-    ));
+    ))?;
     world.set_max_depth(5);
     let canvas = world.render_world_multi(&cam);
     let _ = canvas.to_file("cubes.ppm");
+    Ok(())
 }
 
-fn main() {
+fn main() -> Result<(), RtcError> {
     //let mut env = Environment::new(-0.01, -0.1, 900, 550);
     //env.add_shot(Shot::new(Coord::point(0.0, 1.0, 0.0), Coord::vec(5.0, 8.2, 0.0) * 11.25));
     //println!("{:?}", env);
@@ -627,7 +644,7 @@ fn main() {
     //draw_clock("clock.ppm");
     //let _ = outline_sphere("sphere.ppm", 400, Sphere::default());
 
-    //draw_test_spheres();
-    //draw_bubble_sphere();
-    test_cubes();
+    //draw_test_spheres()?;
+    //draw_bubble_sphere()?;
+    test_cubes()
 }
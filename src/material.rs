@@ -1,7 +1,78 @@
 use std::sync::Arc;
 
 use crate::impl_getters;
-use crate::{coord::Coord, tex::{Tex, TextureType, color::Color}};
+use crate::error::{Result, in_range, non_negative};
+use crate::{coord::Coord, tex::{Tex, TextureType, color::Color, normal::NormalPerturbation, scalar::ScalarMap}};
+
+/// how a material responds to direct light
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum ShadingModel {
+    /// ambient + diffuse + specular highlight from the material's Phong terms
+    #[default]
+    Phong,
+    /// physically based GGX microfacet model (see `microfacet`), both values in [0, 1]
+    ///
+    /// the texture is the base color and `diffuse`, `specular` and `shininess` are ignored.
+    /// `ambient` still adds a flat fill, and `reflection` and `transparency` still add mirror
+    /// reflection and refraction on top
+    MetallicRoughness { metallic: f32, roughness: f32 },
+}
+
+/// a number on a `Material`, for animating it or driving it with a `ScalarMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MaterialParam {
+    Ambient,
+    Diffuse,
+    Specular,
+    Shininess,
+    Reflection,
+    Transparency,
+    RefractiveIndex,
+    /// only used by `ShadingModel::MetallicRoughness`
+    Metallic,
+    /// only used by `ShadingModel::MetallicRoughness`
+    Roughness,
+}
+
+/// built in materials with measured or typical real world values, see `Material::preset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// clear dielectric with an index of 1.5
+    Glass,
+    /// clear dielectric with an index of 1.333
+    Water,
+    /// clear dielectric with an index of 2.417
+    Diamond,
+    /// polished metal, nearly a mirror
+    Chrome,
+    /// slightly brushed yellow metal
+    Gold,
+    /// glossy white dielectric, tint it with `set_color`
+    Plastic,
+    /// rough grey-beige surface with no highlight, for checking shapes and lighting
+    MatteClay,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 7] = [Preset::Glass, Preset::Water, Preset::Diamond, Preset::Chrome, Preset::Gold, Preset::Plastic, Preset::MatteClay];
+
+    /// snake case name, what `World::add_presets` registers it under
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Glass => "glass",
+            Preset::Water => "water",
+            Preset::Diamond => "diamond",
+            Preset::Chrome => "chrome",
+            Preset::Gold => "gold",
+            Preset::Plastic => "plastic",
+            Preset::MatteClay => "matte_clay",
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Material {
@@ -13,6 +84,13 @@ pub struct Material {
     refractive_index: f32,
     transparency: f32,
     texture: Arc<dyn Tex>, 
+    model: ShadingModel,
+    maps: Vec<(MaterialParam, ScalarMap)>,  // at most one per param
+    normal: Option<NormalPerturbation>,
+    absorption: Color,  // Beer-Lambert coefficient per unit of distance, per channel
+    thin_walled: bool,
+    emission: Color,
+    emission_strength: f32,
 }
 
 impl_getters!(Material,
@@ -22,7 +100,12 @@ impl_getters!(Material,
     shininess: f32,
     reflection: f32,
     refractive_index: f32,
-    transparency: f32
+    transparency: f32,
+    model: ShadingModel,
+    absorption: Color,
+    thin_walled: bool,
+    emission: Color,
+    emission_strength: f32
 );
 
 
@@ -30,53 +113,270 @@ impl_getters!(Material,
 impl Material {
     /// ambient, diffuse, specular values should be 0 <= x <= 1
     /// shininess should be 10 <= x <= 200
-    pub fn new(ambient: f32, diffuse: f32, specular: f32, shininess: f32, reflection: f32, refractive_index: f32, transparency: f32, texture: Arc<dyn Tex>) -> Self {
-        assert!(ambient >= 0.0);
-        assert!(ambient >= 0.0);
-        assert!(specular >= 0.0);
-        assert!(shininess >= 0.0);
-        assert!(reflection >= 0.0);
-        assert!(transparency >= 0.0);
-        Self {ambient, diffuse, specular, shininess, reflection, refractive_index, transparency, texture}
+    /// returns an error if any of the numeric values is negative
+    pub fn new(ambient: f32, diffuse: f32, specular: f32, shininess: f32, reflection: f32, refractive_index: f32, transparency: f32, texture: Arc<dyn Tex>) -> Result<Self> {
+        Ok(Self {
+            ambient: non_negative("ambient", ambient)?,
+            diffuse: non_negative("diffuse", diffuse)?,
+            specular: non_negative("specular", specular)?,
+            shininess: non_negative("shininess", shininess)?,
+            reflection: non_negative("reflection", reflection)?,
+            refractive_index: non_negative("refractive_index", refractive_index)?,
+            transparency: non_negative("transparency", transparency)?,
+            texture,
+            model: ShadingModel::Phong,
+            maps: Vec::new(),
+            normal: None,
+            absorption: Color::black(),
+            thin_walled: false,
+            emission: Color::black(),
+            emission_strength: 0.0
+        })
     }
 
     pub fn default() -> Self {
-        Self { ambient: 0.1, diffuse: 0.9, specular: 0.9, shininess: 200.0, reflection: 0.0, refractive_index: 1.0, transparency: 0.0, texture: Arc::new(Color::white()) }
+        Self { ambient: 0.1, diffuse: 0.9, specular: 0.9, shininess: 200.0, reflection: 0.0, refractive_index: 1.0, transparency: 0.0, texture: Arc::new(Color::white()), model: ShadingModel::Phong, maps: Vec::new(), normal: None, absorption: Color::black(), thin_walled: false, emission: Color::black(), emission_strength: 0.0 }
+    }
+
+    /// physically based material with the given base color, metallic and roughness in [0, 1]
+    pub fn metallic_roughness(base_color: Color, metallic: f32, roughness: f32) -> Result<Self> {
+        let mut out = Self { texture: Arc::new(base_color), ..Self::default() };
+        out.set_model(ShadingModel::MetallicRoughness { metallic, roughness })?;
+        Ok(out)
+    }
+
+    /// a fully transparent surface that only reflects what Fresnel says it should, with no
+    /// diffuse color of its own. returns an error if `refractive_index` is negative
+    pub fn dielectric(refractive_index: f32) -> Result<Self> {
+        Ok(Self {
+            ambient: 0.0,
+            diffuse: 0.0,
+            specular: 1.0,
+            shininess: 300.0,
+            reflection: 1.0,
+            transparency: 1.0,
+            refractive_index: non_negative("refractive_index", refractive_index)?,
+            ..Self::default()
+        })
+    }
+
+    /// one of the built in materials, metals use the metallic-roughness model with their measured
+    /// reflectance as the base color
+    pub fn preset(preset: Preset) -> Self {
+        let metal = |r, g, b, roughness| Self::metallic_roughness(Color::new(r, g, b, 0.0), 1.0, roughness);
+        let out = match preset {
+            Preset::Glass => Self::dielectric(1.5),
+            Preset::Water => Self::dielectric(1.333),
+            Preset::Diamond => Self::dielectric(2.417),
+            Preset::Chrome => metal(0.55, 0.56, 0.55, 0.05),
+            Preset::Gold => metal(1.0, 0.71, 0.29, 0.2),
+            Preset::Plastic => Self::metallic_roughness(Color::white(), 0.0, 0.3),
+            Preset::MatteClay => Ok(Self { specular: 0.0, texture: Arc::new(Color::new(0.8, 0.75, 0.7, 0.0)), ..Self::default() }),
+        };
+        out.expect("presets are built from valid values")
+    }
+
+    /// a surface that only glows, `color` times `strength`, and reflects nothing
+    pub fn emissive(color: Color, strength: f32) -> Result<Self> {
+        let mut out = Self { ambient: 0.0, diffuse: 0.0, specular: 0.0, texture: Arc::new(color), ..Self::default() };
+        out.set_emission(color, strength)?;
+        Ok(out)
+    }
+
+    /// light given off by the surface on its own, on top of any it reflects. channels and
+    /// strength must be >= 0, strength can go over 1 for surfaces brighter than white
+    pub fn set_emission(&mut self, color: Color, strength: f32) -> Result<()> {
+        non_negative("emission red", color.get_r())?;
+        non_negative("emission green", color.get_g())?;
+        non_negative("emission blue", color.get_b())?;
+        self.emission_strength = non_negative("emission strength", strength)?;
+        self.emission = color;
+        Ok(())
+    }
+
+    /// light given off, emission color times strength
+    pub fn emitted(&self) -> Color {
+        self.emission * self.emission_strength
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        let e = self.emitted();
+        e.get_r() > 0.0 || e.get_g() > 0.0 || e.get_b() > 0.0
     }
 
-    pub fn set_transparency(&mut self, transparency: f32) {
-        assert!(transparency >= 0.0);
-        self.transparency = transparency;
+    /// glass that light leaving it after travelling `distance` through it is tinted `color`, see
+    /// `set_absorption_color`
+    pub fn tinted_glass(color: Color, distance: f32) -> Result<Self> {
+        let mut out = Self::preset(Preset::Glass);
+        out.set_absorption_color(color, distance)?;
+        Ok(out)
     }
 
-    pub fn set_refractive_index(&mut self, refractive_index: f32) {
-        assert!(refractive_index >= 0.0);
-        self.refractive_index = refractive_index;
+    /// how much light is absorbed per unit of distance travelled inside the object, per channel,
+    /// black (the default) absorbs nothing. each channel must be >= 0
+    pub fn set_absorption(&mut self, absorption: Color) -> Result<()> {
+        non_negative("absorption red", absorption.get_r())?;
+        non_negative("absorption green", absorption.get_g())?;
+        non_negative("absorption blue", absorption.get_b())?;
+        self.absorption = absorption;
+        Ok(())
     }
 
-    pub fn set_ambient(&mut self, ambient: f32) {
-        assert!(ambient >= 0.0);
-        self.ambient = ambient;
+    /// sets the absorption so white light is left as `color` after travelling `distance` inside,
+    /// the easier way to pick a tint. channels must be in [0, 1], 0 is clamped to a tiny value
+    pub fn set_absorption_color(&mut self, color: Color, distance: f32) -> Result<()> {
+        let distance = in_range("distance", distance, f32::EPSILON, f32::MAX)?;
+        let coefficient = |field, c| -> Result<f32> {
+            Ok(-in_range(field, c, 0.0, 1.0)?.max(1e-6).ln() / distance)
+        };
+        self.absorption = Color::new(
+            coefficient("absorption red", color.get_r())?,
+            coefficient("absorption green", color.get_g())?,
+            coefficient("absorption blue", color.get_b())?,
+            0.0
+        );
+        Ok(())
     }
 
-    pub fn set_diffuse(&mut self, diffuse: f32) {
-        assert!(diffuse >= 0.0);
-        self.diffuse = diffuse;
+    /// fraction of light left per channel after travelling `distance` inside, by Beer-Lambert
+    pub fn transmittance(&self, distance: f32) -> Color {
+        beer_lambert(self.absorption, distance)
     }
 
-    pub fn set_specular(&mut self, specular: f32) {
-        assert!(specular >= 0.0);
-        self.specular = specular;
+    /// thin walled surfaces, like window panes or soap bubbles, let refracted rays through without
+    /// bending them and don't change the refractive index seen by anything inside them. they still
+    /// reflect by Fresnel and absorb light crossing them
+    pub fn set_thin_walled(&mut self, thin_walled: bool) {
+        self.thin_walled = thin_walled;
     }
 
-    pub fn set_shininess(&mut self, shininess: f32) {
-        assert!(shininess >= 0.0);
-        self.shininess = shininess;
+    pub fn set_transparency(&mut self, transparency: f32) -> Result<()> {
+        self.transparency = non_negative("transparency", transparency)?;
+        Ok(())
     }
 
-    pub fn set_reflection(&mut self, reflection: f32) {
-        assert!(reflection >= 0.0);
-        self.reflection = reflection;
+    pub fn set_refractive_index(&mut self, refractive_index: f32) -> Result<()> {
+        self.refractive_index = non_negative("refractive_index", refractive_index)?;
+        Ok(())
+    }
+
+    pub fn set_ambient(&mut self, ambient: f32) -> Result<()> {
+        self.ambient = non_negative("ambient", ambient)?;
+        Ok(())
+    }
+
+    pub fn set_diffuse(&mut self, diffuse: f32) -> Result<()> {
+        self.diffuse = non_negative("diffuse", diffuse)?;
+        Ok(())
+    }
+
+    pub fn set_specular(&mut self, specular: f32) -> Result<()> {
+        self.specular = non_negative("specular", specular)?;
+        Ok(())
+    }
+
+    pub fn set_shininess(&mut self, shininess: f32) -> Result<()> {
+        self.shininess = non_negative("shininess", shininess)?;
+        Ok(())
+    }
+
+    pub fn set_reflection(&mut self, reflection: f32) -> Result<()> {
+        self.reflection = non_negative("reflection", reflection)?;
+        Ok(())
+    }
+
+    /// returns an error if metallic or roughness is outside [0, 1]
+    pub fn set_model(&mut self, model: ShadingModel) -> Result<()> {
+        if let ShadingModel::MetallicRoughness { metallic, roughness } = model {
+            in_range("metallic", metallic, 0.0, 1.0)?;
+            in_range("roughness", roughness, 0.0, 1.0)?;
+        }
+        self.model = model;
+        Ok(())
+    }
+
+    /// the constant value of `param`, metallic and roughness are 0 and 1 for Phong materials
+    pub fn get_param(&self, param: MaterialParam) -> f32 {
+        match param {
+            MaterialParam::Ambient => self.ambient,
+            MaterialParam::Diffuse => self.diffuse,
+            MaterialParam::Specular => self.specular,
+            MaterialParam::Shininess => self.shininess,
+            MaterialParam::Reflection => self.reflection,
+            MaterialParam::Transparency => self.transparency,
+            MaterialParam::RefractiveIndex => self.refractive_index,
+            MaterialParam::Metallic => match self.model {
+                ShadingModel::MetallicRoughness { metallic, .. } => metallic,
+                ShadingModel::Phong => 0.0,
+            },
+            MaterialParam::Roughness => match self.model {
+                ShadingModel::MetallicRoughness { roughness, .. } => roughness,
+                ShadingModel::Phong => 1.0,
+            },
+        }
+    }
+
+    /// sets the constant value of `param`, with the same checks as its own setter
+    ///
+    /// setting metallic or roughness on a Phong material switches it to `MetallicRoughness`
+    pub fn set_param(&mut self, param: MaterialParam, value: f32) -> Result<()> {
+        match param {
+            MaterialParam::Ambient => self.set_ambient(value),
+            MaterialParam::Diffuse => self.set_diffuse(value),
+            MaterialParam::Specular => self.set_specular(value),
+            MaterialParam::Shininess => self.set_shininess(value),
+            MaterialParam::Reflection => self.set_reflection(value),
+            MaterialParam::Transparency => self.set_transparency(value),
+            MaterialParam::RefractiveIndex => self.set_refractive_index(value),
+            MaterialParam::Metallic => self.set_model(ShadingModel::MetallicRoughness {
+                metallic: value,
+                roughness: self.get_param(MaterialParam::Roughness),
+            }),
+            MaterialParam::Roughness => self.set_model(ShadingModel::MetallicRoughness {
+                metallic: self.get_param(MaterialParam::Metallic),
+                roughness: value,
+            }),
+        }
+    }
+
+    /// scales `param` by `map` over the surface, e.g. a reflection of 1 with a checker map only
+    /// reflects on the white squares. replaces any map already on `param`
+    ///
+    /// refractive index maps are ignored, the index is needed where rays enter and leave objects
+    /// rather than at a single point
+    pub fn set_map(&mut self, param: MaterialParam, map: ScalarMap) {
+        self.remove_map(param);
+        self.maps.push((param, map));
+    }
+
+    pub fn get_map(&self, param: MaterialParam) -> Option<ScalarMap> {
+        self.maps.iter().find(|(p, _)| *p == param).map(|(_, map)| map.clone())
+    }
+
+    pub fn remove_map(&mut self, param: MaterialParam) {
+        self.maps.retain(|(p, _)| *p != param);
+    }
+
+    pub fn get_maps(&self) -> Vec<(MaterialParam, ScalarMap)> {
+        self.maps.clone()
+    }
+
+    /// `param` at an object space pos, its constant value times its map if it has one
+    pub fn get_param_at(&self, param: MaterialParam, pos: Coord) -> f32 {
+        let value = self.get_param(param);
+        match self.maps.iter().find(|(p, _)| *p == param) {
+            Some((_, map)) => value * map.value_at(pos),
+            None => value,
+        }
+    }
+
+    pub fn get_normal(&self) -> Option<NormalPerturbation> {
+        self.normal.clone()
+    }
+
+    /// tilts the normal of surfaces using this material, `None` keeps the geometric normal
+    pub fn set_normal(&mut self, normal: Option<NormalPerturbation>) {
+        self.normal = normal;
     }
 
     pub fn set_texture(&mut self, tex: Arc<dyn Tex>) {
@@ -102,12 +402,28 @@ impl Material {
     }
 }
 
+/// fraction of light left per channel after `distance` through a medium with coefficients `absorption`
+pub(crate) fn beer_lambert(absorption: Color, distance: f32) -> Color {
+    let a = absorption;
+    Color::new((-a.get_r() * distance).exp(), (-a.get_g() * distance).exp(), (-a.get_b() * distance).exp(), 0.0)
+}
+
 impl PartialEq for Material {
     fn eq(&self, other: &Self) -> bool {
         self.get_ambient() == other.get_ambient() &&
         self.get_diffuse() == other.get_diffuse() &&
         self.get_shininess() == other.get_shininess() &&
         self.get_specular() == other.get_specular() &&
+        self.get_reflection() == other.get_reflection() &&
+        self.get_transparency() == other.get_transparency() &&
+        self.get_refractive_index() == other.get_refractive_index() &&
+        self.get_model() == other.get_model() &&
+        self.maps.len() == other.maps.len() &&
+        self.maps.iter().all(|(param, map)| other.get_map(*param).as_ref() == Some(map)) &&
+        self.normal == other.normal &&
+        self.absorption == other.absorption &&
+        self.thin_walled == other.thin_walled &&
+        self.emitted() == other.emitted() &&
         self.get_texture().compare(other.get_texture())
     }
 }
@@ -120,7 +436,7 @@ mod tests {
 
     #[test]
     fn test_new() {
-        let m = Material::new(1.0, 0.0, 1.0, 27.0, 0.0, 1.0, 0.0, Arc::new(Color::white()));
+        let m = Material::new(1.0, 0.0, 1.0, 27.0, 0.0, 1.0, 0.0, Arc::new(Color::white())).unwrap();
         assert_eq!(m.ambient, 1.0);
         assert_eq!(m.diffuse, 0.0);
         assert_eq!(m.specular, 1.0);
@@ -149,16 +465,168 @@ mod tests {
             0.0,
             1.0,
             0.0,
-            Arc::new(Pattern::new_stripe(Arc::new(Color::black()), Arc::new(Color::white()), Matrix::identity(4))));
+            Arc::new(Pattern::new_stripe(Arc::new(Color::black()), Arc::new(Color::white()), Matrix::identity(4)).unwrap())).unwrap();
         let mut o = Sphere::default();
         o.set_material(m);
         let o = Arc::new(o);
         let eyev = Coord::vec(0.0, 0.0, -1.0);
         let normalv = Coord::vec(0.0, 0.0, -1.0);
-        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white());
+        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white()).unwrap();
         let c1 = lighting(o.clone(), light, Coord::point(0.9, 0.0, 0.0), eyev, normalv, false);
         let c2 = lighting(o, light, Coord::point(1.1, 0.0, 0.0), eyev, normalv, false);
         assert_eq!(c1, Color::black());
         assert_eq!(c2, Color::white());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_presets() {
+        for (preset, index) in [(Preset::Glass, 1.5), (Preset::Water, 1.333), (Preset::Diamond, 2.417)] {
+            let m = Material::preset(preset);
+            assert_eq!(m.get_refractive_index(), index);
+            assert_eq!(m.get_transparency(), 1.0);
+            assert_eq!(m.get_reflection(), 1.0);
+            assert_eq!(m.get_diffuse(), 0.0);
+        }
+        assert_eq!(Material::preset(Preset::Chrome).get_model(), ShadingModel::MetallicRoughness { metallic: 1.0, roughness: 0.05 });
+        assert_eq!(Material::preset(Preset::Gold).get_color(), Color::new(1.0, 0.71, 0.29, 0.0));
+        assert_eq!(Material::preset(Preset::Plastic).get_model(), ShadingModel::MetallicRoughness { metallic: 0.0, roughness: 0.3 });
+        assert_eq!(Material::preset(Preset::MatteClay).get_specular(), 0.0);
+        let names: Vec<_> = Preset::ALL.iter().map(|p| p.name()).collect();
+        assert_eq!(names, ["glass", "water", "diamond", "chrome", "gold", "plastic", "matte_clay"]);
+        assert!(Material::dielectric(-1.0).is_err());
+    }
+
+    #[test]
+    fn test_eq() {
+        assert_ne!(Material::preset(Preset::Glass), Material::preset(Preset::Water));
+        let mut m = Material::default();
+        m.set_reflection(0.5).unwrap();
+        assert_ne!(m, Material::default());
+        let mut m = Material::default();
+        m.set_transparency(0.5).unwrap();
+        assert_ne!(m, Material::default());
+    }
+
+    #[test]
+    fn test_metallic_roughness() {
+        let m = Material::metallic_roughness(Color::red(), 1.0, 0.25).unwrap();
+        assert_eq!(m.get_model(), ShadingModel::MetallicRoughness { metallic: 1.0, roughness: 0.25 });
+        assert_eq!(m.get_color(), Color::red());
+        assert_eq!(Material::default().get_model(), ShadingModel::Phong);
+        assert_ne!(m, Material { texture: Arc::new(Color::red()), ..Material::default() });
+        assert_eq!(
+            Material::metallic_roughness(Color::red(), 1.5, 0.25).err(),
+            Some(crate::error::RtcError::OutOfRange { field: "metallic", value: 1.5, min: 0.0, max: 1.0 })
+        );
+        let mut m = Material::default();
+        assert!(m.set_model(ShadingModel::MetallicRoughness { metallic: 0.0, roughness: -0.1 }).is_err());
+        assert_eq!(m.get_model(), ShadingModel::Phong);
+    }
+
+    #[test]
+    fn test_absorption() {
+        let m = Material::default();
+        assert_eq!(m.transmittance(10.0), Color::new(1.0, 1.0, 1.0, 0.0));
+        assert!(!m.get_thin_walled());
+
+        let m = Material::tinted_glass(Color::new(0.5, 1.0, 0.25, 0.0), 2.0).unwrap();
+        assert_eq!(m.transmittance(2.0), Color::new(0.5, 1.0, 0.25, 0.0));
+        assert_eq!(m.transmittance(4.0), Color::new(0.25, 1.0, 0.0625, 0.0));
+        assert_eq!(m.transmittance(0.0), Color::new(1.0, 1.0, 1.0, 0.0));
+        assert_ne!(m, Material::preset(Preset::Glass));
+
+        let mut m = Material::preset(Preset::Glass);
+        assert!(m.set_absorption(Color::new(0.1, -0.1, 0.0, 0.0)).is_err());
+        assert!(m.set_absorption_color(Color::new(1.5, 1.0, 1.0, 0.0), 1.0).is_err());
+        assert!(m.set_absorption_color(Color::white(), 0.0).is_err());
+        assert_eq!(m.get_absorption(), Color::black());
+        m.set_absorption(Color::new(std::f32::consts::LN_2, 0.0, 0.0, 0.0)).unwrap();
+        assert_eq!(m.transmittance(1.0), Color::new(0.5, 1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_emission() {
+        let m = Material::default();
+        assert_eq!(m.emitted(), Color::black());
+        assert!(!m.is_emissive());
+        let m = Material::emissive(Color::new(1.0, 0.5, 0.0, 0.0), 4.0).unwrap();
+        assert_eq!(m.emitted(), Color::new(4.0, 2.0, 0.0, 0.0));
+        assert!(m.is_emissive());
+        assert_eq!(m.get_diffuse(), 0.0);
+        let mut m = Material::default();
+        assert!(m.set_emission(Color::white(), -1.0).is_err());
+        assert!(m.set_emission(Color::new(-0.5, 0.0, 0.0, 0.0), 1.0).is_err());
+        assert!(!m.is_emissive());
+    }
+
+    #[test]
+    fn test_metallic_roughness_lighting() {
+        let mut o = Sphere::default();
+        o.set_material(Material::metallic_roughness(Color::new(0.2, 0.4, 0.8, 0.0), 0.0, 0.5).unwrap());
+        let o = Arc::new(o);
+        let eyev = Coord::vec(0.0, 0.0, -1.0);
+        let normalv = Coord::vec(0.0, 0.0, -1.0);
+        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white()).unwrap();
+        let pos = Coord::point(0.0, 0.0, 0.0);
+        let ambient = Color::new(0.2, 0.4, 0.8, 0.0) * 0.1;
+        assert_eq!(lighting(o.clone(), light, pos, eyev, normalv, true), ambient);
+        let lit = lighting(o.clone(), light, pos, eyev, normalv, false);
+        let expected = ambient + crate::microfacet::reflectance(Color::new(0.2, 0.4, 0.8, 0.0), 0.0, 0.5, normalv, eyev, normalv);
+        assert_eq!(lit, expected);
+        // light behind the surface only leaves the ambient term
+        let behind = Light::new(Coord::point(0.0, 0.0, 10.0), Color::white()).unwrap();
+        assert_eq!(lighting(o, behind, pos, eyev, normalv, false), ambient);
+    }
+
+    #[test]
+    fn test_params() {
+        let mut m = Material::default();
+        assert_eq!(m.get_param(MaterialParam::Shininess), 200.0);
+        assert_eq!(m.get_param(MaterialParam::Metallic), 0.0);
+        m.set_param(MaterialParam::Reflection, 0.5).unwrap();
+        assert_eq!(m.get_reflection(), 0.5);
+        assert!(m.set_param(MaterialParam::Diffuse, -1.0).is_err());
+        m.set_param(MaterialParam::Roughness, 0.2).unwrap();
+        assert_eq!(m.get_model(), ShadingModel::MetallicRoughness { metallic: 0.0, roughness: 0.2 });
+        m.set_param(MaterialParam::Metallic, 1.0).unwrap();
+        assert_eq!(m.get_model(), ShadingModel::MetallicRoughness { metallic: 1.0, roughness: 0.2 });
+    }
+
+    #[test]
+    fn test_maps() {
+        use crate::tex::scalar::{Channel, ScalarMap};
+        let checker = Pattern::new_checker(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
+        let map = ScalarMap::new(Arc::new(checker), Channel::R);
+        let mut m = Material::default();
+        m.set_reflection(0.8).unwrap();
+        assert_eq!(m.get_param_at(MaterialParam::Reflection, Coord::point(0.5, 0.0, 0.5)), 0.8);
+        m.set_map(MaterialParam::Reflection, map.clone());
+        assert_eq!(m.get_param_at(MaterialParam::Reflection, Coord::point(0.5, 0.0, 0.5)), 0.8);
+        assert_eq!(m.get_param_at(MaterialParam::Reflection, Coord::point(1.5, 0.0, 0.5)), 0.0);
+        assert_eq!(m.get_param_at(MaterialParam::Ambient, Coord::point(1.5, 0.0, 0.5)), 0.1);
+        assert_eq!(m.get_map(MaterialParam::Reflection), Some(map.clone()));
+        assert_ne!(m, { let mut n = m.clone(); n.remove_map(MaterialParam::Reflection); n });
+
+        // one map per param
+        m.set_map(MaterialParam::Reflection, ScalarMap::new(Arc::new(Color::white()), Channel::G));
+        assert_eq!(m.get_maps().len(), 1);
+        m.remove_map(MaterialParam::Reflection);
+        assert_eq!(m.get_map(MaterialParam::Reflection), None);
+    }
+
+    #[test]
+    fn test_mapped_lighting() {
+        // a specular map that turns the highlight off, in object space of a moved sphere
+        use crate::tex::scalar::{Channel, ScalarMap};
+        let mut m = Material::default();
+        m.set_map(MaterialParam::Specular, ScalarMap::new(Arc::new(Color::black()), Channel::R));
+        let mut o = Sphere::default();
+        o.set_material(m);
+        let o = Arc::new(o);
+        let eyev = Coord::vec(0.0, 0.0, -1.0);
+        let normalv = Coord::vec(0.0, 0.0, -1.0);
+        let light = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white()).unwrap();
+        let c = lighting(o, light, Coord::point(0.0, 0.0, -1.0), eyev, normalv, false);
+        assert_eq!(c, Color::new(1.0, 1.0, 1.0, 0.0));
+    }
+}
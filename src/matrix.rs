@@ -1,18 +1,17 @@
 use std::{f32::consts::PI, ops};
 use super::Coord;
+use crate::error::{Result, RtcError};
 
 // TODO: optimize this to use arrays, generic traits, etc.
 // TODO: replace options with results to better do error handling
 /**
- * TODO: IMPORTANT, implement a fluent api to make combining matrixes easier
+ * transformations can be combined with the fluent api, which applies them in reading order
  * ex:
- * transform =  identity
+ * transform =  Matrix::identity(4)
  *              .rotate_x(pi/2)
  *              .scale(5, 5, 5)
  *              .translate(10, 5, 5)
- * where this will unwind in reverse order ie t * s * r order
- * ideally don't do the implied * i at the end
- * this should make combining matrixes more intuitive
+ * is the same as translation * scaling * rotation_x
  */
 // TODO: replace internal Vec with Array to allow implementation of copy
 #[derive(Debug, Clone, PartialEq)]
@@ -95,7 +94,7 @@ impl Matrix {
         Self::scaling(vec.get_x(), vec.get_y(), vec.get_z())
     }
 
-    pub fn rotate_x(radians: f32) -> Self {
+    pub fn rotation_x(radians: f32) -> Self {
         let mut new = Matrix::identity(4);
         new.data[1][1] = radians.cos();
         new.data[1][2] = -(radians.sin());
@@ -104,7 +103,7 @@ impl Matrix {
         new
     }
 
-    pub fn rotate_y(radians: f32) -> Self {
+    pub fn rotation_y(radians: f32) -> Self {
         let mut new = Matrix::identity(4);
         new.data[0][0] = radians.cos();
         new.data[0][2] = radians.sin();
@@ -113,7 +112,7 @@ impl Matrix {
         new
     }
 
-    pub fn rotate_z(radians: f32) -> Self {
+    pub fn rotation_z(radians: f32) -> Self {
         let mut new = Matrix::identity(4);
         new.data[0][0] = radians.cos();
         new.data[0][1] = -(radians.sin());
@@ -122,16 +121,16 @@ impl Matrix {
         new
     }
 
-    pub fn rotate_x_degrees(degrees: f32) -> Self {
-        Matrix::rotate_x(degrees * PI / 180.0)
+    pub fn rotation_x_degrees(degrees: f32) -> Self {
+        Matrix::rotation_x(degrees * PI / 180.0)
     }
 
-    pub fn rotate_y_degrees(degrees: f32) -> Self {
-        Matrix::rotate_y(degrees * PI / 180.0)
+    pub fn rotation_y_degrees(degrees: f32) -> Self {
+        Matrix::rotation_y(degrees * PI / 180.0)
     }
 
-    pub fn rotate_z_degrees(degrees: f32) -> Self {
-        Matrix::rotate_z(degrees * PI / 180.0)
+    pub fn rotation_z_degrees(degrees: f32) -> Self {
+        Matrix::rotation_z(degrees * PI / 180.0)
     }
 
     pub fn to_vec(&self) -> Coord {
@@ -246,6 +245,15 @@ impl Matrix {
         Some(out)
     }
 
+    /// checks that the matrix can be used as a transformation (4x4 and invertible)
+    /// and returns its inverse
+    pub fn transformation_inverse(&self) -> Result<Self> {
+        if self.get_size() != 4 {
+            return Err(RtcError::InvalidMatrixSize(self.get_size()));
+        }
+        self.inverse().ok_or(RtcError::SingularTransformation)
+    }
+
     /// view_transformation(pos: Coord, towards: Coord, up: Coord) -> Matrix
     /// 
     /// will panic if pos or towards is a vec, and if up is a point
@@ -267,6 +275,58 @@ impl Matrix {
 
         orientation * Self::translation(-pos.get_x(), -pos.get_y(), -pos.get_z())
     }
+
+    // fluent api, each call applies its transformation after everything before it
+    // ie identity.rotate_x(r).translate(x, y, z) == translation * rotation_x
+
+    /// applies `transformation` after the current one, ie `transformation * self`
+    pub fn then(self, transformation: Matrix) -> Self {
+        transformation * self
+    }
+
+    pub fn translate(self, x: f32, y: f32, z: f32) -> Self {
+        self.then(Self::translation(x, y, z))
+    }
+
+    pub fn scale(self, x: f32, y: f32, z: f32) -> Self {
+        self.then(Self::scaling(x, y, z))
+    }
+
+    pub fn rotate_x(self, radians: f32) -> Self {
+        self.then(Self::rotation_x(radians))
+    }
+
+    pub fn rotate_y(self, radians: f32) -> Self {
+        self.then(Self::rotation_y(radians))
+    }
+
+    pub fn rotate_z(self, radians: f32) -> Self {
+        self.then(Self::rotation_z(radians))
+    }
+
+    pub fn rotate_x_degrees(self, degrees: f32) -> Self {
+        self.then(Self::rotation_x_degrees(degrees))
+    }
+
+    pub fn rotate_y_degrees(self, degrees: f32) -> Self {
+        self.then(Self::rotation_y_degrees(degrees))
+    }
+
+    pub fn rotate_z_degrees(self, degrees: f32) -> Self {
+        self.then(Self::rotation_z_degrees(degrees))
+    }
+
+    pub fn shear(self, xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+        self.then(Self::shearing(xy, xz, yx, yz, zx, zy))
+    }
+
+    /// applies a view transformation last, so a camera can be placed with
+    /// `Matrix::identity(4).look_at(pos, towards, up)`
+    ///
+    /// will panic under the same conditions as `view_transformation`
+    pub fn look_at(self, pos: Coord, towards: Coord, up: Coord) -> Self {
+        self.then(Self::view_transformation(pos, towards, up))
+    }
 }
 
 
@@ -641,6 +701,14 @@ mod tests {
         assert_eq!(mat.determinate(), 0.0);
         assert_eq!(mat.inverse(), None);
     }
+
+    #[test]
+    fn test_transformation_inverse() {
+        let mat = Matrix::translation(1.0, 2.0, 3.0);
+        assert_eq!(mat.transformation_inverse(), Ok(Matrix::translation(-1.0, -2.0, -3.0)));
+        assert_eq!(Matrix::scaling(1.0, 0.0, 1.0).transformation_inverse(), Err(RtcError::SingularTransformation));
+        assert_eq!(Matrix::identity(3).transformation_inverse(), Err(RtcError::InvalidMatrixSize(3)));
+    }
         
     #[test]
     fn test_invert() {
@@ -754,8 +822,8 @@ mod tests {
     #[test]
     fn test_rotation_x() {
         let p = Coord::point(0.0, 1.0, 0.0);
-        let half_quarter = Matrix::rotate_x(f32::consts::PI / 4.0);
-        let full_quarter = Matrix::rotate_x(f32::consts::PI / 2.0);
+        let half_quarter = Matrix::rotation_x(f32::consts::PI / 4.0);
+        let full_quarter = Matrix::rotation_x(f32::consts::PI / 2.0);
         assert!(test_roughly_equal_coords(half_quarter.clone() * p, Coord::point(0.0, 2.0_f32.sqrt()/2.0, 2.0_f32.sqrt()/2.0)));
         assert!(test_roughly_equal_coords(full_quarter * p, Coord::point(0.0, 0.0, 1.0)));
 
@@ -766,8 +834,8 @@ mod tests {
     #[test]
     fn test_rotation_y() {
         let p = Coord::point(0.0, 0.0, 1.0);
-        let half_quarter = Matrix::rotate_y(f32::consts::PI / 4.0);
-        let full_quarter = Matrix::rotate_y(f32::consts::PI / 2.0);
+        let half_quarter = Matrix::rotation_y(f32::consts::PI / 4.0);
+        let full_quarter = Matrix::rotation_y(f32::consts::PI / 2.0);
         assert!(test_roughly_equal_coords(half_quarter * p, Coord::point(2.0_f32.sqrt()/2.0, 0.0, 2_f32.sqrt()/2.0)));
         assert!(test_roughly_equal_coords(full_quarter * p, Coord::point(1.0, 0.0, 0.0)));
     }
@@ -775,8 +843,8 @@ mod tests {
     #[test]
     fn test_rotation_z() {
         let p = Coord::point(0.0, 1.0, 0.0);
-        let half_quarter = Matrix::rotate_z(f32::consts::PI / 4.0);
-        let full_quarter = Matrix::rotate_z(f32::consts::PI / 2.0);
+        let half_quarter = Matrix::rotation_z(f32::consts::PI / 4.0);
+        let full_quarter = Matrix::rotation_z(f32::consts::PI / 2.0);
         assert!(test_roughly_equal_coords(half_quarter * p, Coord::point(-(2_f32.sqrt())/2.0, 2_f32.sqrt()/2.0, 0.0)));
         assert!(test_roughly_equal_coords(full_quarter * p, Coord::point(-1.0, 0.0, 0.0)));
     }
@@ -806,7 +874,7 @@ mod tests {
     #[test]
     fn test_sequence() {
         let p = Coord::point(1.0, 0.0, 1.0);
-        let a = Matrix::rotate_x(f32::consts::PI/2.0);
+        let a = Matrix::rotation_x(f32::consts::PI/2.0);
         let b = Matrix::scaling(5.0, 5.0, 5.0);
         let c = Matrix::translation(10.0, 5.0, 7.0);
 
@@ -823,6 +891,52 @@ mod tests {
         assert!(test_roughly_equal_coords(transform * p, Coord::point(15.0, 0.0, 7.0)));
     }
 
+    #[test]
+    fn test_fluent_sequence() {
+        let p = Coord::point(1.0, 0.0, 1.0);
+        let transform = Matrix::identity(4)
+            .rotate_x(f32::consts::PI/2.0)
+            .scale(5.0, 5.0, 5.0)
+            .translate(10.0, 5.0, 7.0);
+        let manual = Matrix::translation(10.0, 5.0, 7.0)
+            * Matrix::scaling(5.0, 5.0, 5.0)
+            * Matrix::rotation_x(f32::consts::PI/2.0);
+        assert!(test_roughly_equal(&transform, &manual));
+        assert!(test_roughly_equal_coords(transform * p, Coord::point(15.0, 0.0, 7.0)));
+    }
+
+    #[test]
+    fn test_fluent_rotations_and_shear() {
+        let transform = Matrix::identity(4)
+            .shear(1.0, 0.0, 0.0, 0.0, 0.0, 1.0)
+            .rotate_y_degrees(90.0)
+            .rotate_z(f32::consts::PI/4.0)
+            .rotate_x_degrees(30.0);
+        let manual = Matrix::rotation_x_degrees(30.0)
+            * Matrix::rotation_z(f32::consts::PI/4.0)
+            * Matrix::rotation_y_degrees(90.0)
+            * Matrix::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 1.0);
+        assert!(test_roughly_equal(&transform, &manual));
+
+        let transform = Matrix::identity(4).rotate_y(f32::consts::PI/2.0).rotate_z_degrees(90.0);
+        assert!(test_roughly_equal_coords(transform * Coord::point(0.0, 0.0, 1.0), Coord::point(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_fluent_look_at() {
+        let from = Coord::point(1.0, 3.0, 2.0);
+        let to = Coord::point(4.0, -2.0, 8.0);
+        let up = Coord::vec(1.0, 1.0, 0.0);
+        let t = Matrix::identity(4).look_at(from, to, up);
+        assert!(test_roughly_equal(&t, &Matrix::view_transformation(from, to, up)));
+
+        let t = Matrix::identity(4)
+            .translate(0.0, -2.0, 5.0)
+            .look_at(from, to, up);
+        let manual = Matrix::view_transformation(from, to, up) * Matrix::translation(0.0, -2.0, 5.0);
+        assert!(test_roughly_equal(&t, &manual));
+    }
+
     #[test]
     fn test_from_vec() {
         let vec = Coord::vec(4.0, 2.0, 3.0);
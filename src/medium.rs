@@ -0,0 +1,186 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::error::{Result, in_range, non_negative};
+use crate::{material::beer_lambert, ray::Ray, renderable::Renderable, tex::color::Color};
+
+/// how light scattered inside a medium is spread over directions
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum PhaseFunction {
+    /// the same in every direction
+    #[default]
+    Isotropic,
+    /// Henyey-Greenstein with asymmetry `g` in [-0.99, 0.99], > 0 scatters mostly forward (haze,
+    /// clouds), < 0 mostly back towards the light and 0 is isotropic
+    HenyeyGreenstein { g: f32 },
+}
+
+impl PhaseFunction {
+    /// fraction of scattered light leaving at an angle with cos `cos` to the direction it
+    /// travelled in, per steradian, so it integrates to 1 over the sphere
+    pub fn eval(&self, cos: f32) -> f32 {
+        match self {
+            PhaseFunction::Isotropic => 1.0 / (4.0 * PI),
+            PhaseFunction::HenyeyGreenstein { g } => {
+                let denom = 1.0 + g * g - 2.0 * g * cos;
+                (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+            }
+        }
+    }
+}
+
+/// a homogeneous participating medium like fog, smoke or murky water
+///
+/// coefficients are per unit of distance and per channel, light is lost to absorption and
+/// scattering both and only scattering redirects light from the lights towards the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Medium {
+    absorption: Color,
+    scattering: Color,
+    phase: PhaseFunction,
+}
+
+#[allow(dead_code)]
+impl Medium {
+    /// returns an error if a coefficient is negative or the phase function's g is out of range
+    pub fn new(absorption: Color, scattering: Color, phase: PhaseFunction) -> Result<Self> {
+        for (field, value) in [
+            ("absorption red", absorption.get_r()), ("absorption green", absorption.get_g()), ("absorption blue", absorption.get_b()),
+            ("scattering red", scattering.get_r()), ("scattering green", scattering.get_g()), ("scattering blue", scattering.get_b()),
+        ] {
+            non_negative(field, value)?;
+        }
+        if let PhaseFunction::HenyeyGreenstein { g } = phase {
+            in_range("g", g, -0.99, 0.99)?;
+        }
+        Ok(Self { absorption, scattering, phase })
+    }
+
+    /// grey isotropic fog that scatters without absorbing, `density` is the scattering coefficient
+    pub fn fog(density: f32) -> Result<Self> {
+        Self::new(Color::black(), Color::new(density, density, density, 0.0), PhaseFunction::Isotropic)
+    }
+
+    pub fn get_absorption(&self) -> Color {
+        self.absorption
+    }
+
+    pub fn get_scattering(&self) -> Color {
+        self.scattering
+    }
+
+    pub fn get_phase(&self) -> PhaseFunction {
+        self.phase
+    }
+
+    /// total light lost per unit of distance, absorption plus scattering
+    pub fn extinction(&self) -> Color {
+        self.absorption + self.scattering
+    }
+
+    /// fraction of light left after travelling `distance` through the medium
+    pub fn transmittance(&self, distance: f32) -> Color {
+        beer_lambert(self.extinction(), distance)
+    }
+}
+
+/// a medium filling the inside of a closed renderable
+///
+/// the boundary is only used to find where rays enter and leave, it isn't drawn, so give it its
+/// own surface (eg a glass sphere) by also adding a matching object to the world
+#[derive(Clone)]
+pub struct Volume {
+    boundary: Arc<dyn Renderable>,
+    medium: Medium,
+}
+
+#[allow(dead_code)]
+impl Volume {
+    pub fn new(boundary: Arc<dyn Renderable>, medium: Medium) -> Self {
+        Self { boundary, medium }
+    }
+
+    pub fn get_boundary(&self) -> Arc<dyn Renderable> {
+        self.boundary.clone()
+    }
+
+    pub fn get_medium(&self) -> Medium {
+        self.medium
+    }
+
+    /// (enter, leave) pairs of ray t along which the ray is inside, may start behind the ray
+    pub fn spans(&self, ray: Ray) -> Vec<(f32, f32)> {
+        let mut ts: Vec<f32> = match self.boundary.intersect(ray) {
+            Some(xs) => xs.iter().map(|x| x.get_time()).collect(),
+            None => return Vec::new(),
+        };
+        ts.sort_by(f32::total_cmp);
+        ts.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+}
+
+impl std::fmt::Debug for Volume {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Volume")
+            .field("boundary", &self.boundary.get_type())
+            .field("medium", &self.medium)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{coord::Coord, matrix::Matrix, primitives::{cube::Cube, sphere::Sphere}, renderable::RenderableBase};
+
+    use super::*;
+
+    #[test]
+    fn test_phase_normalized() {
+        for phase in [PhaseFunction::Isotropic, PhaseFunction::HenyeyGreenstein { g: 0.6 }, PhaseFunction::HenyeyGreenstein { g: -0.3 }] {
+            let steps = 4000;
+            let mut sum = 0.0;
+            for i in 0..steps {
+                let theta = (i as f32 + 0.5) / steps as f32 * PI;
+                sum += phase.eval(theta.cos()) * theta.sin() * 2.0 * PI * (PI / steps as f32);
+            }
+            assert!((sum - 1.0).abs() < 0.01, "{phase:?}: {sum}");
+        }
+    }
+
+    #[test]
+    fn test_henyey_greenstein() {
+        let forward = PhaseFunction::HenyeyGreenstein { g: 0.7 };
+        assert!(forward.eval(1.0) > forward.eval(-1.0));
+        let back = PhaseFunction::HenyeyGreenstein { g: -0.7 };
+        assert!(back.eval(1.0) < back.eval(-1.0));
+        let none = PhaseFunction::HenyeyGreenstein { g: 0.0 };
+        assert!((none.eval(0.3) - PhaseFunction::Isotropic.eval(0.3)).abs() < 0.00001);
+    }
+
+    #[test]
+    fn test_new() {
+        let m = Medium::new(Color::new(0.1, 0.2, 0.3, 0.0), Color::new(0.5, 0.5, 0.5, 0.0), PhaseFunction::Isotropic).unwrap();
+        assert_eq!(m.extinction(), Color::new(0.6, 0.7, 0.8, 0.0));
+        assert_eq!(m.transmittance(0.0), Color::new(1.0, 1.0, 1.0, 0.0));
+        assert_eq!(Medium::fog(std::f32::consts::LN_2).unwrap().transmittance(2.0), Color::new(0.25, 0.25, 0.25, 0.0));
+        assert!(Medium::new(Color::new(-0.1, 0.0, 0.0, 0.0), Color::black(), PhaseFunction::Isotropic).is_err());
+        assert!(Medium::new(Color::black(), Color::black(), PhaseFunction::HenyeyGreenstein { g: 1.0 }).is_err());
+    }
+
+    #[test]
+    fn test_spans() {
+        let vol = Volume::new(Arc::new(Sphere::default()), Medium::fog(1.0).unwrap());
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        assert_eq!(vol.spans(r), vec![(4.0, 6.0)]);
+        let r = Ray::new(Coord::point(0.0, 5.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        assert_eq!(vol.spans(r), vec![]);
+        let mut cube = Cube::default();
+        cube.set_transformation(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
+        let vol = Volume::new(Arc::new(cube), Medium::fog(1.0).unwrap());
+        let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0));
+        assert_eq!(vol.spans(r), vec![(-2.0, 2.0)]);
+    }
+}
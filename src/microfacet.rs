@@ -0,0 +1,143 @@
+//! metallic-roughness microfacet BRDF, the model used by glTF, Blender's Principled BSDF and most
+//! game engines, so base color, metallic and roughness values can be copied over as is
+//!
+//! * distribution: GGX / Trowbridge-Reitz with alpha = roughness^2
+//! * geometry: separable Smith GGX
+//! * fresnel: Schlick, with F0 = 0.04 for dielectrics blended to the base color for metals
+//! * diffuse: Lambert, only for the non metallic part and what isn't reflected by Fresnel
+use std::f32::consts::PI;
+
+use crate::{coord::Coord, tex::color::Color};
+
+/// reflectance at normal incidence of a typical dielectric (ior 1.5)
+pub const DIELECTRIC_F0: f32 = 0.04;
+
+/// smallest alpha used, a perfectly smooth surface would need a delta distribution
+const MIN_ALPHA: f32 = 0.001;
+
+/// GGX alpha from perceptual roughness
+pub fn alpha(roughness: f32) -> f32 {
+    (roughness * roughness).max(MIN_ALPHA)
+}
+
+/// reflectance at normal incidence
+pub fn f0(base_color: Color, metallic: f32) -> Color {
+    let dielectric = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0, 0.0);
+    dielectric * (1.0 - metallic) + base_color * metallic
+}
+
+/// GGX normal distribution D(h)
+pub fn distribution(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// Smith masking of a single direction
+fn smith_g1(n_dot_x: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    2.0 * n_dot_x / (n_dot_x + (a2 + (1.0 - a2) * n_dot_x * n_dot_x).sqrt())
+}
+
+/// Smith shadowing-masking G(l, v)
+pub fn geometry(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+    smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha)
+}
+
+/// Schlick's approximation of the Fresnel reflectance
+pub fn fresnel(cos: f32, f0: Color) -> Color {
+    let f = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+    f0 + (Color::white() - f0) * f
+}
+
+/// light reflected towards `eyev` from light arriving along `lightv`, per unit of irradiance
+///
+/// this is the BRDF times cos(theta_l) times pi, the pi making it match the Phong `lighting` where
+/// a white lambertian surface facing a light of intensity 1 reflects 1. returns black when either
+/// direction is below the surface
+pub fn reflectance(base_color: Color, metallic: f32, roughness: f32, normalv: Coord, eyev: Coord, lightv: Coord) -> Color {
+    let n_dot_l = normalv.dot(lightv);
+    let n_dot_v = normalv.dot(eyev);
+    if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+        return Color::black();
+    }
+    let halfv = (eyev + lightv).normalized();
+    let n_dot_h = normalv.dot(halfv).max(0.0);
+    let v_dot_h = eyev.dot(halfv).max(0.0);
+    let a = alpha(roughness);
+
+    let f = fresnel(v_dot_h, f0(base_color, metallic));
+    let specular = f * (distribution(n_dot_h, a) * geometry(n_dot_v, n_dot_l, a) / (4.0 * n_dot_v * n_dot_l));
+    let diffuse = (Color::white() - f) * base_color * ((1.0 - metallic) / PI);
+    (diffuse + specular) * (n_dot_l * PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    #[test]
+    fn test_f0() {
+        assert_eq!(f0(Color::red(), 0.0), Color::new(0.04, 0.04, 0.04, 0.0));
+        assert_eq!(f0(Color::red(), 1.0), Color::red());
+    }
+
+    #[test]
+    fn test_fresnel() {
+        let f0 = Color::new(0.04, 0.04, 0.04, 0.0);
+        assert_eq!(fresnel(1.0, f0), f0);
+        assert_eq!(fresnel(0.0, f0), Color::white());
+    }
+
+    #[test]
+    fn test_distribution_normalized() {
+        // the projected area of the microfacets integrates to 1 over the hemisphere
+        for roughness in [0.3, 0.6, 1.0] {
+            let a = alpha(roughness);
+            let steps = 2000;
+            let mut sum = 0.0;
+            for i in 0..steps {
+                let theta = (i as f32 + 0.5) / steps as f32 * PI / 2.0;
+                sum += distribution(theta.cos(), a) * theta.cos() * theta.sin() * 2.0 * PI * (PI / 2.0 / steps as f32);
+            }
+            assert!((sum - 1.0).abs() < 0.01, "roughness {roughness}: {sum}");
+        }
+    }
+
+    #[test]
+    fn test_rough_is_uniform() {
+        // alpha 1 makes GGX uniform over the hemisphere
+        assert!(close(distribution(1.0, 1.0), 1.0 / PI));
+        assert!(close(distribution(0.3, 1.0), 1.0 / PI));
+    }
+
+    #[test]
+    fn test_geometry() {
+        assert!(close(geometry(1.0, 1.0, 0.5), 1.0));
+        assert!(geometry(0.1, 1.0, 0.5) < geometry(0.5, 1.0, 0.5));
+        assert!(geometry(0.1, 1.0, 0.9) < geometry(0.1, 1.0, 0.1));
+    }
+
+    #[test]
+    fn test_reflectance() {
+        let n = Coord::vec(0.0, 0.0, -1.0);
+        // nothing from behind the surface
+        assert_eq!(reflectance(Color::white(), 0.0, 0.5, n, n, Coord::vec(0.0, 0.0, 1.0)), Color::black());
+        // a rough white dielectric reflects close to all of the light straight back
+        let c = reflectance(Color::white(), 0.0, 1.0, n, n, n);
+        assert!(c.get_r() > 0.9 && c.get_r() < 1.1, "{c:?}");
+        // metals tint their reflection and have no diffuse
+        let c = reflectance(Color::red(), 1.0, 0.5, n, n, n);
+        assert!(c.get_r() > 0.0);
+        assert_eq!(c.get_g(), 0.0);
+        // smooth surfaces have a tight highlight
+        let off = Coord::vec(0.0, 0.5, -1.0).normalized();
+        let smooth = reflectance(Color::white(), 1.0, 0.1, n, n, n).get_r();
+        let smooth_off = reflectance(Color::white(), 1.0, 0.1, n, off, n).get_r();
+        assert!(smooth_off < smooth * 0.01);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::{Result, RtcError};
+use crate::matrix::Matrix;
+
+/// a transformation that changes over time, given by keyframes and linearly interpolated between them
+///
+/// matrices are blended element by element, which is exact for translation and scaling but shrinks
+/// rotations part way between keys, so large rotations need more keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct Motion {
+    keys: Vec<(f32, Matrix)>,   // sorted by time
+}
+
+#[allow(dead_code)]
+impl Motion {
+    /// returns an error if there are no keys, or a key isn't a 4x4 invertible matrix
+    pub fn new(mut keys: Vec<(f32, Matrix)>) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(RtcError::NoKeyframes);
+        }
+        for (_, mat) in &keys {
+            mat.transformation_inverse()?;
+        }
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self { keys })
+    }
+
+    /// moves from `start` at time 0 to `end` at time 1
+    pub fn linear(start: Matrix, end: Matrix) -> Result<Self> {
+        Self::new(vec![(0.0, start), (1.0, end)])
+    }
+
+    /// the same transformation at all times
+    pub fn still(transformation: Matrix) -> Result<Self> {
+        Self::new(vec![(0.0, transformation)])
+    }
+
+    pub fn get_keys(&self) -> &Vec<(f32, Matrix)> {
+        &self.keys
+    }
+
+    /// transformation at `time`, held at the first or last key outside of the keyed range (and at
+    /// the last for a NaN time)
+    pub fn at(&self, time: f32) -> Matrix {
+        let first = &self.keys[0];
+        let last = &self.keys[self.keys.len() - 1];
+        if time <= first.0 {
+            return first.1.clone();
+        }
+        if time >= last.0 {
+            return last.1.clone();
+        }
+        let next = match self.keys.iter().position(|(t, _)| *t > time) {
+            Some(val) => val,
+            None => return last.1.clone(),
+        };
+        let (t0, a) = &self.keys[next - 1];
+        let (t1, b) = &self.keys[next];
+        lerp(a, b, (time - t0) / (t1 - t0))
+    }
+}
+
+impl Default for Motion {
+    fn default() -> Self {
+        Self { keys: vec![(0.0, Matrix::identity(4))] }
+    }
+}
+
+/// elementwise blend of two matrices of the same size, f = 0 gives a and f = 1 gives b
+pub(crate) fn lerp(a: &Matrix, b: &Matrix, f: f32) -> Matrix {
+    let data = a.get_data().iter().zip(b.get_data())
+        .map(|(row_a, row_b)| row_a.iter().zip(row_b).map(|(x, y)| x + (y - x) * f).collect())
+        .collect();
+    Matrix::new(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear() {
+        let m = Motion::linear(Matrix::translation(0.0, 0.0, 0.0), Matrix::translation(2.0, 0.0, 0.0)).unwrap();
+        assert_eq!(m.at(0.0), Matrix::identity(4));
+        assert_eq!(m.at(0.5), Matrix::translation(1.0, 0.0, 0.0));
+        assert_eq!(m.at(1.0), Matrix::translation(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_clamped_outside_keys() {
+        let m = Motion::linear(Matrix::scaling(1.0, 1.0, 1.0), Matrix::scaling(3.0, 3.0, 3.0)).unwrap();
+        assert_eq!(m.at(-1.0), Matrix::identity(4));
+        assert_eq!(m.at(2.0), Matrix::scaling(3.0, 3.0, 3.0));
+        assert_eq!(m.at(f32::NAN), Matrix::scaling(3.0, 3.0, 3.0));
+    }
+
+    #[test]
+    fn test_several_keys() {
+        let m = Motion::new(vec![
+            (2.0, Matrix::translation(0.0, 4.0, 0.0)),
+            (0.0, Matrix::identity(4)),
+            (1.0, Matrix::translation(0.0, 2.0, 0.0)),
+        ]).unwrap();
+        assert_eq!(m.get_keys()[0].0, 0.0);
+        assert_eq!(m.at(0.5), Matrix::translation(0.0, 1.0, 0.0));
+        assert_eq!(m.at(1.5), Matrix::translation(0.0, 3.0, 0.0));
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        assert_eq!(Motion::new(vec![]), Err(RtcError::NoKeyframes));
+        assert_eq!(
+            Motion::linear(Matrix::identity(4), Matrix::scaling(0.0, 1.0, 1.0)),
+            Err(RtcError::SingularTransformation)
+        );
+    }
+
+    #[test]
+    fn test_still() {
+        let m = Motion::still(Matrix::translation(1.0, 2.0, 3.0)).unwrap();
+        assert_eq!(m.at(-5.0), Matrix::translation(1.0, 2.0, 3.0));
+        assert_eq!(m.at(5.0), Matrix::translation(1.0, 2.0, 3.0));
+        assert_eq!(Motion::default().at(0.3), Matrix::identity(4));
+    }
+}
@@ -3,3 +3,12 @@ pub mod cube;
 pub mod plane;
 pub mod cylinder;
 pub mod cone;
+pub mod moving;
+pub mod torus;
+pub mod disk;
+pub mod quad;
+pub mod sdf;
+pub mod heightfield;
+pub mod quadric;
+pub mod triangle;
+pub mod bezier;
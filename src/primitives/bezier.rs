@@ -0,0 +1,487 @@
+use std::sync::Arc;
+
+use super::triangle::{SmoothTriangle, intersect_triangle};
+use crate::{coord::Coord, error::{Result, RtcError, in_range}, impl_getters, impl_getters_setters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
+
+static EPSILON: f32 = 0.00001;
+
+/// a bicubic bezier patch from a 4x4 grid of control points
+///
+/// control point `i * 4 + j` is in row i along v and column j along u. rays are tested against a
+/// tessellation of `resolution` x `resolution` cells and, with `refine` on, the hit is then
+/// polished with newton's method so the silhouette and normals follow the real surface
+#[derive(Clone, PartialEq)]
+pub struct BezierPatch {
+    transformation: Matrix,
+    inverse: Matrix,
+    material: Arc<Material>,
+    control: [Coord; 16],
+    resolution: usize,
+    refine: bool,
+    grid: Arc<Vec<Coord>>,
+    bounds: (Coord, Coord),
+}
+
+impl_getters!(BezierPatch, control: [Coord; 16], resolution: usize);
+impl_getters_setters!(BezierPatch, refine: bool);
+
+/// the cubic bernstein polynomials at t and their derivatives
+fn bernstein(t: f32) -> ([f32; 4], [f32; 4]) {
+    let s = 1.0 - t;
+    (
+        [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t],
+        [-3.0 * s * s, 3.0 * s * s - 6.0 * t * s, 6.0 * t * s - 3.0 * t * t, 3.0 * t * t],
+    )
+}
+
+impl BezierPatch {
+    /// returns an error if a control point isn't a point, `resolution` is 0 or the transformation
+    /// can't be inverted
+    pub fn new(transformation: Matrix, material: Material, control: [Coord; 16], resolution: usize) -> Result<Self> {
+        if control.iter().any(|p| !p.is_point()) {
+            return Err(RtcError::ExpectedPoint);
+        }
+        in_range("resolution", resolution as f32, 1.0, f32::MAX)?;
+        let mut out = Self {
+            inverse: transformation.transformation_inverse()?,
+            transformation,
+            material: Arc::new(material),
+            control,
+            resolution,
+            refine: true,
+            grid: Arc::new(Vec::new()),
+            bounds: (Coord::point(0.0, 0.0, 0.0), Coord::point(0.0, 0.0, 0.0)),
+        };
+        out.update();
+        Ok(out)
+    }
+
+    /// one patch for every set of control points in a teapot style patch file, see
+    /// `parse_patches`
+    pub fn load(filename: &str, transformation: Matrix, material: Material, resolution: usize) -> Result<Vec<Self>> {
+        read_patches(filename)?
+            .into_iter()
+            .map(|control| Self::new(transformation.clone(), material.clone(), control, resolution))
+            .collect()
+    }
+
+    /// returns an error if `resolution` is 0
+    pub fn set_resolution(&mut self, resolution: usize) -> Result<()> {
+        in_range("resolution", resolution as f32, 1.0, f32::MAX)?;
+        self.resolution = resolution;
+        self.update();
+        Ok(())
+    }
+
+    /// rebuilds the tessellated grid and the bounds after the control points or resolution change
+    fn update(&mut self) {
+        let n = self.resolution;
+        let step = 1.0 / n as f32;
+        self.grid = Arc::new(
+            (0..=n)
+                .flat_map(|iv| (0..=n).map(move |iu| (iu, iv)))
+                .map(|(iu, iv)| self.point_at(iu as f32 * step, iv as f32 * step))
+                .collect(),
+        );
+        // the patch stays inside the box around its control points
+        let (mut lo, mut hi) = (self.control[0], self.control[0]);
+        for p in &self.control[1..] {
+            lo = Coord::point(lo.get_x().min(p.get_x()), lo.get_y().min(p.get_y()), lo.get_z().min(p.get_z()));
+            hi = Coord::point(hi.get_x().max(p.get_x()), hi.get_y().max(p.get_y()), hi.get_z().max(p.get_z()));
+        }
+        self.bounds = (lo, hi);
+    }
+
+    /// the surface point at (u, v) in object space and its partial derivatives along u and v
+    fn evaluate(&self, u: f32, v: f32) -> (Coord, Coord, Coord) {
+        let (bu, du) = bernstein(u);
+        let (bv, dv) = bernstein(v);
+        let (mut s, mut su, mut sv) = (Coord::vec(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 0.0));
+        for i in 0..4 {
+            for j in 0..4 {
+                let p = self.control[i * 4 + j].to_vec();
+                s += p * (bv[i] * bu[j]);
+                su += p * (bv[i] * du[j]);
+                sv += p * (dv[i] * bu[j]);
+            }
+        }
+        (s.to_pos(), su, sv)
+    }
+
+    /// the surface point at (u, v), in object space
+    pub fn point_at(&self, u: f32, v: f32) -> Coord {
+        self.evaluate(u, v).0
+    }
+
+    /// the object space normal at (u, v), facing `dS/dv x dS/du`
+    fn normal_at_uv(&self, u: f32, v: f32) -> Coord {
+        // collapsed edges, like the tip of the teapot lid, have no tangent along one side so look
+        // a little further in until there is one
+        let (mut u, mut v) = (u, v);
+        for _ in 0..8 {
+            let (_, su, sv) = self.evaluate(u, v);
+            let n = sv.cross(&su);
+            if n.magnitude() > EPSILON * EPSILON {
+                return n.normalized();
+            }
+            u += (0.5 - u) * 0.01;
+            v += (0.5 - v) * 0.01;
+        }
+        Coord::vec(0.0, 1.0, 0.0)
+    }
+
+    /// (u, v) of the surface point closest to object space `pos`, starting at the closest grid
+    /// vertex and refined with gauss-newton steps
+    fn project(&self, pos: Coord) -> (f32, f32) {
+        let n = self.resolution + 1;
+        let closest = (0..self.grid.len())
+            .min_by(|&a, &b| (self.grid[a] - pos).magnitude().total_cmp(&(self.grid[b] - pos).magnitude()))
+            .unwrap_or(0);
+        let step = 1.0 / self.resolution as f32;
+        let (mut u, mut v) = ((closest % n) as f32 * step, (closest / n) as f32 * step);
+        for _ in 0..8 {
+            let (s, su, sv) = self.evaluate(u, v);
+            let r = pos - s;
+            let (a, b, c) = (su.dot(su), su.dot(sv), sv.dot(sv));
+            let det = a * c - b * b;
+            if det.abs() < f32::EPSILON * f32::EPSILON {
+                break;
+            }
+            let (ru, rv) = (su.dot(r), sv.dot(r));
+            u = (u + (c * ru - b * rv) / det).clamp(0.0, 1.0);
+            v = (v + (a * rv - b * ru) / det).clamp(0.0, 1.0);
+        }
+        (u, v)
+    }
+
+    /// newton steps on S(u, v) = origin + t * dir from a hit on the tessellation, none if they
+    /// don't settle on the patch
+    fn refine_hit(&self, origin: Coord, dir: Coord, hit: (f32, f32, f32)) -> Option<(f32, f32, f32)> {
+        let (mut t, mut u, mut v) = hit;
+        for _ in 0..8 {
+            let (s, su, sv) = self.evaluate(u, v);
+            let r = origin + dir * t - s;
+            if r.magnitude() < EPSILON {
+                break;
+            }
+            // solve su * du + sv * dv - dir * dt = r with cramer's rule
+            let back = -dir;
+            let det = su.dot(sv.cross(&back));
+            if det.abs() < f32::EPSILON * f32::EPSILON {
+                return None;
+            }
+            u += r.dot(sv.cross(&back)) / det;
+            v += su.dot(r.cross(&back)) / det;
+            t += su.dot(sv.cross(&r)) / det;
+        }
+        let settled = (self.point_at(u, v) - (origin + dir * t)).magnitude() < EPSILON * 10.0;
+        let on_patch = (-EPSILON..=1.0 + EPSILON).contains(&u) && (-EPSILON..=1.0 + EPSILON).contains(&v);
+        (settled && on_patch).then_some((t, u.clamp(0.0, 1.0), v.clamp(0.0, 1.0)))
+    }
+
+    /// (t, u, v) of the hits with the patch in object space, sorted by t
+    fn local_intersect(&self, ray: &Ray) -> Vec<(f32, f32, f32)> {
+        let (origin, dir) = (ray.get_origin(), ray.get_direction());
+        let (lo, hi) = self.bounds;
+        let (mut t0, mut t1) = (-f32::INFINITY, f32::INFINITY);
+        for (o, d, lo, hi) in [
+            (origin.get_x(), dir.get_x(), lo.get_x(), hi.get_x()),
+            (origin.get_y(), dir.get_y(), lo.get_y(), hi.get_y()),
+            (origin.get_z(), dir.get_z(), lo.get_z(), hi.get_z()),
+        ] {
+            let (lo, hi) = (lo - EPSILON, hi + EPSILON);
+            if d.abs() < f32::EPSILON {
+                if o < lo || o > hi {
+                    return Vec::new();
+                }
+                continue;
+            }
+            let (a, b) = ((lo - o) / d, (hi - o) / d);
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        if t0 > t1 {
+            return Vec::new();
+        }
+
+        let n = self.resolution;
+        let step = 1.0 / n as f32;
+        let vertex = |iu: usize, iv: usize| self.grid[iv * (n + 1) + iu];
+        let mut out = Vec::new();
+        for iv in 0..n {
+            for iu in 0..n {
+                let (p00, p10, p01, p11) = (vertex(iu, iv), vertex(iu + 1, iv), vertex(iu, iv + 1), vertex(iu + 1, iv + 1));
+                // the barycentric weights of each triangle give where in the cell the hit is
+                let hits = [
+                    intersect_triangle(origin, dir, p00, p10, p11).map(|(t, a, b)| (t, a + b, b)),
+                    intersect_triangle(origin, dir, p00, p11, p01).map(|(t, a, b)| (t, a, a + b)),
+                ];
+                for (t, fu, fv) in hits.into_iter().flatten() {
+                    let hit = (t, (iu as f32 + fu) * step, (iv as f32 + fv) * step);
+                    out.push(if self.refine { self.refine_hit(origin, dir, hit).unwrap_or(hit) } else { hit });
+                }
+            }
+        }
+        out.sort_by(|a, b| a.0.total_cmp(&b.0));
+        // a ray through an edge or vertex of the tessellation hits every triangle that shares it
+        out.dedup_by(|a, b| (a.0 - b.0).abs() < EPSILON * 10.0);
+        out
+    }
+
+    fn to_world_normal(&self, local_normal: Coord) -> Coord {
+        let mut out = self.get_inverse().transpose() * local_normal;
+        out.set_w(0.0);
+        out.normalized()
+    }
+
+    /// the patch as `resolution` x `resolution` cells of two smooth triangles each, with the
+    /// patch's transformation and sharing its material
+    pub fn tessellate(&self) -> Result<Vec<SmoothTriangle>> {
+        let n = self.resolution;
+        let step = 1.0 / n as f32;
+        let normals: Vec<Coord> = (0..=n)
+            .flat_map(|iv| (0..=n).map(move |iu| (iu, iv)))
+            .map(|(iu, iv)| self.normal_at_uv(iu as f32 * step, iv as f32 * step))
+            .collect();
+        let index = |iu: usize, iv: usize| iv * (n + 1) + iu;
+        let mut out = Vec::with_capacity(2 * n * n);
+        for iv in 0..n {
+            for iu in 0..n {
+                for corners in [
+                    [index(iu, iv), index(iu + 1, iv), index(iu + 1, iv + 1)],
+                    [index(iu, iv), index(iu + 1, iv + 1), index(iu, iv + 1)],
+                ] {
+                    let mut triangle = SmoothTriangle::new(
+                        self.transformation.clone(),
+                        Material::default(),
+                        corners.map(|i| self.grid[i]),
+                        corners.map(|i| normals[i]),
+                    )?;
+                    triangle.set_shared_material(self.material.clone());
+                    out.push(triangle);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// reads the control points of a teapot style patch file, see `parse_patches`
+pub fn read_patches(filename: &str) -> Result<Vec<[Coord; 16]>> {
+    parse_patches(&std::fs::read_to_string(filename)?)
+}
+
+/// parses the text format the utah teapot is usually shared in: the number of patches, a line of
+/// 16 vertex indices (starting at 1) for each patch, the number of vertices and then a line of
+/// x, y, z for each vertex. numbers can be split by commas or whitespace and blank lines are
+/// skipped
+pub fn parse_patches(text: &str) -> Result<Vec<[Coord; 16]>> {
+    let invalid = |msg: String| RtcError::InvalidModel(msg);
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    let mut next_line = |what: &str| lines.next().ok_or_else(|| invalid(format!("missing {what}")));
+
+    let patch_count = parse_numbers::<usize>(next_line("patch count")?, 1)?[0];
+    let indices = (0..patch_count)
+        .map(|_| parse_numbers::<usize>(next_line("patch")?, 16))
+        .collect::<Result<Vec<_>>>()?;
+    let vertex_count = parse_numbers::<usize>(next_line("vertex count")?, 1)?[0];
+    let vertices = (0..vertex_count)
+        .map(|_| parse_numbers::<f32>(next_line("vertex")?, 3).map(|p| Coord::point(p[0], p[1], p[2])))
+        .collect::<Result<Vec<_>>>()?;
+
+    indices
+        .into_iter()
+        .map(|patch| {
+            let mut control = [Coord::point(0.0, 0.0, 0.0); 16];
+            for (c, i) in control.iter_mut().zip(patch) {
+                *c = *i.checked_sub(1).and_then(|i| vertices.get(i)).ok_or_else(|| invalid(format!("no vertex {i}")))?;
+            }
+            Ok(control)
+        })
+        .collect()
+}
+
+/// exactly `count` numbers from a line
+fn parse_numbers<T: std::str::FromStr>(line: &str, count: usize) -> Result<Vec<T>> {
+    let out = line
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|_| RtcError::InvalidModel(format!("{s:?} isn't a number"))))
+        .collect::<Result<Vec<T>>>()?;
+    if out.len() != count {
+        return Err(RtcError::InvalidModel(format!("expected {count} numbers, got {line:?}")));
+    }
+    Ok(out)
+}
+
+impl_renderable_base!(BezierPatch, RenderableType::BezierPatch);
+
+impl_renderable_tests!(crate::primitives::bezier::BezierPatch, RenderableType::BezierPatch);
+
+impl Renderable for BezierPatch {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
+        let hits = self.local_intersect(&ray);
+        if hits.is_empty() {
+            return (ray, None);
+        }
+        let xs = hits
+            .into_iter()
+            .map(|(t, _, _)| Intersection::new(t, this.clone()))
+            .collect();
+        (ray, Some(xs))
+    }
+
+    fn normal_at(&self, pos: Coord) -> Coord {
+        let (u, v) = self.project(self.get_inverse() * pos);
+        self.to_world_normal(self.normal_at_uv(u, v))
+    }
+
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        self.project(self.get_inverse() * pos)
+    }
+
+    /// the square [-1, 1] x [-1, 1] on the xz plane facing +y, tessellated into 8 x 8 cells
+    fn default() -> Self {
+        let control = std::array::from_fn(|k| {
+            let (i, j) = (k / 4, k % 4);
+            Coord::point(-1.0 + 2.0 * j as f32 / 3.0, 0.0, -1.0 + 2.0 * i as f32 / 3.0)
+        });
+        Self::new(Matrix::identity(4), Material::default(), control, 8).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    /// the default square with the four middle control points raised to y = 1
+    fn dome() -> BezierPatch {
+        let mut control = BezierPatch::default().get_control();
+        for k in [5, 6, 9, 10] {
+            control[k] = Coord::point(control[k].get_x(), 1.0, control[k].get_z());
+        }
+        BezierPatch::new(Matrix::identity(4), Material::default(), control, 8).unwrap()
+    }
+
+    fn close(a: Coord, b: Coord, eps: f32) -> bool {
+        (a - b).magnitude() < eps
+    }
+
+    #[test]
+    fn test_flat() {
+        let p = BezierPatch::default();
+        let xs = p.intersect(Ray::new(Coord::point(0.3, 2.0, -0.4), Coord::vec(0.0, -1.0, 0.0))).unwrap();
+        assert_eq!(xs.len(), 1);
+        assert!((xs[0].get_time() - 2.0).abs() < 0.0001);
+        assert!(close(p.normal_at(Coord::point(0.3, 0.0, -0.4)), Coord::vec(0.0, 1.0, 0.0), 0.0001));
+        let (u, v) = p.uv_at(Coord::point(0.3, 0.0, -0.4));
+        assert!((u - 0.65).abs() < 0.0001 && (v - 0.3).abs() < 0.0001);
+    }
+
+    #[test_case(Coord::point(1.5, 2.0, 0.0), Coord::vec(0.0, -1.0, 0.0) ; "beside")]
+    #[test_case(Coord::point(0.0, 2.0, 0.0), Coord::vec(1.0, 0.0, 0.0) ; "above")]
+    #[test_case(Coord::point(0.0, 0.0, -2.0), Coord::vec(0.0, 0.0, 1.0) ; "edge on")]
+    fn test_miss(origin: Coord, direction: Coord) {
+        assert!(BezierPatch::default().intersect(Ray::new(origin, direction)).is_none());
+    }
+
+    #[test_case(0.3, -0.4 ; "off grid")]
+    #[test_case(0.0, 0.0 ; "through a grid vertex")]
+    #[test_case(-0.85, 0.6 ; "near the edge")]
+    fn test_curved(x: f32, z: f32) {
+        let mut p = dome();
+        // evenly spaced control points keep x and z linear in u and v
+        let (u, v) = ((x + 1.0) / 2.0, (z + 1.0) / 2.0);
+        let expected = p.point_at(u, v);
+        let ray = Ray::new(Coord::point(x, 5.0, z), Coord::vec(0.0, -1.0, 0.0));
+
+        let xs = p.intersect(ray.clone()).unwrap();
+        assert_eq!(xs.len(), 1);
+        assert!((xs[0].get_time() - (5.0 - expected.get_y())).abs() < 0.0001);
+        let (_, su, sv) = p.evaluate(u, v);
+        assert!(close(p.normal_at(expected), sv.cross(&su).normalized(), 0.001));
+
+        p.set_refine(false);
+        let xs = p.intersect(ray).unwrap();
+        assert_eq!(xs.len(), 1);
+        assert!((xs[0].get_time() - (5.0 - expected.get_y())).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_tessellate() {
+        let mut p = dome();
+        p.set_resolution(2).unwrap();
+        let triangles = p.tessellate().unwrap();
+        assert_eq!(triangles.len(), 8);
+        for t in &triangles {
+            for corner in t.get_points() {
+                let (u, v) = p.uv_at(corner);
+                assert!(close(p.point_at(u, v), corner, 0.0001));
+            }
+        }
+        // the middle vertex is the top of the dome
+        assert!(close(triangles[0].normal_at(p.point_at(0.5, 0.5)), Coord::vec(0.0, 1.0, 0.0), 0.0001));
+        assert!(p.set_resolution(0).is_err());
+    }
+
+    #[test]
+    fn test_new() {
+        let mut control = BezierPatch::default().get_control();
+        assert!(BezierPatch::new(Matrix::identity(4), Material::default(), control, 0).is_err());
+        control[3] = Coord::vec(0.0, 0.0, 0.0);
+        assert_eq!(BezierPatch::new(Matrix::identity(4), Material::default(), control, 4).err(), Some(RtcError::ExpectedPoint));
+    }
+
+    #[test]
+    fn test_collapsed_edge() {
+        // every control point of the first row is the same, like the tip of the teapot lid
+        let mut control = dome().get_control();
+        for c in control.iter_mut().take(4) {
+            *c = Coord::point(0.0, 0.0, -1.0);
+        }
+        let p = BezierPatch::new(Matrix::identity(4), Material::default(), control, 8).unwrap();
+        let n = p.normal_at_uv(0.5, 0.0);
+        assert!(n.magnitude() > 0.99 && n.get_y() > 0.0);
+    }
+
+    fn square_patch_text() -> String {
+        let indices: Vec<String> = (1..=16).map(|i| i.to_string()).collect();
+        let vertices: Vec<String> = (0..16).map(|k| format!("{}, {}, 0.0", k % 4, k / 4)).collect();
+        format!("1\n{}\n16\n{}\n", indices.join(","), vertices.join("\n"))
+    }
+
+    #[test]
+    fn test_parse_patches() {
+        let patches = parse_patches(&square_patch_text()).unwrap();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0][0], Coord::point(0.0, 0.0, 0.0));
+        assert_eq!(patches[0][6], Coord::point(2.0, 1.0, 0.0));
+        assert_eq!(patches[0][15], Coord::point(3.0, 3.0, 0.0));
+
+        // whitespace separated and blank lines work too
+        let spaced = square_patch_text().replace(',', " ").replace('\n', "\n\n");
+        assert_eq!(parse_patches(&spaced).unwrap(), patches);
+    }
+
+    #[test_case("1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16", "16" ; "index out of range")]
+    #[test_case("0,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16", "0" ; "zero index")]
+    #[test_case("1,2,3,4,5,6,7,8,9,10,11,12,13,14,15", "expected 16 numbers" ; "short patch")]
+    #[test_case("1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,x", "\"x\"" ; "not a number")]
+    fn test_parse_patches_errors(patch: &str, message: &str) {
+        let text = format!("1\n{patch}\n15\n{}", "0, 0, 0\n".repeat(15));
+        let e = parse_patches(&text).unwrap_err();
+        assert!(matches!(e, RtcError::InvalidModel(_)));
+        assert!(e.to_string().contains(message), "{e}");
+    }
+
+    #[test]
+    fn test_parse_patches_truncated() {
+        let text = square_patch_text();
+        let cut = &text[..text.len() - 10];
+        assert_eq!(parse_patches(cut).err(), Some(RtcError::InvalidModel("missing vertex".to_string())));
+        assert!(parse_patches("").is_err());
+    }
+}
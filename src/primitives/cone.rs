@@ -1,16 +1,18 @@
-use crate::{coord::Coord, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
+use crate::{coord::Coord, error::Result, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
 
-use std::sync::Arc;
+use std::{f32::consts::PI, sync::Arc};
 
 #[derive(PartialEq, Clone)]
 pub struct Cone {
     transformation: Matrix,
-    material: Material  // TODO: refactor this to a pointer
+    inverse: Matrix,
+    material: Arc<Material>,
 }
 
 impl Cone {
-    pub fn new(transformation: Matrix, material: Material) -> Self {
-        Self { transformation, material }
+    /// returns an error if the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material) -> Result<Self> {
+        Ok(Self { inverse: transformation.transformation_inverse()?, transformation, material: Arc::new(material) })
     }
 
     fn normal_at_local_space(&self, pos: Coord) -> Coord {
@@ -24,25 +26,37 @@ impl_renderable_tests!(crate::primitives::cone::Cone, RenderableType::Cone);
 
 impl Renderable for Cone {
 
-    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
-        todo!()
-    }
-
-    fn intersect_get_ray(&self, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+    fn intersect_shared(&self, _this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
         todo!()
     }
 
     fn normal_at(&self, pos: Coord) -> Coord {
-        let pos = self.get_transformation().inverse().unwrap() * pos;
+        let pos = self.get_inverse() * pos;
         self.normal_at_local_space(pos)
     }
 
+    /// the same cylindrical mapping as `Cylinder`, u around the y axis and v up it
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        let theta = p.get_x().atan2(p.get_z());
+        (1.0 - (theta / (2.0 * PI) + 0.5), p.get_y().rem_euclid(1.0))
+    }
+
     fn default() -> Self where Self: Sized {
-        todo!()
+        Self { transformation: Matrix::identity(4), inverse: Matrix::identity(4), material: Arc::new(Material::default()) }
     }
 }
 
 #[cfg(test)]
 mod tests {
-
+    use super::*;
+
+    #[test]
+    fn test_uv_at() {
+        let c = Cone::default();
+        let (u, v) = c.uv_at(Coord::point(0.0, 0.25, -0.25));
+        assert!((u - 0.0).abs() < 0.0001 && (v - 0.25).abs() < 0.0001);
+        let (u, v) = c.uv_at(Coord::point(1.5, 1.5, 0.0));
+        assert!((u - 0.25).abs() < 0.0001 && (v - 0.5).abs() < 0.0001);
+    }
 }
\ No newline at end of file
@@ -1,20 +1,20 @@
 use std::sync::Arc;
 
-use crate::{coord::Coord, impl_getters_setters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
+use crate::{coord::Coord, error::Result, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType, SurfaceSample}, tex::color::Color};
 
 static EPSILON: f32 = 0.005; // TODO: unify this with other epsilon values
 
 #[derive(PartialEq, Clone)]
 pub struct Cube {
     transformation: Matrix,
-    material: Material
+    inverse: Matrix,
+    material: Arc<Material>,
 }
 
-impl_getters_setters!(Cube, transformation: Matrix, material: Material);
-
 impl Cube {
-    pub fn new(transformation: Matrix, material: Material) -> Self {
-        Self { transformation, material }
+    /// returns an error if the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material) -> Result<Self> {
+        Ok(Self { inverse: transformation.transformation_inverse()?, transformation, material: Arc::new(material) })
     }
 
     /// given a 1d coord and dir, returns the two times of intersection with a cube on that plane
@@ -54,9 +54,7 @@ impl Cube {
         };
 
         let mut out = self
-            .get_transformation()
-            .inverse()
-            .unwrap()
+            .get_inverse()
             .transpose()
             * local_normal;
         out.set_w(0.0);
@@ -69,13 +67,8 @@ impl_renderable_base!(Cube, RenderableType::Cube);
 impl_renderable_tests!(crate::primitives::cube::Cube, RenderableType::Cube);
 
 impl Renderable for Cube {
-    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
-        let (_, out) = self.intersect_get_ray(ray);
-        out
-    }
-
-    fn intersect_get_ray(&self, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
-        let ray = ray.transform(self.get_transformation().inverse().unwrap());
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
 
         // TODO: can optimize by skipping rest after it's clear the ray is a miss
         let (xtmin, xtmax) = Cube::check_axis(ray.get_origin().get_x(), ray.get_direction().get_x());
@@ -85,12 +78,11 @@ impl Renderable for Cube {
         let tmin = vec![xtmin, ytmin, ztmin].into_iter().reduce(f32::max).unwrap();
         let tmax = vec![xtmax, ytmax, ztmax].into_iter().reduce(f32::min).unwrap();
 
-        let obj = Arc::new(self.clone());   // TODO: remove this clone
         let data = if tmax >= tmin {
             Some(
                 vec![
-                    Intersection::new(tmin, obj.clone(), self.normal_at_local_space(ray.position(tmin))),
-                    Intersection::new(tmax, obj        , self.normal_at_local_space(ray.position(tmax)))        
+                    Intersection::new(tmin, this.clone()),
+                    Intersection::new(tmax, this.clone())        
             ])
         } else {
             None
@@ -101,12 +93,43 @@ impl Renderable for Cube {
 
     // note, striking a corner or edge right on the seem is undefined, it could be a normal in any of the 3(2) valid directions
     fn normal_at(&self, pos: Coord) -> Coord {
-        let pos = self.get_transformation().inverse().unwrap() * pos; // TODO: isn't this already done in intersect method
+        let pos = self.get_inverse() * pos; // TODO: isn't this already done in intersect method
         self.normal_at_local_space(pos)
     }
 
+    /// each face gets the full [0, 1] range, picked the same way as the normal
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        let (x, y, z) = (p.get_x(), p.get_y(), p.get_z());
+        let max_axis = x.abs().max(y.abs()).max(z.abs());
+        let (u, v) = if max_axis == x.abs() {
+            if x > 0.0 { (1.0 - z, y + 1.0) } else { (z + 1.0, y + 1.0) }
+        } else if max_axis == y.abs() {
+            if y > 0.0 { (x + 1.0, 1.0 - z) } else { (x + 1.0, z + 1.0) }
+        } else if z > 0.0 {
+            (1.0 - x, y + 1.0)
+        } else {
+            (x + 1.0, y + 1.0)
+        };
+        (u.rem_euclid(2.0) / 2.0, v.rem_euclid(2.0) / 2.0)
+    }
+
+    /// a face picked with u[2], then uniform over it
+    fn sample_surface(&self, u: [f32; 3]) -> Option<SurfaceSample> {
+        let face = ((u[2] * 6.0) as usize).min(5);
+        let sign = if face.is_multiple_of(2) { 1.0 } else { -1.0 };
+        let (a, b) = (2.0 * u[0] - 1.0, 2.0 * u[1] - 1.0);
+        let (point, normal) = match face / 2 {
+            0 => (Coord::point(sign, a, b), Coord::vec(sign, 0.0, 0.0)),
+            1 => (Coord::point(a, sign, b), Coord::vec(0.0, sign, 0.0)),
+            _ => (Coord::point(a, b, sign), Coord::vec(0.0, 0.0, sign)),
+        };
+        // 6 faces of area 4
+        Some(SurfaceSample::from_local(self, point, normal, 1.0 / 24.0))
+    }
+
     fn default() -> Self where Self: Sized {
-        Self { transformation: Matrix::identity(4), material: Material::default() }
+        Self { transformation: Matrix::identity(4), inverse: Matrix::identity(4), material: Arc::new(Material::default()) }
     }
 }
 
@@ -161,9 +184,30 @@ mod tests {
     #[test]
     fn test_normal_at_world_to_local() {
         let mut c = Cube::default();
-        c.set_transformation(Matrix::rotate_z(std::f32::consts::PI / 2.0));
+        c.set_transformation(Matrix::rotation_z(std::f32::consts::PI / 2.0)).unwrap();
         let world_point = c.get_transformation() * Coord::point(1.0, 0.3, 0.0);
         let n = c.normal_at(world_point);
         assert_eq!(n, Coord::vec(0.0, 1.0, 0.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sample_surface() {
+        let mut c = Cube::default();
+        c.set_transformation(Matrix::rotation_y(0.3) * Matrix::scaling(1.0, 2.0, 3.0)).unwrap();
+        let n = 12;
+        let mut inverse_pdf = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..6 {
+                    let u = [(i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32, (k as f32 + 0.5) / 6.0];
+                    let sample = c.sample_surface(u).unwrap();
+                    assert!((sample.get_normal() - c.normal_at(sample.get_point())).magnitude() < 0.0001);
+                    inverse_pdf += 1.0 / sample.get_pdf();
+                }
+            }
+        }
+        // faces of 2x4, 1x3 and 2x6 doubled, each twice
+        let area = inverse_pdf / (n * n * 6) as f32;
+        assert!((area - 88.0).abs() < 0.01, "{area}");
+    }
+}
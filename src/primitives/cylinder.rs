@@ -1,23 +1,25 @@
-use crate::{coord::Coord, impl_getters_setters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
+use crate::{coord::Coord, error::Result, impl_getters_setters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType, SurfaceSample}, tex::color::Color};
 
-use std::sync::Arc;
+use std::{f32::consts::PI, sync::Arc};
 
 static EPSILON: f32 = 0.005;
 
 #[derive(PartialEq, Clone)]
 pub struct Cylinder {
     transformation: Matrix,
-    material: Material,  // TODO: refactor this to a pointer
+    inverse: Matrix,
+    material: Arc<Material>,
     min: f32,
     max: f32,
     closed: bool
 }
 
-impl_getters_setters!(Cylinder, transformation: Matrix, material: Material, min: f32, max: f32, closed: bool);
+impl_getters_setters!(Cylinder, min: f32, max: f32, closed: bool);
 
 impl Cylinder {
-    pub fn new(transformation: Matrix, material: Material, min: f32, max: f32, closed: bool) -> Self {
-        Self { transformation, material, min, max, closed }
+    /// returns an error if the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material, min: f32, max: f32, closed: bool) -> Result<Self> {
+        Ok(Self { inverse: transformation.transformation_inverse()?, transformation, material: Arc::new(material), min, max, closed })
     }
 
     fn normal_at_local_space(&self, pos: Coord) -> Coord {
@@ -31,21 +33,20 @@ impl Cylinder {
         x.powi(2) + z.powi(2) <= 1.0
     }
 
-    fn intersect_caps(&self, ray: Ray, data: &mut Vec<Intersection>) {
+    fn intersect_caps(&self, ray: Ray, this: &Arc<dyn Renderable>, data: &mut Vec<Intersection>) {
         if !self.get_closed() || ray.get_direction().get_y().abs() < EPSILON {
             return;
         }
 
-        let object = Arc::new(self.clone());
         let t = (self.get_min() - ray.get_origin().get_y()) / ray.get_direction().get_y();
         if Cylinder::check_cap(ray, t) {
-            data.push(Intersection::new(t, object.clone(), self.normal_at_local_space(ray.position(t))));
+            data.push(Intersection::new(t, this.clone()));
         }
 
 
         let t = (self.get_max() - ray.get_origin().get_y()) / ray.get_direction().get_y();
         if Cylinder::check_cap(ray, t) {
-            data.push(Intersection::new(t, object.clone(), self.normal_at_local_space(ray.position(t))));
+            data.push(Intersection::new(t, this.clone()));
         }
     }
 }
@@ -56,13 +57,8 @@ impl_renderable_tests!(crate::primitives::cylinder::Cylinder, RenderableType::Cy
 
 impl Renderable for Cylinder {
 
-    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
-        let (_, out) = self.intersect_get_ray(ray);
-        out
-    }
-
-    fn intersect_get_ray(&self, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
-        let ray = ray.transform(self.get_transformation().inverse().unwrap());
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
 
         let a = ray.get_direction().get_x().powi(2) + ray.get_direction().get_z().powi(2);
 
@@ -70,7 +66,7 @@ impl Renderable for Cylinder {
         if a.abs() < EPSILON {
             if self.get_closed() {
                 let mut data = Vec::<Intersection>::new();
-                self.intersect_caps(ray, &mut data);
+                self.intersect_caps(ray, this, &mut data);
 
                 if data.len() == 0 {
                     return (ray, None);
@@ -99,20 +95,18 @@ impl Renderable for Cylinder {
             (t0, t1) = (t1, t0);
         }
 
-        let obj = Arc::new(self.clone());
-        
         let mut data = Vec::<Intersection>::new();
         let y0 = ray.position(t0).get_y();
         if self.get_min() < y0 && self.get_max() > y0 {
-            data.push(Intersection::new(t0, obj.clone(), self.normal_at_local_space(ray.position(t0))));
+            data.push(Intersection::new(t0, this.clone()));
         }
 
         let y1 = ray.position(t1).get_y();
         if self.get_min() < y1 && self.get_max() > y1 { // BUG: corner cases fail here bc of floating point imprecision
-            data.push(Intersection::new(t1, obj.clone(), self.normal_at_local_space(ray.position(t1))));
+            data.push(Intersection::new(t1, this.clone()));
         }
 
-        self.intersect_caps(ray, &mut data);
+        self.intersect_caps(ray, this, &mut data);
 
         if data.len() == 0 {
             return (ray, None);
@@ -122,14 +116,43 @@ impl Renderable for Cylinder {
     }
 
     fn normal_at(&self, pos: Coord) -> Coord {
-        let pos = self.get_transformation().inverse().unwrap() * pos;
+        let pos = self.get_inverse() * pos;
         self.normal_at_local_space(pos)
     }
 
+    /// cylindrical mapping, u goes around the y axis and v repeats every unit along it
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        let theta = p.get_x().atan2(p.get_z());
+        (1.0 - (theta / (2.0 * PI) + 0.5), p.get_y().rem_euclid(1.0))
+    }
+
+    /// uniform over the side and caps, `None` when the cylinder is unbounded
+    fn sample_surface(&self, u: [f32; 3]) -> Option<SurfaceSample> {
+        let (min, max) = (self.get_min(), self.get_max());
+        if !min.is_finite() || !max.is_finite() || max <= min {
+            return None;
+        }
+        let side = 2.0 * PI * (max - min);
+        let caps = if self.get_closed() { 2.0 * PI } else { 0.0 };
+        let pick = u[2] * (side + caps);
+        let (point, normal) = if pick < side {
+            let phi = 2.0 * PI * u[0];
+            let y = min + u[1] * (max - min);
+            (Coord::point(phi.cos(), y, phi.sin()), Coord::vec(phi.cos(), 0.0, phi.sin()))
+        } else {
+            let (r, phi) = (u[0].sqrt(), 2.0 * PI * u[1]);
+            let (y, ny) = if pick - side < PI { (min, -1.0) } else { (max, 1.0) };
+            (Coord::point(r * phi.cos(), y, r * phi.sin()), Coord::vec(0.0, ny, 0.0))
+        };
+        Some(SurfaceSample::from_local(self, point, normal, 1.0 / (side + caps)))
+    }
+
     fn default() -> Self where Self: Sized {
         Self { 
             transformation: Matrix::identity(4), 
-            material: Material::default(),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
             min: -f32::INFINITY,
             max: f32::INFINITY,
             closed: false
@@ -139,6 +162,7 @@ impl Renderable for Cylinder {
 
 #[cfg(test)]
 mod tests {
+    use std::f32::consts::PI;
     use test_case::test_case;
     use crate::{coord::Coord, material::Material, matrix::Matrix, primitives::cylinder::Cylinder, ray::Ray, renderable::Renderable};
 
@@ -184,7 +208,7 @@ mod tests {
     #[test_case(Coord::point(0.0, 1.0, -5.0), Coord::vec(0.0, 0.0, 1.0), 0 ; "case 5")]
     #[test_case(Coord::point(0.0, 1.5, -2.0), Coord::vec(0.0, 0.0, 1.0), 2 ; "case 6")]
     fn test_truncated_intersection(point: Coord, direction: Coord, count: usize) {
-        let c = Cylinder::new(Matrix::identity(4), Material::default(), 1.0, 2.0, false);
+        let c = Cylinder::new(Matrix::identity(4), Material::default(), 1.0, 2.0, false).unwrap();
         let direction = direction.normalized();
         let ray = Ray::new(point, direction);
         let xs =  c.intersect(ray);
@@ -201,10 +225,32 @@ mod tests {
     #[test_case(Coord::point(0.0, 0.0, -2.0), Coord::vec(0.0, 1.0, 2.0) ; "case 4")]
     #[test_case(Coord::point(0.0, -1.0, -2.0), Coord::vec(0.0, 1.0, 1.0) ; "case 5")]
     fn test_caps(point: Coord, direction: Coord) {
-        let c = Cylinder::new(Matrix::identity(4), Material::default(), 1.0, 2.0, true);
+        let c = Cylinder::new(Matrix::identity(4), Material::default(), 1.0, 2.0, true).unwrap();
         let direction = direction.normalized();
         let ray = Ray::new(point, direction);
         let xs = c.intersect(ray).unwrap();
         assert_eq!(xs.len(), 2);
     } 
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sample_surface() {
+        assert!(Cylinder::default().sample_surface([0.5, 0.5, 0.5]).is_none());
+        let c = Cylinder::new(Matrix::scaling(2.0, 1.0, 2.0), Material::default(), 0.0, 3.0, true).unwrap();
+        let n = 16;
+        let mut inverse_pdf = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..n {
+                    let u = [(i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32, (k as f32 + 0.5) / n as f32];
+                    let sample = c.sample_surface(u).unwrap();
+                    let p = sample.get_point();
+                    assert!((p.get_x().hypot(p.get_z()) - 2.0).abs() < 0.0001 || p.get_y() == 0.0 || p.get_y() == 3.0);
+                    inverse_pdf += 1.0 / sample.get_pdf();
+                }
+            }
+        }
+        // side 2 pi r h plus two caps of pi r^2
+        let area = inverse_pdf / (n * n * n) as f32;
+        assert!((area - (12.0 * PI + 8.0 * PI)).abs() < 0.01, "{area}");
+    }
+}
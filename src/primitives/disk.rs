@@ -0,0 +1,158 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use crate::{coord::Coord, error::{Result, in_range}, impl_getters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType, SurfaceSample}, tex::color::Color};
+
+static EPSILON: f32 = 0.00001;
+
+/// a flat disk of radius 1 in the xz plane facing up, or a ring when `inner` is above 0
+#[derive(Clone, PartialEq)]
+pub struct Disk {
+    transformation: Matrix,
+    inverse: Matrix,
+    material: Arc<Material>,
+    inner: f32,
+}
+
+impl_getters!(Disk, inner: f32);
+
+impl Disk {
+    /// returns an error if the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material) -> Result<Self> {
+        Ok(Self { inverse: transformation.transformation_inverse()?, transformation, material: Arc::new(material), inner: 0.0 })
+    }
+
+    /// a ring with the middle up to radius `inner` cut out, returns an error if the transformation
+    /// can't be inverted or `inner` isn't in [0, 1)
+    pub fn annulus(transformation: Matrix, material: Material, inner: f32) -> Result<Self> {
+        in_range("inner radius", inner, 0.0, 1.0 - f32::EPSILON)?;
+        Ok(Self { inner, ..Self::new(transformation, material)? })
+    }
+
+    fn normal_at_local_space(&self) -> Coord {
+        (self.get_inverse().transpose() * Coord::vec(0.0, 1.0, 0.0)).to_vec().normalized()
+    }
+}
+
+impl_renderable_base!(Disk, RenderableType::Disk);
+
+impl_renderable_tests!(crate::primitives::disk::Disk, RenderableType::Disk);
+
+impl Renderable for Disk {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
+        if ray.get_direction().get_y().abs() < EPSILON {
+            return (ray, None);
+        }
+        let t = -ray.get_origin().get_y() / ray.get_direction().get_y();
+        let p = ray.position(t);
+        let dist2 = p.get_x().powi(2) + p.get_z().powi(2);
+        if dist2 > 1.0 || dist2 < self.inner.powi(2) {
+            return (ray, None);
+        }
+        (ray, Some(vec![Intersection::new(t, this.clone())]))
+    }
+
+    fn normal_at(&self, _pos: Coord) -> Coord {
+        self.normal_at_local_space()
+    }
+
+    /// polar mapping, u goes around the y axis and v from the inner edge to the outer one
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        let theta = p.get_x().atan2(p.get_z());
+        let r = p.get_x().hypot(p.get_z());
+        (1.0 - (theta / (2.0 * PI) + 0.5), ((r - self.inner) / (1.0 - self.inner)).clamp(0.0, 1.0))
+    }
+
+    /// uniform over the disk or ring
+    fn sample_surface(&self, u: [f32; 3]) -> Option<SurfaceSample> {
+        let inner2 = self.inner * self.inner;
+        let r = (inner2 + u[0] * (1.0 - inner2)).sqrt();
+        let phi = 2.0 * PI * u[1];
+        let point = Coord::point(r * phi.cos(), 0.0, r * phi.sin());
+        let pdf = 1.0 / (PI * (1.0 - inner2));
+        Some(SurfaceSample::from_local(self, point, Coord::vec(0.0, 1.0, 0.0), pdf))
+    }
+
+    fn default() -> Self {
+        Self {
+            transformation: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
+            inner: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Coord::point(0.0, 1.0, 0.0), Some(1.0) ; "center")]
+    #[test_case(Coord::point(0.6, 2.0, -0.6), Some(2.0) ; "inside")]
+    #[test_case(Coord::point(1.0, -1.0, 0.0), Some(1.0) ; "edge from below")]
+    #[test_case(Coord::point(0.8, 1.0, 0.8), None ; "outside")]
+    fn test_intersect(origin: Coord, t: Option<f32>) {
+        let d = Disk::default();
+        let dir = if origin.get_y() > 0.0 { Coord::vec(0.0, -1.0, 0.0) } else { Coord::vec(0.0, 1.0, 0.0) };
+        let xs = d.intersect(Ray::new(origin, dir));
+        assert_eq!(xs.map(|xs| xs[0].get_time()), t);
+    }
+
+    #[test]
+    fn test_parallel_miss() {
+        let d = Disk::default();
+        assert!(d.intersect(Ray::new(Coord::point(-2.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0))).is_none());
+    }
+
+    #[test_case(0.0, true ; "hole")]
+    #[test_case(0.7, true ; "ring")]
+    #[test_case(1.2, false ; "outside")]
+    fn test_annulus(x: f32, hit: bool) {
+        let ring = Disk::annulus(Matrix::identity(4), Material::default(), 0.5).unwrap();
+        let xs = ring.intersect(Ray::new(Coord::point(x, 1.0, 0.0), Coord::vec(0.0, -1.0, 0.0)));
+        assert_eq!(xs.is_some(), hit && x >= 0.5);
+        assert!(Disk::annulus(Matrix::identity(4), Material::default(), 1.0).is_err());
+        assert!(Disk::annulus(Matrix::identity(4), Material::default(), -0.1).is_err());
+    }
+
+    #[test]
+    fn test_normal_and_transform() {
+        let d = Disk::new(Matrix::translation(0.0, 0.0, 5.0) * Matrix::rotation_x(-PI / 2.0) * Matrix::scaling(2.0, 2.0, 2.0), Material::default()).unwrap();
+        // now a disk of radius 2 facing -z at z = 5
+        let n = d.normal_at(Coord::point(0.0, 0.0, 5.0));
+        assert!((n - Coord::vec(0.0, 0.0, -1.0)).magnitude() < 0.0001);
+        let xs = d.intersect(Ray::new(Coord::point(1.5, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0))).unwrap();
+        assert!((xs[0].get_time() - 5.0).abs() < 0.0001);
+        assert!(d.intersect(Ray::new(Coord::point(2.5, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0))).is_none());
+    }
+
+    #[test]
+    fn test_uv_at() {
+        let ring = Disk::annulus(Matrix::identity(4), Material::default(), 0.5).unwrap();
+        assert_eq!(ring.uv_at(Coord::point(0.0, 0.0, -0.5)), (0.0, 0.0));
+        assert_eq!(ring.uv_at(Coord::point(0.0, 0.0, 1.0)), (0.5, 1.0));
+        assert_eq!(ring.uv_at(Coord::point(-0.75, 0.0, 0.0)), (0.75, 0.5));
+    }
+
+    #[test]
+    fn test_sample_surface() {
+        let mut ring = Disk::annulus(Matrix::identity(4), Material::default(), 0.5).unwrap();
+        ring.set_transformation(Matrix::scaling(2.0, 1.0, 2.0)).unwrap();
+        let n = 20;
+        let mut inverse_pdf = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let sample = ring.sample_surface([(i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32, 0.5]).unwrap();
+                let p = sample.get_point();
+                let r = p.get_x().hypot(p.get_z());
+                assert!((1.0..=2.0).contains(&r) && p.get_y() == 0.0);
+                inverse_pdf += 1.0 / sample.get_pdf();
+            }
+        }
+        let area = inverse_pdf / (n * n) as f32;
+        assert!((area - PI * 3.0).abs() < 0.01, "{area}");
+    }
+}
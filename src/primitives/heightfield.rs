@@ -0,0 +1,376 @@
+use std::sync::Arc;
+
+use super::triangle::intersect_triangle;
+use crate::{canvas::Canvas, coord::Coord, error::{Result, RtcError}, impl_getters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::{color::Color, scalar::ScalarMap}};
+
+static EPSILON: f32 = 0.00001;
+
+/// terrain from a grid of height samples, spread over [0, 1] along x and z in object space
+///
+/// sample (ix, iz) sits at x = ix / (width - 1), z = iz / (depth - 1) and y = its height. each
+/// cell between four samples is two triangles, split along the diagonal from the low x low z
+/// corner, and normals are blended between the samples so the terrain shades smoothly. scale the
+/// object to size it and rays only visit the cells they pass over
+#[derive(Clone, PartialEq)]
+pub struct Heightfield {
+    transformation: Matrix,
+    inverse: Matrix,
+    material: Arc<Material>,
+    width: usize,
+    depth: usize,
+    heights: Arc<Vec<f32>>,
+    min_height: f32,
+    max_height: f32,
+}
+
+impl_getters!(Heightfield, width: usize, depth: usize, heights: Arc<Vec<f32>>);
+
+impl Heightfield {
+    /// `heights` is row by row, `width` samples along x for each of the `depth` rows along z.
+    /// returns an error if the grid is smaller than 2x2, the number of heights doesn't match or
+    /// the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material, width: usize, depth: usize, heights: Vec<f32>) -> Result<Self> {
+        if width < 2 || depth < 2 || heights.len() != width * depth {
+            return Err(RtcError::InvalidGrid { width, depth, samples: heights.len() });
+        }
+        let min_height = heights.iter().copied().fold(f32::INFINITY, f32::min);
+        let max_height = heights.iter().copied().fold(-f32::INFINITY, f32::max);
+        Ok(Self {
+            inverse: transformation.transformation_inverse()?,
+            transformation,
+            material: Arc::new(material),
+            width,
+            depth,
+            heights: Arc::new(heights),
+            min_height,
+            max_height,
+        })
+    }
+
+    /// one sample per pixel, the average of its red, green and blue. the top row of the image
+    /// ends up at z = 0
+    pub fn from_canvas(transformation: Matrix, material: Material, canvas: &Canvas) -> Result<Self> {
+        let (width, depth) = (canvas.get_width(), canvas.get_height());
+        let heights = (0..depth)
+            .flat_map(|z| (0..width).map(move |x| (z, x)))
+            .map(|(z, x)| {
+                let c = canvas.get_pixel(x, z);
+                (c.get_r() + c.get_g() + c.get_b()) / 3.0
+            })
+            .collect();
+        Self::new(transformation, material, width, depth, heights)
+    }
+
+    /// samples `height(x, z)` for x and z across [0, 1]
+    pub fn from_fn(transformation: Matrix, material: Material, width: usize, depth: usize, height: impl Fn(f32, f32) -> f32) -> Result<Self> {
+        let step = |i: usize, n: usize| i as f32 / (n.max(2) - 1) as f32;
+        let heights = (0..depth)
+            .flat_map(|z| (0..width).map(move |x| (z, x)))
+            .map(|(z, x)| height(step(x, width), step(z, depth)))
+            .collect();
+        Self::new(transformation, material, width, depth, heights)
+    }
+
+    /// samples a scalar map (eg a `Noise`) at (x, 0, z) for x and z across [0, 1]
+    pub fn from_map(transformation: Matrix, material: Material, width: usize, depth: usize, map: &ScalarMap) -> Result<Self> {
+        Self::from_fn(transformation, material, width, depth, |x, z| map.value_at(Coord::point(x, 0.0, z)))
+    }
+
+    pub fn get_height(&self, ix: usize, iz: usize) -> f32 {
+        self.heights[iz * self.width + ix]
+    }
+
+    fn cells(&self) -> (usize, usize) {
+        (self.width - 1, self.depth - 1)
+    }
+
+    fn vertex(&self, ix: usize, iz: usize) -> Coord {
+        let (cw, cd) = self.cells();
+        Coord::point(ix as f32 / cw as f32, self.get_height(ix, iz), iz as f32 / cd as f32)
+    }
+
+    /// normal at a sample from the slope to its neighbours
+    fn vertex_normal(&self, ix: usize, iz: usize) -> Coord {
+        let (cw, cd) = self.cells();
+        let (x0, x1) = (ix.saturating_sub(1), (ix + 1).min(cw));
+        let (z0, z1) = (iz.saturating_sub(1), (iz + 1).min(cd));
+        let slope_x = (self.get_height(x1, iz) - self.get_height(x0, iz)) * cw as f32 / (x1 - x0) as f32;
+        let slope_z = (self.get_height(ix, z1) - self.get_height(ix, z0)) * cd as f32 / (z1 - z0) as f32;
+        Coord::vec(-slope_x, 1.0, -slope_z).normalized()
+    }
+
+    /// cell holding local (x, z) and where in it the point is, as fractions of the cell
+    fn cell_at(&self, x: f32, z: f32) -> (usize, usize, f32, f32) {
+        let (cw, cd) = self.cells();
+        let (gx, gz) = (x.clamp(0.0, 1.0) * cw as f32, z.clamp(0.0, 1.0) * cd as f32);
+        let (ix, iz) = ((gx.floor() as usize).min(cw - 1), (gz.floor() as usize).min(cd - 1));
+        (ix, iz, gx - ix as f32, gz - iz as f32)
+    }
+
+    fn normal_at_local_space(&self, pos: Coord) -> Coord {
+        let (ix, iz, fx, fz) = self.cell_at(pos.get_x(), pos.get_z());
+        let n00 = self.vertex_normal(ix, iz);
+        let n11 = self.vertex_normal(ix + 1, iz + 1);
+        // barycentric weights within whichever of the two triangles holds the point
+        let local_normal = if fx >= fz {
+            n00 * (1.0 - fx) + self.vertex_normal(ix + 1, iz) * (fx - fz) + n11 * fz
+        } else {
+            n00 * (1.0 - fz) + n11 * fx + self.vertex_normal(ix, iz + 1) * (fz - fx)
+        };
+        let mut out = self.get_inverse().transpose() * local_normal;
+        out.set_w(0.0);
+        out.normalized()
+    }
+
+    /// times where the ray crosses the two triangles of a cell
+    fn intersect_cell(&self, origin: Coord, dir: Coord, ix: usize, iz: usize, out: &mut Vec<f32>) {
+        let p00 = self.vertex(ix, iz);
+        let p10 = self.vertex(ix + 1, iz);
+        let p01 = self.vertex(ix, iz + 1);
+        let p11 = self.vertex(ix + 1, iz + 1);
+        out.extend(intersect_triangle(origin, dir, p00, p10, p11).map(|(t, _, _)| t));
+        out.extend(intersect_triangle(origin, dir, p00, p11, p01).map(|(t, _, _)| t));
+    }
+
+    /// ray times of the hits with the terrain in object space, sorted
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        let (origin, dir) = (ray.get_origin(), ray.get_direction());
+
+        // clip the ray to the box around the terrain
+        let (mut t0, mut t1) = (-f32::INFINITY, f32::INFINITY);
+        for (o, d, lo, hi) in [
+            (origin.get_x(), dir.get_x(), 0.0, 1.0),
+            (origin.get_y(), dir.get_y(), self.min_height - EPSILON, self.max_height + EPSILON),
+            (origin.get_z(), dir.get_z(), 0.0, 1.0),
+        ] {
+            if d.abs() < f32::EPSILON {
+                if o < lo || o > hi {
+                    return Vec::new();
+                }
+                continue;
+            }
+            let (a, b) = ((lo - o) / d, (hi - o) / d);
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        if t0 > t1 || !t0.is_finite() || !t1.is_finite() {
+            return Vec::new();
+        }
+
+        // walk the cells the ray passes over in order (a 2d DDA over x and z)
+        let (cw, cd) = self.cells();
+        let start = origin + dir * t0;
+        let (mut ix, mut iz, _, _) = self.cell_at(start.get_x(), start.get_z());
+        let axis = |o: f32, d: f32, i: usize, cells: usize| -> (isize, f32, f32) {
+            let size = 1.0 / cells as f32;
+            if d > f32::EPSILON {
+                (1, ((i + 1) as f32 * size - o) / d, size / d)
+            } else if d < -f32::EPSILON {
+                (-1, (i as f32 * size - o) / d, -size / d)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_x, mut next_x, delta_x) = axis(origin.get_x(), dir.get_x(), ix, cw);
+        let (step_z, mut next_z, delta_z) = axis(origin.get_z(), dir.get_z(), iz, cd);
+
+        let mut hits = Vec::new();
+        let mut cell_hits = Vec::new();
+        let mut t_start = t0;
+        loop {
+            let t_end = next_x.min(next_z).min(t1);
+            cell_hits.clear();
+            self.intersect_cell(origin, dir, ix, iz, &mut cell_hits);
+            // a hit right on the edge between cells shows up in both, keep it once
+            hits.extend(cell_hits.iter().filter(|t| **t >= t_start - EPSILON && **t <= t_end + EPSILON));
+            if t_end >= t1 {
+                break;
+            }
+            if next_x < next_z {
+                match ix.checked_add_signed(step_x) {
+                    Some(i) if i < cw => ix = i,
+                    _ => break,
+                }
+                next_x += delta_x;
+            } else {
+                match iz.checked_add_signed(step_z) {
+                    Some(i) if i < cd => iz = i,
+                    _ => break,
+                }
+                next_z += delta_z;
+            }
+            t_start = t_end;
+        }
+        hits.sort_by(f32::total_cmp);
+        hits.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+        hits
+    }
+}
+
+impl_renderable_base!(Heightfield, RenderableType::Heightfield);
+
+impl_renderable_tests!(crate::primitives::heightfield::Heightfield, RenderableType::Heightfield);
+
+impl Renderable for Heightfield {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
+        let ts = self.local_intersect(&ray);
+        if ts.is_empty() {
+            return (ray, None);
+        }
+
+        let data = ts.into_iter()
+            .map(|t| Intersection::new(t, this.clone()))
+            .collect();
+        (ray, Some(data))
+    }
+
+    fn normal_at(&self, pos: Coord) -> Coord {
+        let pos = self.get_inverse() * pos;
+        self.normal_at_local_space(pos)
+    }
+
+    /// the whole terrain maps once onto [0, 1], u along x and v along z
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        (p.get_x().clamp(0.0, 1.0), p.get_z().clamp(0.0, 1.0))
+    }
+
+    /// a flat 2x2 grid at height 0
+    fn default() -> Self {
+        Self {
+            transformation: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
+            width: 2,
+            depth: 2,
+            heights: Arc::new(vec![0.0; 4]),
+            min_height: 0.0,
+            max_height: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::{primitives::sphere::Sphere, tex::{noise::Noise, scalar::Channel}, world::World};
+
+    use super::*;
+
+    fn close(a: Coord, b: Coord) -> bool {
+        (a - b).magnitude() < 0.0001
+    }
+
+    fn times(h: &Heightfield, ray: Ray) -> Vec<f32> {
+        h.intersect(ray).map(|xs| xs.iter().map(|x| x.get_time()).collect()).unwrap_or_default()
+    }
+
+    /// a 3x3 grid, flat at 0 with the middle raised to 1
+    fn peak() -> Heightfield {
+        let mut heights = vec![0.0; 9];
+        heights[4] = 1.0;
+        Heightfield::new(Matrix::identity(4), Material::default(), 3, 3, heights).unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(
+            Heightfield::new(Matrix::identity(4), Material::default(), 3, 2, vec![0.0; 5]).err(),
+            Some(RtcError::InvalidGrid { width: 3, depth: 2, samples: 5 })
+        );
+        assert!(Heightfield::new(Matrix::identity(4), Material::default(), 1, 4, vec![0.0; 4]).is_err());
+        let h = Heightfield::from_fn(Matrix::identity(4), Material::default(), 3, 2, |x, z| x + 10.0 * z).unwrap();
+        assert_eq!(*h.get_heights(), vec![0.0, 0.5, 1.0, 10.0, 10.5, 11.0]);
+    }
+
+    #[test]
+    fn test_from_canvas() {
+        let mut c = Canvas::new(2, 2);
+        c.set_pixel(1, 0, Color::white());
+        c.set_pixel(0, 1, Color::new(0.3, 0.6, 0.9, 0.0));
+        let h = Heightfield::from_canvas(Matrix::identity(4), Material::default(), &c).unwrap();
+        assert_eq!(h.get_height(0, 0), 0.0);
+        assert_eq!(h.get_height(1, 0), 1.0);
+        assert!((h.get_height(0, 1) - 0.6).abs() < 0.0001);
+    }
+
+    #[test_case(Coord::point(0.3, 5.0, 0.6), Some(4.5) ; "middle")]
+    #[test_case(Coord::point(1.0, 5.0, 1.0), Some(4.5) ; "corner")]
+    #[test_case(Coord::point(1.2, 5.0, 0.6), None ; "beside")]
+    fn test_flat(origin: Coord, t: Option<f32>) {
+        let h = Heightfield::new(Matrix::identity(4), Material::default(), 2, 2, vec![0.5; 4]).unwrap();
+        let ts = times(&h, Ray::new(origin, Coord::vec(0.0, -1.0, 0.0)));
+        assert_eq!(ts.first().copied(), t);
+        if t.is_some() {
+            assert!(close(h.normal_at(Coord::point(0.3, 0.5, 0.6)), Coord::vec(0.0, 1.0, 0.0)));
+        }
+    }
+
+    #[test]
+    fn test_slope() {
+        let h = Heightfield::from_fn(Matrix::identity(4), Material::default(), 3, 3, |x, _| x).unwrap();
+        let ts = times(&h, Ray::new(Coord::point(0.25, 2.0, 0.5), Coord::vec(0.0, -1.0, 0.0)));
+        assert!(ts.len() == 1 && (ts[0] - 1.75).abs() < 0.0001, "{ts:?}");
+        assert!(close(h.normal_at(Coord::point(0.25, 0.25, 0.5)), Coord::vec(-1.0, 1.0, 0.0).normalized()));
+    }
+
+    #[test]
+    fn test_across_cells() {
+        // along z = 0.5, over the edges between cells, up one side of the peak and down the other
+        let h = peak();
+        let ts = times(&h, Ray::new(Coord::point(-1.0, 0.5, 0.5), Coord::vec(1.0, 0.0, 0.0)));
+        assert_eq!(ts.len(), 2, "{ts:?}");
+        assert!((ts[0] - 1.25).abs() < 0.0001 && (ts[1] - 1.75).abs() < 0.0001, "{ts:?}");
+        assert!(times(&h, Ray::new(Coord::point(-1.0, 1.5, 0.5), Coord::vec(1.0, 0.0, 0.0))).is_empty());
+    }
+
+    #[test]
+    fn test_smooth_normals() {
+        let h = peak();
+        // straight up at the top and tilted the same way on either side of a triangle edge
+        assert!(close(h.normal_at(Coord::point(0.5, 1.0, 0.5)), Coord::vec(0.0, 1.0, 0.0)));
+        let a = h.normal_at(Coord::point(0.25, 0.0, 0.2499));
+        let b = h.normal_at(Coord::point(0.25, 0.0, 0.2501));
+        assert!((a - b).magnitude() < 0.001);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let noise = Noise::new(Color::black(), Color::white(), 3, 7, Matrix::scaling(0.3, 0.3, 0.3)).unwrap();
+        let map = ScalarMap::new(Arc::new(noise), Channel::R);
+        let h = Heightfield::from_map(Matrix::identity(4), Material::default(), 24, 17, &map).unwrap();
+        let (cw, cd) = h.cells();
+        for i in 0..40 {
+            let a = i as f32 * 0.37;
+            let origin = Coord::point(0.5 + 1.5 * a.cos(), 1.5, 0.5 + 1.5 * a.sin());
+            let target = Coord::point(0.5 + 0.4 * (a * 2.3).sin(), 0.2, 0.5 + 0.4 * (a * 1.7).cos());
+            let ray = Ray::new(origin, target - origin);
+            let mut expected = Vec::new();
+            for iz in 0..cd {
+                for ix in 0..cw {
+                    h.intersect_cell(origin, target - origin, ix, iz, &mut expected);
+                }
+            }
+            expected.sort_by(f32::total_cmp);
+            expected.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+            let found = times(&h, ray);
+            assert_eq!(found.first().is_some(), expected.first().is_some(), "ray {i}: {found:?} {expected:?}");
+            if let (Some(f), Some(e)) = (found.first(), expected.first()) {
+                assert!((f - e).abs() < 0.0001, "ray {i}: {found:?} {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_in_world() {
+        let mut w = World::new();
+        let terrain = Heightfield::new(Matrix::scaling(10.0, 2.0, 10.0), Material::default(), 2, 2, vec![0.5; 4]).unwrap();
+        let id = w.add_obj(Arc::new(terrain));
+        w.add_obj(Arc::new(Sphere::new(Coord::point(5.0, -3.0, 5.0)).unwrap()));
+        let hit = w.cast_ray(Ray::new(Coord::point(5.0, 5.0, 5.0), Coord::vec(0.0, -1.0, 0.0))).unwrap();
+        assert_eq!(hit.get_id(), Some(id));
+        assert!((hit.get_distance() - 4.0).abs() < 0.0001);
+    }
+}
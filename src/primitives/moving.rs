@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use crate::{coord::Coord, error::Result, impl_renderable_tests, material::Material, matrix::Matrix, motion::Motion, primitives::sphere::Sphere, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
+
+/// wraps another object and moves it over time, rays are tested against it where it is at the ray's time
+///
+/// the motion is applied on top of the object's own transformation, i.e. the object at time t has
+/// `motion.at(t) * object.get_transformation()`. everything that doesn't take a ray (normals, uvs,
+/// colors) uses the object where it is at time 0
+#[derive(Clone)]
+pub struct Moving {
+    object: Arc<dyn Renderable>,
+    motion: Motion,
+}
+
+#[allow(dead_code)]
+impl Moving {
+    pub fn new(object: Arc<dyn Renderable>, motion: Motion) -> Self {
+        Self { object, motion }
+    }
+
+    pub fn get_object(&self) -> Arc<dyn Renderable> {
+        self.object.clone()
+    }
+
+    pub fn get_motion(&self) -> Motion {
+        self.motion.clone()
+    }
+
+    pub fn set_motion(&mut self, motion: Motion) {
+        self.motion = motion;
+    }
+
+    /// standalone copy of the wrapped object where it is at `time`, None if the interpolated
+    /// transformation can't be inverted
+    pub fn at_time(&self, time: f32) -> Option<Box<dyn Renderable>> {
+        let mut out = self.object.clone_dyn();
+        out.set_transformation(self.motion.at(time) * out.get_transformation()).ok()?;
+        Some(out)
+    }
+
+    /// applies `f` to a copy of the wrapped object and swaps it in
+    fn update_object(&mut self, f: impl FnOnce(&mut dyn Renderable) -> Result<()>) -> Result<()> {
+        let mut obj = self.object.clone_dyn();
+        f(obj.as_mut())?;
+        self.object = Arc::from(obj);
+        Ok(())
+    }
+}
+
+impl PartialEq for Moving {
+    fn eq(&self, other: &Self) -> bool {
+        self.object.compare(other.object.clone()) && self.motion == other.motion
+    }
+}
+
+impl RenderableBase for Moving {
+    fn get_material(&self) -> Material { self.object.get_material() }
+    fn set_material(&mut self, mat: Material) {
+        self.update_object(|o| { o.set_material(mat); Ok(()) }).unwrap();
+    }
+    fn get_shared_material(&self) -> Arc<Material> { self.object.get_shared_material() }
+    fn set_shared_material(&mut self, mat: Arc<Material>) {
+        self.update_object(|o| { o.set_shared_material(mat); Ok(()) }).unwrap();
+    }
+    fn get_pos(&self) -> Coord { self.object.get_pos() }
+    fn get_transformation(&self) -> Matrix { self.object.get_transformation() }
+    fn get_inverse(&self) -> Matrix { self.object.get_inverse() }
+    fn set_transformation(&mut self, transform: Matrix) -> Result<()> {
+        self.update_object(|o| o.set_transformation(transform))
+    }
+    fn apply_transformation(&mut self, transform: Matrix) -> Result<()> {
+        self.update_object(|o| o.apply_transformation(transform))
+    }
+    fn get_type(&self) -> RenderableType { RenderableType::Moving }
+    fn clone_rc(&self) -> Arc<dyn Renderable> { Arc::new(self.clone()) }
+    fn clone_dyn(&self) -> Box<dyn Renderable> { Box::new(self.clone()) }
+    fn get_color_at(&self, pos: Coord) -> Color { self.object.get_color_at(pos) }
+    fn as_any(&self) -> &dyn std::any::Any { self }
+    fn compare(&self, other: Arc<dyn Renderable>) -> bool {
+        match other.as_any().downcast_ref::<Moving>() {
+            Some(p) => self == p,
+            None => false
+        }
+    }
+}
+
+impl_renderable_tests!(crate::primitives::moving::Moving, RenderableType::Moving);
+
+impl Renderable for Moving {
+    /// hits are against the wrapped object at the ray's time, so they are shaded with that snapshot
+    /// but still belong to `this`
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let snapshot: Arc<dyn Renderable> = match self.at_time(ray.get_time()) {
+            Some(obj) => Arc::from(obj),
+            None => return (ray, None),
+        };
+        let (ray, xs) = snapshot.intersect_shared(&snapshot, ray);
+        (ray, xs.map(|xs| xs.into_iter().map(|x| x.with_owner(this.clone())).collect()))
+    }
+
+    fn normal_at(&self, pos: Coord) -> Coord {
+        self.object.normal_at(pos)
+    }
+
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        self.object.uv_at(pos)
+    }
+
+    fn default() -> Self where Self: Sized {
+        Self { object: Arc::new(<Sphere as Renderable>::default()), motion: Motion::default() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moving_sphere() -> Moving {
+        let motion = Motion::linear(Matrix::identity(4), Matrix::translation(4.0, 0.0, 0.0)).unwrap();
+        Moving::new(Arc::new(<Sphere as Renderable>::default()), motion)
+    }
+
+    #[test]
+    fn test_intersect_at_ray_time() {
+        let s = moving_sphere();
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        let xs = s.intersect(r).unwrap();
+        assert_eq!(xs[0].get_time(), 4.0);
+        assert_eq!(xs[1].get_time(), 6.0);
+        // by time 0.5 the sphere has moved 2 units, clear of the ray
+        assert!(s.intersect(r.with_time(0.5)).is_none());
+        let r = Ray::new(Coord::point(4.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0)).with_time(1.0);
+        assert_eq!(s.intersect(r).unwrap()[0].get_time(), 4.0);
+    }
+
+    #[test]
+    fn test_hit_object_is_snapshot() {
+        let s = moving_sphere();
+        let r = Ray::new(Coord::point(2.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0)).with_time(0.5);
+        let xs = s.intersect(r).unwrap();
+        let obj = xs[0].get_object();
+        assert_eq!(obj.get_type(), RenderableType::Sphere);
+        assert_eq!(obj.get_transformation(), Matrix::translation(2.0, 0.0, 0.0));
+        assert_eq!(obj.normal_at(Coord::point(2.0, 0.0, -1.0)), Coord::vec(0.0, 0.0, -1.0));
+        // but the hit still belongs to the moving object
+        assert_eq!(xs[0].get_owner().get_type(), RenderableType::Moving);
+    }
+
+    #[test]
+    fn test_motion_on_top_of_transformation() {
+        let mut s = moving_sphere();
+        s.set_transformation(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
+        let snapshot = s.at_time(1.0).unwrap();
+        assert_eq!(snapshot.get_transformation(), Matrix::translation(4.0, 0.0, 0.0) * Matrix::scaling(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn test_singular_motion_misses() {
+        let motion = Motion::linear(Matrix::scaling(1.0, 1.0, 1.0), Matrix::scaling(-1.0, 1.0, 1.0)).unwrap();
+        let s = Moving::new(Arc::new(<Sphere as Renderable>::default()), motion);
+        assert!(s.at_time(0.5).is_none());
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0)).with_time(0.5);
+        assert!(s.intersect(r).is_none());
+    }
+}
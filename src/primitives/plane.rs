@@ -1,27 +1,27 @@
 use std::sync::Arc;
 
-use crate::{tex::color::Color, coord::Coord, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}};
+use crate::{tex::color::Color, coord::Coord, error::Result, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}};
 
 
 #[derive(Clone, PartialEq)]
 pub struct Plane {
     transformation: Matrix,
-    material: Material,
+    inverse: Matrix,
+    material: Arc<Material>,
 }
 
 #[allow(dead_code)]
 impl Plane {
 
-    pub fn new(transformation: Matrix, material: Material) -> Self {
-        Self { transformation, material }
+    /// returns an error if the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material) -> Result<Self> {
+        Ok(Self { inverse: transformation.transformation_inverse()?, transformation, material: Arc::new(material) })
     }
 
     /// normal is always straight up (in local space) regardless of pos, then
     /// converted back to world space via transpose(inverse) of the transform
     fn normal_at_local_space(&self, _pos: Coord) -> Coord {
-        let out = self.get_transformation()
-            .inverse()
-            .unwrap()
+        let out = self.get_inverse()
             .transpose()
             * Coord::vec(0.0, 1.0, 0.0);
         out
@@ -35,33 +35,34 @@ impl_renderable_base!(Plane, RenderableType::Plane);
 
 impl Renderable for Plane {
     
-    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
-        let (_, out) = self.intersect_get_ray(ray);
-        out
-    }
-
-    fn intersect_get_ray(&self, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
         // plane only exists on xz plane in local space (before transformation is applied)
-        let ray = ray.transform(self.get_transformation().inverse().unwrap()); 
+        let ray = ray.transform(self.get_inverse()); 
         if ray.get_direction().get_y().abs() < 0.00001 {    // TODO: need a global EPSILON value rather then this magic value
             return (ray, None);
         }
         let t = -ray.get_origin().get_y() / ray.get_direction().get_y();
         // TODO: would this work if I just returned a reference to self instead of a RC box of it?
         // TODO: is there a better way to do the RC then to make a new one here?
-        let reflection = ray.get_direction().reflect(self.normal_at_local_space(Coord::point(0.0, 0.0, 0.0)));
-        (ray, Some(vec![Intersection::new(t, Arc::new(self.clone()), reflection)]))
+        (ray, Some(vec![Intersection::new(t, this.clone())]))
     }
 
     fn normal_at(&self, pos: Coord) -> Coord {
-        let pos = self.get_transformation().inverse().unwrap() * pos;
+        let pos = self.get_inverse() * pos;
         self.normal_at_local_space(pos)
     }
 
+    /// planar mapping, the texture repeats every unit along x and z
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        (p.get_x().rem_euclid(1.0), p.get_z().rem_euclid(1.0))
+    }
+
     fn default() -> Self {
         Self {
             transformation: Matrix::identity(4),
-            material: Material::default()
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default())
         }
     }
 }
@@ -70,7 +71,7 @@ impl_renderable_tests!(crate::primitives::plane::Plane, RenderableType::Plane);
 
 #[cfg(test)]
 mod tests {
-    use crate::renderable::RenderableBase;
+    use crate::{renderable::RenderableBase, world::Comps};
     use super::*;
 
     #[test]
@@ -88,7 +89,7 @@ mod tests {
     #[test]
     fn test_normal_at_world_to_local() {
         let mut p = Plane::default();
-        p.set_transformation(Matrix::rotate_z(std::f32::consts::PI / 2.0));
+        p.set_transformation(Matrix::rotation_z(std::f32::consts::PI / 2.0)).unwrap();
         let world_point = p.get_transformation() * Coord::point(1.0, 0.0, 0.0);
         let n = p.normal_at(world_point);
         assert_eq!(n, Coord::vec(-1.0, 0.0, 0.0));
@@ -119,6 +120,7 @@ mod tests {
         let xs = p.intersect(r).unwrap();
         let xs = Intersection::aggregate_intersections(xs);
         assert_eq!(xs.len(), 1);
-        assert_eq!(xs[0].get_reflectv(), Coord::vec(0.0, 2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0))
+        let comps = Comps::prepare_computations(xs[0].clone(), r, xs);
+        assert_eq!(comps.get_reflectv(), Coord::vec(0.0, 2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0))
     }
 }
\ No newline at end of file
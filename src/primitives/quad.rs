@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use crate::{coord::Coord, error::{Result, RtcError}, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType, SurfaceSample}, tex::color::Color};
+
+static EPSILON: f32 = 0.00001;
+
+/// a flat square from -1 to 1 along x and z facing up, scale or shear it into any rectangle or
+/// parallelogram
+#[derive(Clone, PartialEq)]
+pub struct Quad {
+    transformation: Matrix,
+    inverse: Matrix,
+    material: Arc<Material>,
+}
+
+impl Quad {
+    /// returns an error if the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material) -> Result<Self> {
+        Ok(Self { inverse: transformation.transformation_inverse()?, transformation, material: Arc::new(material) })
+    }
+
+    /// the parallelogram with a corner at `corner` spanned by `edge_u` and `edge_v`, facing along
+    /// `edge_u x edge_v`. returns an error if the edges are parallel or not vecs
+    pub fn from_corner(corner: Coord, edge_u: Coord, edge_v: Coord, material: Material) -> Result<Self> {
+        if !corner.is_point() {
+            return Err(RtcError::ExpectedPoint);
+        }
+        if !edge_u.is_vec() || !edge_v.is_vec() {
+            return Err(RtcError::ExpectedVector);
+        }
+        let normal = edge_u.cross(&edge_v);
+        if normal.magnitude() < EPSILON {
+            return Err(RtcError::SingularTransformation);
+        }
+        let normal = normal.normalized();
+        let center = corner + (edge_u + edge_v) * 0.5;
+        // local x runs along edge_u, z along edge_v and y along the normal
+        let transformation = Matrix::new(vec![
+            vec![edge_u.get_x() / 2.0, normal.get_x(), edge_v.get_x() / 2.0, center.get_x()],
+            vec![edge_u.get_y() / 2.0, normal.get_y(), edge_v.get_y() / 2.0, center.get_y()],
+            vec![edge_u.get_z() / 2.0, normal.get_z(), edge_v.get_z() / 2.0, center.get_z()],
+            vec![0.0, 0.0, 0.0, 1.0],
+        ]);
+        Self::new(transformation, material)
+    }
+
+    fn normal_at_local_space(&self) -> Coord {
+        (self.get_inverse().transpose() * Coord::vec(0.0, 1.0, 0.0)).to_vec().normalized()
+    }
+}
+
+impl_renderable_base!(Quad, RenderableType::Quad);
+
+impl_renderable_tests!(crate::primitives::quad::Quad, RenderableType::Quad);
+
+impl Renderable for Quad {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
+        if ray.get_direction().get_y().abs() < EPSILON {
+            return (ray, None);
+        }
+        let t = -ray.get_origin().get_y() / ray.get_direction().get_y();
+        let p = ray.position(t);
+        if p.get_x().abs() > 1.0 || p.get_z().abs() > 1.0 {
+            return (ray, None);
+        }
+        (ray, Some(vec![Intersection::new(t, this.clone())]))
+    }
+
+    fn normal_at(&self, _pos: Coord) -> Coord {
+        self.normal_at_local_space()
+    }
+
+    /// the whole quad maps once onto [0, 1], u along x and v along z
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        (((p.get_x() + 1.0) / 2.0).clamp(0.0, 1.0), ((p.get_z() + 1.0) / 2.0).clamp(0.0, 1.0))
+    }
+
+    /// uniform over the quad
+    fn sample_surface(&self, u: [f32; 3]) -> Option<SurfaceSample> {
+        let point = Coord::point(2.0 * u[0] - 1.0, 0.0, 2.0 * u[1] - 1.0);
+        Some(SurfaceSample::from_local(self, point, Coord::vec(0.0, 1.0, 0.0), 0.25))
+    }
+
+    fn default() -> Self {
+        Self {
+            transformation: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn close(a: Coord, b: Coord) -> bool {
+        (a - b).magnitude() < 0.0001
+    }
+
+    #[test_case(Coord::point(0.0, 1.0, 0.0), true ; "center")]
+    #[test_case(Coord::point(0.9, 1.0, -0.9), true ; "corner")]
+    #[test_case(Coord::point(1.0, 1.0, 1.0), true ; "edge")]
+    #[test_case(Coord::point(1.1, 1.0, 0.0), false ; "past x")]
+    #[test_case(Coord::point(0.0, 1.0, -1.1), false ; "past z")]
+    fn test_intersect(origin: Coord, hit: bool) {
+        let q = Quad::default();
+        let xs = q.intersect(Ray::new(origin, Coord::vec(0.0, -1.0, 0.0)));
+        assert_eq!(xs.is_some(), hit);
+        if let Some(xs) = xs {
+            assert_eq!(xs[0].get_time(), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_from_corner() {
+        // a 4 x 2 sign standing in the xy plane facing -z
+        let q = Quad::from_corner(Coord::point(0.0, 0.0, 3.0), Coord::vec(0.0, 2.0, 0.0), Coord::vec(4.0, 0.0, 0.0), Material::default()).unwrap();
+        assert!(close(q.normal_at(Coord::point(1.0, 1.0, 3.0)), Coord::vec(0.0, 0.0, -1.0)));
+        let xs = q.intersect(Ray::new(Coord::point(3.5, 1.5, 0.0), Coord::vec(0.0, 0.0, 1.0))).unwrap();
+        assert!((xs[0].get_time() - 3.0).abs() < 0.0001);
+        assert!(q.intersect(Ray::new(Coord::point(4.5, 1.0, 0.0), Coord::vec(0.0, 0.0, 1.0))).is_none());
+        assert!(q.intersect(Ray::new(Coord::point(1.0, 2.5, 0.0), Coord::vec(0.0, 0.0, 1.0))).is_none());
+
+        let (u, v) = q.uv_at(Coord::point(1.0, 1.5, 3.0));
+        assert!((u - 0.75).abs() < 0.0001 && (v - 0.25).abs() < 0.0001);
+
+        let edge = Coord::vec(1.0, 0.0, 0.0);
+        assert_eq!(Quad::from_corner(Coord::point(0.0, 0.0, 0.0), edge, edge * 2.0, Material::default()).err(), Some(RtcError::SingularTransformation));
+        assert!(Quad::from_corner(Coord::vec(0.0, 0.0, 0.0), edge, Coord::vec(0.0, 0.0, 1.0), Material::default()).is_err());
+    }
+
+    #[test]
+    fn test_sample_surface() {
+        let q = Quad::from_corner(Coord::point(0.0, 0.0, 0.0), Coord::vec(3.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 2.0), Material::default()).unwrap();
+        let sample = q.sample_surface([0.5, 0.5, 0.0]).unwrap();
+        assert!(close(sample.get_point(), Coord::point(2.0, 0.0, 1.0)));
+        // a 3 x 2 parallelogram
+        assert!((1.0 / sample.get_pdf() - 6.0).abs() < 0.0001);
+        assert!(close(sample.get_normal(), Coord::vec(0.0, -1.0, 0.0)));
+    }
+}
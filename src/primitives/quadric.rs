@@ -0,0 +1,334 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use crate::{coord::Coord, error::{Result, RtcError}, impl_getters, impl_getters_setters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
+
+static EPSILON: f32 = 0.00001;
+
+/// the surface `f(x, y, z) = 0` of a second degree polynomial, with the inside where `f < 0`
+///
+/// the coefficients are in the order `[xx, yy, zz, xy, xz, yz, x, y, z, 1]`, so
+/// `[1, 1, 1, 0, 0, 0, 0, 0, 0, -1]` is the unit sphere. like `Cylinder` it can be cut off below
+/// `min` and above `max` along y, and `closed` puts flat caps over the inside at the cuts
+#[derive(PartialEq, Clone)]
+pub struct Quadric {
+    transformation: Matrix,
+    inverse: Matrix,
+    material: Arc<Material>,
+    coefficients: [f32; 10],
+    min: f32,
+    max: f32,
+    closed: bool,
+}
+
+impl_getters_setters!(Quadric, min: f32, max: f32, closed: bool);
+
+impl_getters!(Quadric, coefficients: [f32; 10]);
+
+#[allow(dead_code)]
+impl Quadric {
+    /// an unclipped quadric, returns an error if the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material, coefficients: [f32; 10]) -> Result<Self> {
+        Ok(Self {
+            inverse: transformation.transformation_inverse()?,
+            transformation,
+            material: Arc::new(material),
+            coefficients,
+            min: -f32::INFINITY,
+            max: f32::INFINITY,
+            closed: false,
+        })
+    }
+
+    /// from the symmetric 4x4 matrix `Q` with `p^T Q p = 0` for points `p` on the surface, a
+    /// matrix that isn't symmetric is treated as its symmetric part (which gives the same surface)
+    pub fn from_matrix(transformation: Matrix, material: Material, q: &Matrix) -> Result<Self> {
+        if q.get_size() != 4 {
+            return Err(RtcError::InvalidMatrixSize(q.get_size()));
+        }
+        let d = q.get_data();
+        let both = |i: usize, j: usize| d[i][j] + d[j][i];
+        Self::new(transformation, material, [
+            d[0][0], d[1][1], d[2][2],
+            both(0, 1), both(0, 2), both(1, 2),
+            both(0, 3), both(1, 3), both(2, 3),
+            d[3][3],
+        ])
+    }
+
+    /// `x^2 / a^2 + y^2 / b^2 + z^2 / c^2 = 1`
+    pub fn ellipsoid(a: f32, b: f32, c: f32) -> Self {
+        Self { coefficients: [1.0 / (a * a), 1.0 / (b * b), 1.0 / (c * c), 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0], ..<Self as Renderable>::default() }
+    }
+
+    /// `y = x^2 + z^2`, a dish opening upwards from the origin
+    pub fn paraboloid() -> Self {
+        Self { coefficients: [1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0], ..<Self as Renderable>::default() }
+    }
+
+    /// `x^2 + z^2 - y^2 = 1`, a cooling tower with a waist of radius 1 at y = 0
+    pub fn hyperboloid_one_sheet() -> Self {
+        Self { coefficients: [1.0, -1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0], ..<Self as Renderable>::default() }
+    }
+
+    /// `y^2 - x^2 - z^2 = 1`, two bowls facing away from each other with tips at y = 1 and -1
+    pub fn hyperboloid_two_sheets() -> Self {
+        Self { coefficients: [1.0, -1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0], ..<Self as Renderable>::default() }
+    }
+
+    /// `x^2 + z^2 = y^2`, two cones meeting at their tips at the origin
+    pub fn double_cone() -> Self {
+        Self { coefficients: [1.0, -1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0], ..<Self as Renderable>::default() }
+    }
+
+    /// the symmetric 4x4 matrix of the quadric, see `from_matrix`
+    pub fn to_matrix(&self) -> Matrix {
+        let [xx, yy, zz, xy, xz, yz, x, y, z, c] = self.coefficients;
+        Matrix::new(vec![
+            vec![xx, xy / 2.0, xz / 2.0, x / 2.0],
+            vec![xy / 2.0, yy, yz / 2.0, y / 2.0],
+            vec![xz / 2.0, yz / 2.0, zz, z / 2.0],
+            vec![x / 2.0, y / 2.0, z / 2.0, c],
+        ])
+    }
+
+    /// value of the polynomial at object space `pos`, negative inside
+    pub fn value_at(&self, pos: Coord) -> f32 {
+        let [xx, yy, zz, xy, xz, yz, x, y, z, c] = self.coefficients;
+        let (px, py, pz) = (pos.get_x(), pos.get_y(), pos.get_z());
+        xx * px * px + yy * py * py + zz * pz * pz + xy * px * py + xz * px * pz + yz * py * pz + x * px + y * py + z * pz + c
+    }
+
+    fn normal_at_local_space(&self, pos: Coord) -> Coord {
+        let local_normal = if self.closed && pos.get_y() >= self.max - EPSILON {
+            Coord::vec(0.0, 1.0, 0.0)
+        } else if self.closed && pos.get_y() <= self.min + EPSILON {
+            Coord::vec(0.0, -1.0, 0.0)
+        } else {
+            // gradient of the polynomial
+            let [xx, yy, zz, xy, xz, yz, x, y, z, _] = self.coefficients;
+            let (px, py, pz) = (pos.get_x(), pos.get_y(), pos.get_z());
+            Coord::vec(
+                2.0 * xx * px + xy * py + xz * pz + x,
+                2.0 * yy * py + xy * px + yz * pz + y,
+                2.0 * zz * pz + xz * px + yz * py + z,
+            )
+        };
+        let mut out = self.get_inverse().transpose() * local_normal;
+        out.set_w(0.0);
+        out.normalized()
+    }
+
+    /// times along the local ray where it crosses the (unclipped) surface
+    fn surface_times(&self, ray: &Ray) -> Vec<f32> {
+        let [xx, yy, zz, xy, xz, yz, x, y, z, _] = self.coefficients;
+        let (o, d) = (ray.get_origin(), ray.get_direction());
+        let (ox, oy, oz) = (o.get_x(), o.get_y(), o.get_z());
+        let (dx, dy, dz) = (d.get_x(), d.get_y(), d.get_z());
+
+        let a = xx * dx * dx + yy * dy * dy + zz * dz * dz + xy * dx * dy + xz * dx * dz + yz * dy * dz;
+        let b = 2.0 * (xx * ox * dx + yy * oy * dy + zz * oz * dz)
+            + xy * (ox * dy + oy * dx) + xz * (ox * dz + oz * dx) + yz * (oy * dz + oz * dy)
+            + x * dx + y * dy + z * dz;
+        let c = self.value_at(o);
+
+        if a.abs() < EPSILON {
+            // the ray runs parallel to an asymptote (or along a paraboloid's axis), one crossing
+            if b.abs() < EPSILON {
+                return Vec::new();
+            }
+            return vec![-c / b];
+        }
+        let disc = b * b - 4.0 * a * c;
+        if disc < 0.0 {
+            return Vec::new();
+        }
+        let q = -0.5 * (b + b.signum() * disc.sqrt());
+        let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+        vec![t0.min(t1), t0.max(t1)]
+    }
+
+    /// checks if the ray crosses a cap at time `t` inside the quadric
+    fn check_cap(&self, ray: &Ray, t: f32, y: f32) -> bool {
+        let p = ray.position(t);
+        self.value_at(Coord::point(p.get_x(), y, p.get_z())) <= 0.0
+    }
+
+    fn intersect_caps(&self, ray: &Ray, this: &Arc<dyn Renderable>, data: &mut Vec<Intersection>) {
+        if !self.closed || ray.get_direction().get_y().abs() < EPSILON {
+            return;
+        }
+        for y in [self.min, self.max] {
+            if !y.is_finite() {
+                continue;
+            }
+            let t = (y - ray.get_origin().get_y()) / ray.get_direction().get_y();
+            if self.check_cap(ray, t, y) {
+                data.push(Intersection::new(t, this.clone()));
+            }
+        }
+    }
+}
+
+impl_renderable_base!(Quadric, RenderableType::Quadric);
+
+impl_renderable_tests!(crate::primitives::quadric::Quadric, RenderableType::Quadric);
+
+impl Renderable for Quadric {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
+
+        let mut data = Vec::new();
+        for t in self.surface_times(&ray) {
+            let p = ray.position(t);
+            if self.min < p.get_y() && p.get_y() < self.max {
+                data.push(Intersection::new(t, this.clone()));
+            }
+        }
+        self.intersect_caps(&ray, this, &mut data);
+
+        if data.is_empty() {
+            return (ray, None);
+        }
+        data.sort_by(|a, b| a.get_time().total_cmp(&b.get_time()));
+        (ray, Some(data))
+    }
+
+    fn normal_at(&self, pos: Coord) -> Coord {
+        let pos = self.get_inverse() * pos;
+        self.normal_at_local_space(pos)
+    }
+
+    /// cylindrical mapping, u goes around the y axis and v repeats every unit along it
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        let theta = p.get_x().atan2(p.get_z());
+        (1.0 - (theta / (2.0 * PI) + 0.5), p.get_y().rem_euclid(1.0))
+    }
+
+    /// the unit sphere
+    fn default() -> Self where Self: Sized {
+        Self {
+            transformation: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
+            coefficients: [1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+            min: -f32::INFINITY,
+            max: f32::INFINITY,
+            closed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::primitives::sphere::Sphere;
+
+    use super::*;
+
+    fn times(obj: &dyn Renderable, ray: Ray) -> Vec<f32> {
+        obj.intersect(ray).map(|xs| xs.iter().map(|x| x.get_time()).collect()).unwrap_or_default()
+    }
+
+    fn all_close(a: &[f32], b: &[f32]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.0001)
+    }
+
+    fn close(a: Coord, b: Coord) -> bool {
+        (a - b).magnitude() < 0.0001
+    }
+
+    #[test_case(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0) ; "through the middle")]
+    #[test_case(Coord::point(0.5, 0.3, -5.0), Coord::vec(0.0, 0.0, 2.0) ; "unnormalized")]
+    #[test_case(Coord::point(0.0, 2.0, -5.0), Coord::vec(0.0, 0.0, 1.0) ; "miss")]
+    fn test_matches_sphere(origin: Coord, direction: Coord) {
+        let ray = Ray::new(origin, direction);
+        let (a, b) = (times(&Quadric::default(), ray), times(&Sphere::default(), ray));
+        assert!(all_close(&a, &b), "{a:?} {b:?}");
+    }
+
+    #[test]
+    fn test_ellipsoid() {
+        let e = Quadric::ellipsoid(2.0, 1.0, 1.0);
+        assert!(all_close(&times(&e, Ray::new(Coord::point(-5.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0))), &[3.0, 7.0]));
+        assert!(all_close(&times(&e, Ray::new(Coord::point(0.0, -5.0, 0.0), Coord::vec(0.0, 1.0, 0.0))), &[4.0, 6.0]));
+        assert!(close(e.normal_at(Coord::point(2.0, 0.0, 0.0)), Coord::vec(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_paraboloid() {
+        let mut p = Quadric::paraboloid();
+        // down the axis only the bottom of the dish is crossed
+        let down = Ray::new(Coord::point(0.0, 5.0, 0.0), Coord::vec(0.0, -1.0, 0.0));
+        assert!(all_close(&times(&p, down), &[5.0]));
+        assert!(all_close(&times(&p, Ray::new(Coord::point(-5.0, 1.0, 0.0), Coord::vec(1.0, 0.0, 0.0))), &[4.0, 6.0]));
+        assert!(close(p.normal_at(Coord::point(1.0, 1.0, 0.0)), Coord::vec(2.0, -1.0, 0.0).normalized()));
+
+        p.set_max(2.0);
+        assert!(times(&p, Ray::new(Coord::point(-5.0, 3.0, 0.0), Coord::vec(1.0, 0.0, 0.0))).is_empty());
+        p.set_closed(true);
+        assert!(all_close(&times(&p, down), &[3.0, 5.0]));
+        assert!(close(p.normal_at(Coord::point(0.5, 2.0, 0.0)), Coord::vec(0.0, 1.0, 0.0)));
+        // outside the rim the cap isn't there
+        assert!(times(&p, Ray::new(Coord::point(1.5, 5.0, 0.0), Coord::vec(0.0, -1.0, 0.0))).iter().all(|t| (t - 3.0).abs() > 0.01));
+    }
+
+    #[test]
+    fn test_hyperboloid_one_sheet() {
+        let mut h = Quadric::hyperboloid_one_sheet();
+        assert!(all_close(&times(&h, Ray::new(Coord::point(-5.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0))), &[4.0, 6.0]));
+        let r = 2.0_f32.sqrt();
+        assert!(all_close(&times(&h, Ray::new(Coord::point(-5.0, 1.0, 0.0), Coord::vec(1.0, 0.0, 0.0))), &[5.0 - r, 5.0 + r]));
+        // straight up the middle never touches the walls
+        let up = Ray::new(Coord::point(0.0, -5.0, 0.0), Coord::vec(0.0, 1.0, 0.0));
+        assert!(times(&h, up).is_empty());
+        h.set_min(-1.0);
+        h.set_max(2.0);
+        h.set_closed(true);
+        assert!(all_close(&times(&h, up), &[4.0, 7.0]));
+        assert!(close(h.normal_at(Coord::point(0.0, -1.0, 0.0)), Coord::vec(0.0, -1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_hyperboloid_two_sheets() {
+        let h = Quadric::hyperboloid_two_sheets();
+        let down = Ray::new(Coord::point(0.0, 5.0, 0.0), Coord::vec(0.0, -1.0, 0.0));
+        assert!(all_close(&times(&h, down), &[4.0, 6.0]));
+        assert!(times(&h, Ray::new(Coord::point(-5.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0))).is_empty());
+        assert!(close(h.normal_at(Coord::point(0.0, 1.0, 0.0)), Coord::vec(0.0, -1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_double_cone() {
+        let c = Quadric::double_cone();
+        assert!(all_close(&times(&c, Ray::new(Coord::point(-5.0, 1.0, 0.0), Coord::vec(1.0, 0.0, 0.0))), &[4.0, 6.0]));
+        // parallel to the side, crosses only the other half
+        assert!(all_close(&times(&c, Ray::new(Coord::point(0.0, 0.0, -1.0), Coord::vec(0.0, 1.0, 1.0))), &[0.5]));
+    }
+
+    #[test]
+    fn test_from_matrix() {
+        let sphere = Quadric::from_matrix(Matrix::identity(4), Material::default(), &Quadric::default().to_matrix()).unwrap();
+        assert_eq!(sphere.get_coefficients(), Quadric::default().get_coefficients());
+        // only the symmetric part matters
+        let lopsided = Matrix::new(vec![
+            vec![1.0, 0.0, 0.0, 0.0],
+            vec![0.0, 0.0, 0.0, -1.0],
+            vec![0.0, 0.0, 1.0, 0.0],
+            vec![0.0, 0.0, 0.0, 0.0],
+        ]);
+        let p = Quadric::from_matrix(Matrix::identity(4), Material::default(), &lopsided).unwrap();
+        assert_eq!(p.get_coefficients(), Quadric::paraboloid().get_coefficients());
+        assert_eq!(Quadric::from_matrix(Matrix::identity(4), Material::default(), &Matrix::identity(3)).err(), Some(RtcError::InvalidMatrixSize(3)));
+    }
+
+    #[test]
+    fn test_transformed() {
+        let mut tower = Quadric::hyperboloid_one_sheet();
+        tower.set_transformation(Matrix::translation(0.0, 0.0, 10.0) * Matrix::scaling(2.0, 3.0, 2.0)).unwrap();
+        let ts = times(&tower, Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0)));
+        assert!(all_close(&ts, &[8.0, 12.0]), "{ts:?}");
+        assert!(close(tower.normal_at(Coord::point(0.0, 0.0, 8.0)), Coord::vec(0.0, 0.0, -1.0)));
+    }
+}
@@ -0,0 +1,411 @@
+use std::{f32::consts::PI, fmt, sync::Arc};
+
+use crate::{coord::Coord, error::{Result, in_range}, impl_getters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
+
+/// a distance function in object space, negative inside the shape
+pub type DistanceFn = Arc<dyn Fn(Coord) -> f32 + Send + Sync>;
+
+/// a tree of shapes and operations making up a signed distance field
+///
+/// vectors are `[x, y, z]`. the shapes are exact distances, but the operations (apart from
+/// `Union` and `Translate`) can overestimate the distance a little, which makes sphere tracing
+/// step through the surface. lower the `Sdf`'s step scale if thin features go missing
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum SdfNode {
+    Sphere { radius: f32 },
+    /// centered at the origin, reaching `half_extents` along each axis
+    Cuboid { half_extents: [f32; 3] },
+    /// a ring around the y axis like `Torus`
+    Torus { major: f32, minor: f32 },
+    /// a line from `a` to `b` with rounded ends
+    Capsule { a: [f32; 3], b: [f32; 3], radius: f32 },
+    Union { a: Box<SdfNode>, b: Box<SdfNode> },
+    /// union blending the two shapes together over a distance of about `k`
+    SmoothUnion { a: Box<SdfNode>, b: Box<SdfNode>, k: f32 },
+    /// `a` with `b` cut out of it
+    Subtraction { a: Box<SdfNode>, b: Box<SdfNode> },
+    /// turns the node around the y axis by `rate` radians per unit of height
+    Twist { node: Box<SdfNode>, rate: f32 },
+    /// infinite copies of the node every `period` units, an axis with a period of 0 isn't repeated
+    Repeat { node: Box<SdfNode>, period: [f32; 3] },
+    Translate { node: Box<SdfNode>, offset: [f32; 3] },
+    /// any other distance function, can't be saved to a scene file
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(DistanceFn),
+}
+
+#[allow(dead_code)]
+impl SdfNode {
+    pub fn union(a: SdfNode, b: SdfNode) -> Self {
+        Self::Union { a: Box::new(a), b: Box::new(b) }
+    }
+
+    pub fn smooth_union(a: SdfNode, b: SdfNode, k: f32) -> Self {
+        Self::SmoothUnion { a: Box::new(a), b: Box::new(b), k }
+    }
+
+    pub fn subtraction(a: SdfNode, b: SdfNode) -> Self {
+        Self::Subtraction { a: Box::new(a), b: Box::new(b) }
+    }
+
+    pub fn twist(self, rate: f32) -> Self {
+        Self::Twist { node: Box::new(self), rate }
+    }
+
+    pub fn repeat(self, period: [f32; 3]) -> Self {
+        Self::Repeat { node: Box::new(self), period }
+    }
+
+    pub fn translate(self, offset: [f32; 3]) -> Self {
+        Self::Translate { node: Box::new(self), offset }
+    }
+
+    pub fn custom(f: impl Fn(Coord) -> f32 + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(f))
+    }
+
+    /// signed distance from object space `pos` to the surface
+    pub fn distance(&self, pos: Coord) -> f32 {
+        let (x, y, z) = (pos.get_x(), pos.get_y(), pos.get_z());
+        match self {
+            Self::Sphere { radius } => (x * x + y * y + z * z).sqrt() - radius,
+            Self::Cuboid { half_extents: [hx, hy, hz] } => {
+                let (qx, qy, qz) = (x.abs() - hx, y.abs() - hy, z.abs() - hz);
+                let outside = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2) + qz.max(0.0).powi(2)).sqrt();
+                outside + qx.max(qy).max(qz).min(0.0)
+            }
+            Self::Torus { major, minor } => (x.hypot(z) - major).hypot(y) - minor,
+            Self::Capsule { a, b, radius } => {
+                let pa = [x - a[0], y - a[1], z - a[2]];
+                let ba = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+                let len2 = ba[0] * ba[0] + ba[1] * ba[1] + ba[2] * ba[2];
+                let h = if len2 > 0.0 { ((pa[0] * ba[0] + pa[1] * ba[1] + pa[2] * ba[2]) / len2).clamp(0.0, 1.0) } else { 0.0 };
+                let d = [pa[0] - ba[0] * h, pa[1] - ba[1] * h, pa[2] - ba[2] * h];
+                (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt() - radius
+            }
+            Self::Union { a, b } => a.distance(pos).min(b.distance(pos)),
+            Self::SmoothUnion { a, b, k } => {
+                let (da, db) = (a.distance(pos), b.distance(pos));
+                if *k <= 0.0 {
+                    return da.min(db);
+                }
+                let h = (0.5 + 0.5 * (db - da) / k).clamp(0.0, 1.0);
+                db + (da - db) * h - k * h * (1.0 - h)
+            }
+            Self::Subtraction { a, b } => a.distance(pos).max(-b.distance(pos)),
+            Self::Twist { node, rate } => {
+                let (sin, cos) = (rate * y).sin_cos();
+                node.distance(Coord::point(cos * x - sin * z, y, sin * x + cos * z))
+            }
+            Self::Repeat { node, period } => {
+                let wrap = |v: f32, c: f32| if c > 0.0 { v - c * (v / c).round() } else { v };
+                node.distance(Coord::point(wrap(x, period[0]), wrap(y, period[1]), wrap(z, period[2])))
+            }
+            Self::Translate { node, offset } => node.distance(Coord::point(x - offset[0], y - offset[1], z - offset[2])),
+            Self::Custom(f) => f(pos),
+        }
+    }
+}
+
+impl PartialEq for SdfNode {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Sphere { radius: a }, Self::Sphere { radius: b }) => a == b,
+            (Self::Cuboid { half_extents: a }, Self::Cuboid { half_extents: b }) => a == b,
+            (Self::Torus { major: ma, minor: ia }, Self::Torus { major: mb, minor: ib }) => ma == mb && ia == ib,
+            (Self::Capsule { a: a1, b: b1, radius: r1 }, Self::Capsule { a: a2, b: b2, radius: r2 }) => a1 == a2 && b1 == b2 && r1 == r2,
+            (Self::Union { a: a1, b: b1 }, Self::Union { a: a2, b: b2 }) => a1 == a2 && b1 == b2,
+            (Self::SmoothUnion { a: a1, b: b1, k: k1 }, Self::SmoothUnion { a: a2, b: b2, k: k2 }) => a1 == a2 && b1 == b2 && k1 == k2,
+            (Self::Subtraction { a: a1, b: b1 }, Self::Subtraction { a: a2, b: b2 }) => a1 == a2 && b1 == b2,
+            (Self::Twist { node: n1, rate: r1 }, Self::Twist { node: n2, rate: r2 }) => n1 == n2 && r1 == r2,
+            (Self::Repeat { node: n1, period: p1 }, Self::Repeat { node: n2, period: p2 }) => n1 == n2 && p1 == p2,
+            (Self::Translate { node: n1, offset: o1 }, Self::Translate { node: n2, offset: o2 }) => n1 == n2 && o1 == o2,
+            (Self::Custom(a), Self::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for SdfNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sphere { radius } => f.debug_struct("Sphere").field("radius", radius).finish(),
+            Self::Cuboid { half_extents } => f.debug_struct("Cuboid").field("half_extents", half_extents).finish(),
+            Self::Torus { major, minor } => f.debug_struct("Torus").field("major", major).field("minor", minor).finish(),
+            Self::Capsule { a, b, radius } => f.debug_struct("Capsule").field("a", a).field("b", b).field("radius", radius).finish(),
+            Self::Union { a, b } => f.debug_struct("Union").field("a", a).field("b", b).finish(),
+            Self::SmoothUnion { a, b, k } => f.debug_struct("SmoothUnion").field("a", a).field("b", b).field("k", k).finish(),
+            Self::Subtraction { a, b } => f.debug_struct("Subtraction").field("a", a).field("b", b).finish(),
+            Self::Twist { node, rate } => f.debug_struct("Twist").field("node", node).field("rate", rate).finish(),
+            Self::Repeat { node, period } => f.debug_struct("Repeat").field("node", node).field("period", period).finish(),
+            Self::Translate { node, offset } => f.debug_struct("Translate").field("node", node).field("offset", offset).finish(),
+            Self::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// a shape given by a signed distance field, found by sphere tracing: stepping along the ray by
+/// the distance to the nearest surface until that distance drops below `epsilon`
+///
+/// marching stops after `max_steps` steps or `max_distance` units in object space, so a
+/// repeating field isn't infinite. hits start from the ray's origin, a ray starting inside the
+/// shape only sees where it leaves
+#[derive(Clone, PartialEq)]
+pub struct Sdf {
+    transformation: Matrix,
+    inverse: Matrix,
+    material: Arc<Material>,
+    root: SdfNode,
+    max_steps: usize,
+    epsilon: f32,
+    max_distance: f32,
+    step_scale: f32,
+}
+
+impl_getters!(Sdf, root: SdfNode, max_steps: usize, epsilon: f32, max_distance: f32, step_scale: f32);
+
+impl Sdf {
+    /// returns an error if the transformation can't be inverted
+    pub fn new(transformation: Matrix, material: Material, root: SdfNode) -> Result<Self> {
+        Ok(Self { inverse: transformation.transformation_inverse()?, transformation, material: Arc::new(material), root, ..<Self as Renderable>::default() })
+    }
+
+    pub fn set_root(&mut self, root: SdfNode) {
+        self.root = root;
+    }
+
+    /// returns an error unless `epsilon` and `max_distance` are positive and `step_scale` is in
+    /// (0, 1], scales below 1 take smaller steps for fields that overestimate the distance
+    pub fn set_limits(&mut self, max_steps: usize, epsilon: f32, max_distance: f32, step_scale: f32) -> Result<()> {
+        in_range("epsilon", epsilon, f32::MIN_POSITIVE, f32::MAX)?;
+        in_range("max distance", max_distance, f32::MIN_POSITIVE, f32::INFINITY)?;
+        in_range("step scale", step_scale, f32::MIN_POSITIVE, 1.0)?;
+        (self.max_steps, self.epsilon, self.max_distance, self.step_scale) = (max_steps, epsilon, max_distance, step_scale);
+        Ok(())
+    }
+
+    fn normal_at_local_space(&self, pos: Coord) -> Coord {
+        // central differences of the field
+        let h = self.epsilon;
+        let diff = |d: Coord| self.root.distance(pos + d) - self.root.distance(pos - d);
+        let local_normal = Coord::vec(
+            diff(Coord::vec(h, 0.0, 0.0)),
+            diff(Coord::vec(0.0, h, 0.0)),
+            diff(Coord::vec(0.0, 0.0, h)),
+        );
+        let mut out = self.get_inverse().transpose() * local_normal;
+        out.set_w(0.0);
+        out.normalized()
+    }
+
+    /// object space distances along the unit direction `dir` where the ray crosses the surface
+    fn march(&self, origin: Coord, dir: Coord) -> Vec<f32> {
+        let mut hits = Vec::new();
+        let mut s = 0.0;
+        let mut d = self.root.distance(origin);
+        let mut inside = d < 0.0;
+        let mut steps = 0;
+        while steps < self.max_steps && s <= self.max_distance {
+            if d.abs() < self.epsilon {
+                // creep past the surface, only a change of side counts as a hit so grazing rays
+                // don't leave a hit without its pair
+                let hit = s;
+                while d.abs() < self.epsilon && steps < self.max_steps {
+                    s += self.epsilon;
+                    d = self.root.distance(origin + dir * s);
+                    steps += 1;
+                }
+                if (d < 0.0) != inside {
+                    inside = d < 0.0;
+                    hits.push(hit);
+                }
+                continue;
+            }
+            s += d.abs() * self.step_scale;
+            d = self.root.distance(origin + dir * s);
+            steps += 1;
+        }
+        hits
+    }
+}
+
+impl_renderable_base!(Sdf, RenderableType::Sdf);
+
+impl_renderable_tests!(crate::primitives::sdf::Sdf, RenderableType::Sdf);
+
+impl Renderable for Sdf {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
+        let len = ray.get_direction().magnitude();
+        if len < f32::EPSILON {
+            return (ray, None);
+        }
+        let hits = self.march(ray.get_origin(), ray.get_direction() / len);
+        if hits.is_empty() {
+            return (ray, None);
+        }
+
+        let data = hits.into_iter()
+            .map(|s| {
+                let t = s / len;
+                Intersection::new(t, this.clone())
+            })
+            .collect();
+        (ray, Some(data))
+    }
+
+    fn normal_at(&self, pos: Coord) -> Coord {
+        let pos = self.get_inverse() * pos;
+        self.normal_at_local_space(pos)
+    }
+
+    /// spherical mapping around the object's origin
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        let theta = p.get_x().atan2(p.get_z());
+        let radius = p.to_vec().magnitude();
+        let phi = if radius > 0.0 { (p.get_y() / radius).clamp(-1.0, 1.0).acos() } else { 0.0 };
+        (1.0 - (theta / (2.0 * PI) + 0.5), 1.0 - phi / PI)
+    }
+
+    /// a unit sphere, marched for up to 256 steps to within 0.0001 over 100 units
+    fn default() -> Self {
+        Self {
+            transformation: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
+            root: SdfNode::Sphere { radius: 1.0 },
+            max_steps: 256,
+            epsilon: 0.0001,
+            max_distance: 100.0,
+            step_scale: 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use crate::{primitives::{sphere::Sphere, torus::Torus}, world::World};
+
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.001
+    }
+
+    fn times(obj: &dyn Renderable, ray: Ray) -> Vec<f32> {
+        obj.intersect(ray).map(|xs| xs.iter().map(|x| x.get_time()).collect()).unwrap_or_default()
+    }
+
+    #[test_case(SdfNode::Sphere { radius: 1.0 }, Coord::point(0.0, 0.0, 2.0), 1.0 ; "sphere")]
+    #[test_case(SdfNode::Cuboid { half_extents: [1.0, 2.0, 3.0] }, Coord::point(0.0, 0.0, 0.0), -1.0 ; "cuboid inside")]
+    #[test_case(SdfNode::Cuboid { half_extents: [1.0, 1.0, 1.0] }, Coord::point(2.0, 2.0, 1.0), 2.0_f32.sqrt() ; "cuboid corner")]
+    #[test_case(SdfNode::Torus { major: 1.0, minor: 0.25 }, Coord::point(0.0, 0.0, 0.0), 0.75 ; "torus hole")]
+    #[test_case(SdfNode::Capsule { a: [0.0, -1.0, 0.0], b: [0.0, 1.0, 0.0], radius: 0.5 }, Coord::point(0.0, 3.0, 0.0), 1.5 ; "capsule end")]
+    #[test_case(SdfNode::Capsule { a: [0.0, -1.0, 0.0], b: [0.0, 1.0, 0.0], radius: 0.5 }, Coord::point(2.0, 0.5, 0.0), 1.5 ; "capsule side")]
+    fn test_shape_distance(node: SdfNode, pos: Coord, expected: f32) {
+        assert!(close(node.distance(pos), expected), "{}", node.distance(pos));
+    }
+
+    #[test]
+    fn test_operations() {
+        let a = SdfNode::Sphere { radius: 1.0 };
+        let b = SdfNode::Sphere { radius: 1.0 }.translate([1.5, 0.0, 0.0]);
+        let union = SdfNode::union(a.clone(), b.clone());
+        assert!(close(union.distance(Coord::point(3.0, 0.0, 0.0)), 0.5));
+        assert!(close(union.distance(Coord::point(-3.0, 0.0, 0.0)), 2.0));
+        // blending fills in the gap where the two meet
+        let smooth = SdfNode::smooth_union(a.clone(), b.clone(), 0.5);
+        let between = Coord::point(0.75, 0.8, 0.0);
+        assert!(smooth.distance(between) < union.distance(between));
+        let cut = SdfNode::subtraction(a.clone(), b);
+        assert!(cut.distance(Coord::point(0.9, 0.0, 0.0)) > 0.0);
+        assert!(cut.distance(Coord::point(-0.5, 0.0, 0.0)) < 0.0);
+        let rows = a.clone().repeat([4.0, 0.0, 0.0]);
+        assert!(close(rows.distance(Coord::point(8.0, 0.0, 2.0)), 1.0));
+        assert!(close(rows.distance(Coord::point(0.0, 4.0, 0.0)), 3.0));
+        // a quarter turn per unit up, the box's long axis goes from x to z at y = 1
+        let twisted = SdfNode::Cuboid { half_extents: [2.0, 5.0, 0.5] }.twist(PI / 2.0);
+        assert!(twisted.distance(Coord::point(1.5, 0.0, 0.0)) < 0.0);
+        assert!(twisted.distance(Coord::point(0.0, 1.0, 1.5)) < 0.0);
+        assert!(twisted.distance(Coord::point(1.5, 1.0, 0.0)) > 0.0);
+    }
+
+    #[test]
+    fn test_matches_analytic_sphere() {
+        let sdf = Sdf::default();
+        let sphere = Sphere::default();
+        for ray in [
+            Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0)),
+            Ray::new(Coord::point(0.3, 0.5, -5.0), Coord::vec(0.0, 0.0, 2.0)),
+            Ray::new(Coord::point(-4.0, 2.0, 1.0), Coord::vec(1.0, -0.4, -0.2)),
+        ] {
+            let (a, b) = (times(&sdf, ray), times(&sphere, ray));
+            assert_eq!(a.len(), 2);
+            assert!(a.iter().zip(&b).all(|(a, b)| close(*a, *b)), "{a:?} {b:?}");
+        }
+        assert!(sdf.intersect(Ray::new(Coord::point(0.0, 2.0, -5.0), Coord::vec(0.0, 0.0, 1.0))).is_none());
+    }
+
+    #[test]
+    fn test_matches_analytic_torus() {
+        let transform = Matrix::translation(1.0, 0.0, 3.0) * Matrix::rotation_x(0.4);
+        let sdf = Sdf::new(transform.clone(), Material::default(), SdfNode::Torus { major: 1.0, minor: 0.25 }).unwrap();
+        let torus = Torus::new(transform, Material::default(), 1.0, 0.25).unwrap();
+        let ray = Ray::new(Coord::point(-4.0, 0.1, 3.0), Coord::vec(1.0, 0.0, 0.0));
+        let (a, b) = (times(&sdf, ray), times(&torus, ray));
+        assert_eq!(a.len(), b.len());
+        assert!(a.iter().zip(&b).all(|(a, b)| close(*a, *b)), "{a:?} {b:?}");
+        let p = ray.position(a[0]);
+        assert!((sdf.normal_at(p) - torus.normal_at(p)).magnitude() < 0.01);
+    }
+
+    #[test]
+    fn test_inside_and_limits() {
+        let mut sdf = Sdf::default();
+        assert_eq!(times(&sdf, Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0))).len(), 1);
+
+        // endless rows of spheres, only those within max distance are found
+        sdf.set_root(SdfNode::Sphere { radius: 0.5 }.repeat([2.0, 0.0, 0.0]));
+        sdf.set_limits(256, 0.0001, 8.0, 1.0).unwrap();
+        let ts = times(&sdf, Ray::new(Coord::point(-1.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0)));
+        assert_eq!(ts.len(), 8, "{ts:?}");
+        assert!(close(ts[0], 0.5) && close(ts[7], 7.5));
+
+        assert!(sdf.set_limits(10, 0.0, 1.0, 1.0).is_err());
+        assert!(sdf.set_limits(10, 0.001, 1.0, 1.5).is_err());
+        sdf.set_limits(2, 0.0001, 100.0, 1.0).unwrap();
+        sdf.set_root(SdfNode::Sphere { radius: 1.0 });
+        // not enough steps to get there
+        assert!(sdf.intersect(Ray::new(Coord::point(0.3, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0))).is_none());
+    }
+
+    #[test]
+    fn test_custom() {
+        // a slab of thickness 1 around y = 0
+        let slab = SdfNode::custom(|p| p.get_y().abs() - 0.5);
+        assert_eq!(slab, slab.clone());
+        assert_ne!(slab, SdfNode::custom(|p| p.get_y().abs() - 0.5));
+        let sdf = Sdf::new(Matrix::identity(4), Material::default(), slab).unwrap();
+        let ts = times(&sdf, Ray::new(Coord::point(0.0, 3.0, 0.0), Coord::vec(0.0, -1.0, 0.0)));
+        assert!(ts.len() == 2 && close(ts[0], 2.5) && close(ts[1], 3.5));
+        let n = sdf.normal_at(Coord::point(0.0, 0.5, 0.0));
+        assert!((n - Coord::vec(0.0, 1.0, 0.0)).magnitude() < 0.001);
+    }
+
+    #[test]
+    fn test_in_world() {
+        let mut w = World::new();
+        let sdf = Sdf::new(Matrix::translation(0.0, 0.0, 2.0), Material::default(), SdfNode::Cuboid { half_extents: [1.0, 1.0, 1.0] }).unwrap();
+        let id = w.add_obj(Arc::new(sdf));
+        w.add_obj(Arc::new(Sphere::new(Coord::point(0.0, 0.0, 6.0)).unwrap()));
+        let hit = w.cast_ray(Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0))).unwrap();
+        assert_eq!(hit.get_id(), Some(id));
+        assert!(close(hit.get_distance(), 6.0));
+        assert!((hit.get_normal() - Coord::vec(0.0, 0.0, -1.0)).magnitude() < 0.001);
+    }
+}
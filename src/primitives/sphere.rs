@@ -1,10 +1,12 @@
 use core::f32;
 use std::sync::Arc;
+use std::f32::consts::PI;
 use crate::{impl_renderable_base, impl_renderable_tests};
+use crate::error::{Result, RtcError};
 use crate::material::Material;
 use crate::matrix::Matrix;
 use crate::ray::Ray;
-use crate::renderable::{Intersection, Renderable, RenderableType};
+use crate::renderable::{Intersection, Renderable, RenderableBase, RenderableType, SurfaceSample};
 use crate::tex::color::Color;
 use crate::coord::Coord;
 
@@ -14,7 +16,8 @@ pub struct Sphere {
     //origin: Coord,
     //radius: f32,
     transformation: Matrix,
-    material: Material,
+    inverse: Matrix,
+    material: Arc<Material>,
 }
 
 #[allow(dead_code)]
@@ -29,37 +32,33 @@ impl Sphere {
      *      .set_transformation(matrix)
      *      .set_material(material);
      */
-    pub fn new(origin: Coord) -> Self {
-        assert!(origin.is_point());
-        Self { 
-            transformation: Matrix::from_point(&origin),
-            material: Material::default(),
+    pub fn new(origin: Coord) -> Result<Self> {
+        if !origin.is_point() {
+            return Err(RtcError::ExpectedPoint);
         }
+        let transformation = Matrix::from_point(&origin);
+        Ok(Self { 
+            inverse: transformation.transformation_inverse()?,
+            transformation,
+            material: Arc::new(Material::default()),
+        })
     }
 
+    /// the book's test sphere, a default material made fully transparent with an index of 1.5,
+    /// use `Preset::Glass` for glass that looks right
     pub fn glass_sphere() -> Self {
         let mut mat = Material::default();
-        mat.set_transparency(1.0);
-        mat.set_refractive_index(1.5);
-        Self {
-            transformation: Matrix::identity(4),
-            material: mat
-        }
-    }
-    
-    // TODO: remove clone here
-    pub fn apply_transformation(&mut self, mat: Matrix) {
-        self.transformation = self.transformation.clone() * mat;
+        mat.set_transparency(1.0).expect("1.0 is a valid transparency");
+        mat.set_refractive_index(1.5).expect("1.5 is a valid refractive index");
+        let mut out = Self::default();
+        out.material = Arc::new(mat);
+        out
     }
 
     pub fn get_origin(&self) -> Coord {
         self.transformation.to_point()
     }
 
-    pub fn get_transformation(&self) -> Matrix {
-        self.transformation.clone()
-    }
-
     // breaks when you transform the sphere
     fn geometric_intersect(&self, ray: &Ray) -> Option<[f32; 2]> {
         // ref: https://discussions.unity.com/t/how-do-i-find-the-closest-point-on-a-line/588895/3
@@ -163,44 +162,55 @@ impl_renderable_tests!(crate::primitives::sphere::Sphere, RenderableType::Sphere
 
 impl Renderable for Sphere { 
     
-    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
-        let (_, out) = self.intersect_get_ray(ray);
-        out
-    }
-
-    fn intersect_get_ray(&self, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
-        let ray = ray.transform(self.get_transformation().inverse().unwrap());
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
         let data = self.analytical_intersect(&ray);
         if data.is_none() {
             return (ray, None);
         }
         let data = data.unwrap();
-        let t = Arc::new(self.clone());
-        let r1 = ray.get_direction().reflect(self.normal_at((ray.position(data[0])).to_pos()));
-        let r2 = ray.get_direction().reflect(self.normal_at((ray.position(data[1])).to_pos()));
         (
             ray,
-            Some(vec![Intersection::new(data[0], t.clone(), r1), Intersection::new(data[1], t, r2)])
+            Some(vec![Intersection::new(data[0], this.clone()), Intersection::new(data[1], this.clone())])
         )
     }
 
     /// func assumes pos is on the sphere, if it is not results are undefined
     fn normal_at(&self, pos: Coord) -> Coord {
-        let object_pos = self.get_transformation().inverse().unwrap() * pos; // TODO: isn't this already done in intersect method
+        let object_pos = self.get_inverse() * pos; // TODO: isn't this already done in intersect method
         let obj_normal = self.local_normal_at(object_pos);
-        let mut world_norm = self.transformation.inverse()
-            .unwrap()
+        let mut world_norm = self.get_inverse()
             .transpose()
             * obj_normal;
         world_norm.set_w(0.0);
         world_norm.normalized()
     }
 
+    /// spherical mapping, u goes around the y axis and v from the bottom pole to the top
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        let theta = p.get_x().atan2(p.get_z());
+        let radius = p.to_vec().magnitude();
+        let phi = (p.get_y() / radius).clamp(-1.0, 1.0).acos();
+        (1.0 - (theta / (2.0 * PI) + 0.5), 1.0 - phi / PI)
+    }
+
+    /// uniform over the surface
+    fn sample_surface(&self, u: [f32; 3]) -> Option<SurfaceSample> {
+        let z = 1.0 - 2.0 * u[0];
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u[1];
+        let normal = Coord::vec(r * phi.cos(), r * phi.sin(), z);
+        let point = Coord::point(normal.get_x(), normal.get_y(), normal.get_z());
+        Some(SurfaceSample::from_local(self, point, normal, 1.0 / (4.0 * PI)))
+    }
+
     /// a sphere at position (0, 0, 0) with a radius of 1
     fn default() -> Self {
         Self {
             transformation: Matrix::identity(4),
-            material: Material::default(),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
         }
     }
 }
@@ -233,7 +243,7 @@ mod tests {
         assert_eq!(s.transformation, Matrix::identity(4));
         //assert_eq!(s.material, Material::default());
 
-        let s = Sphere::new(Coord::point(0.0, 0.0, 0.0));
+        let s = Sphere::new(Coord::point(0.0, 0.0, 0.0)).unwrap();
         //assert_eq!(s.radius, 2.0);
         assert_eq!(s.transformation, Matrix::identity(4));
         //assert_eq!(s.material, Material::default());
@@ -243,7 +253,7 @@ mod tests {
     fn test_get_origin() {
         let mut s = Sphere::default();
         assert_eq!(s.get_origin(), Coord::point(0.0, 0.0, 0.0));
-        s.apply_transformation(Matrix::scaling(0.5, 0.5, 0.5));
+        s.apply_transformation(Matrix::scaling(0.5, 0.5, 0.5)).unwrap();
         assert_eq!(s.get_origin(), Coord::point(0.0, 0.0, 0.0));
     }
 
@@ -325,7 +335,7 @@ mod tests {
 
         let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
         let mut s = Sphere::default();
-        s.apply_transformation(Matrix::scaling(0.5, 0.5, 0.5));
+        s.apply_transformation(Matrix::scaling(0.5, 0.5, 0.5)).unwrap();
         let xs = r.intersect(&s);
         assert!(xs.is_some());
         let xs = xs.unwrap();
@@ -360,12 +370,12 @@ mod tests {
         assert_eq!(n, n.normalized());
 
         let mut s = Sphere::default();
-        s.set_transformation(Matrix::translation(0.0, 1.0, 0.0));
+        s.set_transformation(Matrix::translation(0.0, 1.0, 0.0)).unwrap();
         let n = s.normal_at(Coord::point(0.0, 1.70711, -0.70711));
         assert_eq!(n, Coord::vec(0.0, 0.7071068, -0.70710677));
 
         let mut s = Sphere::default();
-        s.set_transformation(Matrix::scaling(1.0, 0.5, 1.0) * Matrix::rotate_z(f32::consts::PI/5.0));
+        s.set_transformation(Matrix::scaling(1.0, 0.5, 1.0) * Matrix::rotation_z(f32::consts::PI/5.0)).unwrap();
         let n = s.normal_at(Coord::point(0.0, (2.0_f32.sqrt())/2.0, -(2.0_f32.sqrt())/2.0));
         let n = test_near_0(&n);
         assert_eq!(n, Coord::vec(0.0, 0.97014254, -0.24253564));
@@ -377,7 +387,7 @@ mod tests {
         // point into object space before computing the surface normal; without
         // that conversion a translated sphere yields the wrong normal
         let mut s = Sphere::default();
-        s.set_transformation(Matrix::translation(0.0, 1.0, 0.0));
+        s.set_transformation(Matrix::translation(0.0, 1.0, 0.0)).unwrap();
         let n = s.normal_at(Coord::point(0.0, 1.70711, -0.70711));
         assert_eq!(n, Coord::vec(0.0, 0.7071068, -0.70710677));
     }
@@ -389,4 +399,24 @@ mod tests {
         assert_eq!(mat.get_refractive_index(), 1.5);
         assert_eq!(mat.get_transparency(), 1.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sample_surface() {
+        let mut s = Sphere::default();
+        s.set_transformation(Matrix::translation(1.0, 0.0, 0.0) * Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
+        let n = 20;
+        let mut inverse_pdf = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = [(i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32, 0.5];
+                let sample = s.sample_surface(u).unwrap();
+                assert!(((sample.get_point() - Coord::point(1.0, 0.0, 0.0)).magnitude() - 2.0).abs() < 0.0001);
+                assert!((sample.get_normal() - s.normal_at(sample.get_point())).magnitude() < 0.0001);
+                inverse_pdf += 1.0 / sample.get_pdf();
+            }
+        }
+        // the average of 1 / pdf is the area
+        let area = inverse_pdf / (n * n) as f32;
+        assert!((area - 16.0 * PI).abs() < 0.01, "{area}");
+    }
+}
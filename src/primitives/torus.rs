@@ -0,0 +1,367 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use crate::{coord::Coord, error::{Result, in_range, non_negative}, impl_getters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType, SurfaceSample}, tex::color::Color};
+
+/// a ring around the y axis, `major` is the distance from the center to the middle of the tube
+/// and `minor` the radius of the tube
+///
+/// a `minor` bigger than `major` gives a spindle torus that overlaps itself through the middle
+#[derive(PartialEq, Clone)]
+pub struct Torus {
+    transformation: Matrix,
+    inverse: Matrix,
+    material: Arc<Material>,
+    major: f32,
+    minor: f32,
+}
+
+impl_getters!(Torus, major: f32, minor: f32);
+
+impl Torus {
+    /// returns an error if the transformation can't be inverted, `major` is negative or `minor`
+    /// isn't positive
+    pub fn new(transformation: Matrix, material: Material, major: f32, minor: f32) -> Result<Self> {
+        non_negative("major radius", major)?;
+        in_range("minor radius", minor, f32::MIN_POSITIVE, f32::MAX)?;
+        Ok(Self { inverse: transformation.transformation_inverse()?, transformation, material: Arc::new(material), major, minor })
+    }
+
+    fn normal_at_local_space(&self, pos: Coord) -> Coord {
+        // away from the closest point on the circle through the middle of the tube
+        let ring = pos.get_x().hypot(pos.get_z());
+        let scale = if ring > f32::EPSILON { 1.0 - self.major / ring } else { 1.0 };
+        let local_normal = Coord::vec(pos.get_x() * scale, pos.get_y(), pos.get_z() * scale);
+
+        let mut out = self.get_inverse().transpose() * local_normal;
+        out.set_w(0.0);
+        out.normalized()
+    }
+
+    /// ray times of the hits with the torus in object space, sorted
+    fn local_intersect(&self, ray: &Ray) -> Vec<f32> {
+        let (major, minor) = (self.major as f64, self.minor as f64);
+        let o = ray.get_origin();
+        let d = ray.get_direction();
+        let (ox, oy, oz) = (o.get_x() as f64, o.get_y() as f64, o.get_z() as f64);
+        let (dx, dy, dz) = (d.get_x() as f64, d.get_y() as f64, d.get_z() as f64);
+
+        // a unit direction starting at the point closest to the center keeps the coefficients
+        // small, far away rays would otherwise lose all precision to cancellation
+        let len = (dx * dx + dy * dy + dz * dz).sqrt();
+        let (dx, dy, dz) = (dx / len, dy / len, dz / len);
+        let closest = -(ox * dx + oy * dy + oz * dz);
+        let (ox, oy, oz) = (ox + dx * closest, oy + dy * closest, oz + dz * closest);
+
+        // miss the bounding sphere and the torus is missed too
+        let bound = major + minor;
+        if ox * ox + oy * oy + oz * oz > bound * bound {
+            return Vec::new();
+        }
+
+        // (|p|^2 - R^2 - r^2)^2 = 4 R^2 (r^2 - y^2) along p = o + t d, with |d| = 1
+        let f = ox * dx + oy * dy + oz * dz;
+        let e = ox * ox + oy * oy + oz * oz - major * major - minor * minor;
+        let four_r2 = 4.0 * major * major;
+        let coeffs = [
+            e * e - four_r2 * (minor * minor - oy * oy),
+            4.0 * f * e + 2.0 * four_r2 * oy * dy,
+            2.0 * e + 4.0 * f * f + four_r2 * dy * dy,
+            4.0 * f,
+            1.0,
+        ];
+        solve_quartic(coeffs).into_iter().map(|t| ((t + closest) / len) as f32).collect()
+    }
+}
+
+impl_renderable_base!(Torus, RenderableType::Torus);
+
+impl_renderable_tests!(crate::primitives::torus::Torus, RenderableType::Torus);
+
+impl Renderable for Torus {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
+        let ts = self.local_intersect(&ray);
+        if ts.is_empty() {
+            return (ray, None);
+        }
+
+        let data = ts.into_iter()
+            .map(|t| Intersection::new(t, this.clone()))
+            .collect();
+        (ray, Some(data))
+    }
+
+    fn normal_at(&self, pos: Coord) -> Coord {
+        let pos = self.get_inverse() * pos;
+        self.normal_at_local_space(pos)
+    }
+
+    /// u goes around the y axis and v around the tube, starting from its outer edge
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let p = self.get_inverse() * pos;
+        let theta = p.get_x().atan2(p.get_z());
+        let phi = p.get_y().atan2(p.get_x().hypot(p.get_z()) - self.major);
+        (1.0 - (theta / (2.0 * PI) + 0.5), (phi / (2.0 * PI)).rem_euclid(1.0))
+    }
+
+    /// uniform in both angles, so the outer edge gets sampled a little less than the inner one
+    fn sample_surface(&self, u: [f32; 3]) -> Option<SurfaceSample> {
+        let (theta, phi) = (2.0 * PI * u[0], 2.0 * PI * u[1]);
+        let ring = self.major + self.minor * phi.cos();
+        let normal = Coord::vec(phi.cos() * theta.cos(), phi.sin(), phi.cos() * theta.sin());
+        let point = Coord::point(ring * theta.cos(), self.minor * phi.sin(), ring * theta.sin());
+        // an area element is r (R + r cos phi) dtheta dphi
+        let pdf = 1.0 / (4.0 * PI * PI * self.minor * ring.abs().max(f32::EPSILON));
+        Some(SurfaceSample::from_local(self, point, normal, pdf))
+    }
+
+    /// a ring of radius 1 with a tube of radius 0.25
+    fn default() -> Self where Self: Sized {
+        Self {
+            transformation: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
+            major: 1.0,
+            minor: 0.25,
+        }
+    }
+}
+
+/// real roots of `c[0] + c[1] x + c[2] x^2 + c[3] x^3 + c[4] x^4`, sorted
+///
+/// a double root (a ray grazing the surface) comes back twice, or not at all when rounding
+/// pushes it just off the real line
+///
+/// Ferrari's method splits the quartic into two quadratics through the largest root of its
+/// resolvent cubic, then each root is polished with newton steps on the original quartic
+pub(crate) fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    if c[4] == 0.0 {
+        return Vec::new();
+    }
+    let (a, b, cc, d) = (c[3] / c[4], c[2] / c[4], c[1] / c[4], c[0] / c[4]);
+
+    // x = y - a / 4 gives y^4 + p y^2 + q y + r
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = cc - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * cc / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut ys = Vec::with_capacity(4);
+    if q.abs() < 1e-12 {
+        // biquadratic, solve for y^2
+        for z in solve_quadratic(1.0, p, r) {
+            if z >= 0.0 {
+                ys.push(z.sqrt());
+                ys.push(-z.sqrt());
+            } else if z > -1e-9 {
+                ys.extend([0.0, 0.0]);
+            }
+        }
+    } else {
+        // (y^2 + p/2 + m)^2 is a perfect square of a line in y for any m solving this
+        let m = largest_cubic_root(8.0, 8.0 * p, 2.0 * p * p - 8.0 * r, -q * q);
+        let s = (2.0 * m).sqrt();
+        ys.extend(solve_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s)));
+        ys.extend(solve_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s)));
+    }
+
+    let eval = |x: f64| (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+    let derivative = |x: f64| ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+    let mut roots: Vec<f64> = ys.into_iter().map(|y| {
+        let mut x = y - a / 4.0;
+        for _ in 0..4 {
+            let slope = derivative(x);
+            if slope.abs() < 1e-14 {
+                break;
+            }
+            let step = eval(x) / slope;
+            x -= step;
+            if step.abs() < 1e-12 * x.abs().max(1.0) {
+                break;
+            }
+        }
+        x
+    }).collect();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// real roots of `a x^2 + b x + c`, avoiding cancellation between b and the square root
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return Vec::new();
+    }
+    let q = -0.5 * (b + b.signum() * disc.sqrt());
+    if q == 0.0 {
+        return vec![0.0, 0.0];
+    }
+    vec![q / a, c / q]
+}
+
+/// largest real root of `a x^3 + b x^2 + c x + d`
+fn largest_cubic_root(a: f64, b: f64, c: f64, d: f64) -> f64 {
+    let (b, c, d) = (b / a, c / a, d / a);
+    // x = t - b / 3 gives t^3 + p t + q
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let disc = q * q / 4.0 + p * p * p / 27.0;
+    let t = if disc > 0.0 {
+        // one real root
+        let sq = disc.sqrt();
+        (-q / 2.0 + sq).cbrt() + (-q / 2.0 - sq).cbrt()
+    } else {
+        // three real roots, the k = 0 one of the trigonometric solution is the largest
+        let rho = (-p / 3.0).sqrt();
+        let cos = if rho == 0.0 { 0.0 } else { (-q / (2.0 * rho * rho * rho)).clamp(-1.0, 1.0) };
+        2.0 * rho * (cos.acos() / 3.0).cos()
+    };
+    let mut x = t - b / 3.0;
+    // one newton step cleans up cbrt and acos rounding
+    let slope = (3.0 * x + 2.0 * b) * x + c;
+    if slope.abs() > 1e-14 {
+        x -= (((x + b) * x + c) * x + d) / slope;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+
+    use super::*;
+
+    fn close(a: Coord, b: Coord) -> bool {
+        (a - b).magnitude() < 0.0001
+    }
+
+    /// coefficients of the monic polynomial with these roots
+    fn from_roots(roots: [f64; 4]) -> [f64; 5] {
+        let mut c = [1.0, 0.0, 0.0, 0.0, 0.0];
+        for (deg, root) in roots.iter().enumerate() {
+            for i in (0..=deg + 1).rev() {
+                let lower = if i > 0 { c[i - 1] } else { 0.0 };
+                c[i] = lower - root * c[i];
+            }
+        }
+        c
+    }
+
+    #[test_case([1.0, 2.0, 3.0, 4.0] ; "distinct")]
+    #[test_case([-3.0, -0.5, 0.25, 10.0] ; "spread")]
+    #[test_case([0.001, 0.002, 1000.0, 1000.5] ; "far apart")]
+    fn test_solve_quartic_real(roots: [f64; 4]) {
+        let found = solve_quartic(from_roots(roots));
+        assert_eq!(found.len(), 4, "{found:?}");
+        for (f, e) in found.iter().zip(roots) {
+            assert!((f - e).abs() < 1e-6 * e.abs().max(1.0), "{found:?}");
+        }
+    }
+
+    #[test]
+    fn test_solve_quartic_double() {
+        let found = solve_quartic(from_roots([-2.0, -2.0, 1.0, 1.0]));
+        assert!(!found.is_empty() && found.len() % 2 == 0, "{found:?}");
+        assert!(found.iter().all(|r| (r + 2.0).abs() < 1e-4 || (r - 1.0).abs() < 1e-4), "{found:?}");
+    }
+
+    #[test]
+    fn test_solve_quartic_complex() {
+        // (x^2 + 1)(x^2 - 4)
+        assert_eq!(solve_quartic([-4.0, 0.0, -3.0, 0.0, 1.0]), vec![-2.0, 2.0]);
+        // (x^2 + 1)(x^2 + 2x + 5)
+        assert!(solve_quartic([5.0, 2.0, 6.0, 2.0, 1.0]).is_empty());
+        // (x^2 + 1)(x - 3)^2
+        let roots = solve_quartic([9.0, -6.0, 10.0, -6.0, 1.0]);
+        assert!(roots.iter().all(|r| (r - 3.0).abs() < 1e-4), "{roots:?}");
+    }
+
+    #[test_case(Coord::point(-5.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0), vec![3.75, 4.25, 5.75, 6.25] ; "through both sides")]
+    #[test_case(Coord::point(1.0, 5.0, 0.0), Coord::vec(0.0, -1.0, 0.0), vec![4.75, 5.25] ; "down the tube")]
+    #[test_case(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 2.0), vec![1.875, 2.125, 2.875, 3.125] ; "unnormalized")]
+    #[test_case(Coord::point(1.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0), vec![-2.25, -1.75, -0.25, 0.25] ; "inside the tube")]
+    fn test_intersect(origin: Coord, direction: Coord, expected: Vec<f32>) {
+        let t = Torus::default();
+        let xs = t.intersect(Ray::new(origin, direction)).unwrap();
+        let times: Vec<f32> = xs.iter().map(|x| x.get_time()).collect();
+        assert_eq!(times.len(), expected.len(), "{times:?}");
+        for (t, e) in times.iter().zip(expected) {
+            assert!((t - e).abs() < 0.0001, "{times:?}");
+        }
+    }
+
+    #[test_case(Coord::point(0.0, 5.0, 0.0), Coord::vec(0.0, -1.0, 0.0) ; "through the hole")]
+    #[test_case(Coord::point(-5.0, 0.5, 0.0), Coord::vec(1.0, 0.0, 0.0) ; "over the top")]
+    #[test_case(Coord::point(-5.0, 0.0, 5.0), Coord::vec(1.0, 0.0, 0.0) ; "beside")]
+    fn test_miss(origin: Coord, direction: Coord) {
+        assert!(Torus::default().intersect(Ray::new(origin, direction)).is_none());
+    }
+
+    #[test]
+    fn test_intersect_far_away() {
+        // the hits of a ray from far off stay precise enough to land on the surface
+        let t = Torus::new(Matrix::translation(0.0, 0.0, 500.0), Material::default(), 2.0, 0.5).unwrap();
+        let r = Ray::new(Coord::point(0.0, 0.1, 0.0), Coord::vec(0.003, 0.0, 1.0).normalized());
+        let xs = t.intersect(r).unwrap();
+        assert_eq!(xs.len(), 4);
+        for x in xs {
+            let p = t.get_inverse() * r.position(x.get_time());
+            let tube = (p.get_x().hypot(p.get_z()) - 2.0).hypot(p.get_y());
+            assert!((tube - 0.5).abs() < 0.001, "{tube}");
+        }
+    }
+
+    #[test_case(Coord::point(1.25, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0) ; "outer edge")]
+    #[test_case(Coord::point(0.75, 0.0, 0.0), Coord::vec(-1.0, 0.0, 0.0) ; "inner edge")]
+    #[test_case(Coord::point(0.0, 0.25, 1.0), Coord::vec(0.0, 1.0, 0.0) ; "top")]
+    #[test_case(Coord::point(0.0, -0.25, -1.0), Coord::vec(0.0, -1.0, 0.0) ; "bottom")]
+    fn test_normal_at(pos: Coord, normal: Coord) {
+        assert!(close(Torus::default().normal_at(pos), normal));
+    }
+
+    #[test]
+    fn test_normal_at_transformed() {
+        let t = Torus::new(Matrix::translation(0.0, 2.0, 0.0) * Matrix::rotation_x(PI / 2.0), Material::default(), 1.0, 0.25).unwrap();
+        // the ring now stands up in the xy plane
+        assert!(close(t.normal_at(Coord::point(1.25, 2.0, 0.0)), Coord::vec(1.0, 0.0, 0.0)));
+        assert!(close(t.normal_at(Coord::point(1.0, 2.0, -0.25)), Coord::vec(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Torus::new(Matrix::identity(4), Material::default(), 1.0, 0.0).is_err());
+        assert!(Torus::new(Matrix::identity(4), Material::default(), -1.0, 0.5).is_err());
+        let spindle = Torus::new(Matrix::identity(4), Material::default(), 0.5, 1.0).unwrap();
+        assert_eq!((spindle.get_major(), spindle.get_minor()), (0.5, 1.0));
+        assert!(spindle.intersect(Ray::new(Coord::point(0.0, 5.0, 0.0), Coord::vec(0.0, -1.0, 0.0))).is_some());
+    }
+
+    #[test]
+    fn test_uv_at() {
+        let t = Torus::default();
+        let (_, v) = t.uv_at(Coord::point(0.0, 0.0, 1.25));
+        assert!(v.abs() < 0.0001 || (v - 1.0).abs() < 0.0001);
+        let (_, v) = t.uv_at(Coord::point(0.0, 0.25, 1.0));
+        assert!((v - 0.25).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_sample_surface() {
+        let mut t = Torus::new(Matrix::identity(4), Material::default(), 2.0, 0.5).unwrap();
+        t.set_transformation(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
+        let n = 32;
+        let mut inverse_pdf = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u = [(i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32, 0.5];
+                let sample = t.sample_surface(u).unwrap();
+                assert!(close(sample.get_normal(), t.normal_at(sample.get_point())));
+                inverse_pdf += 1.0 / sample.get_pdf();
+            }
+        }
+        // 4 pi^2 R r, with both radii doubled
+        let area = inverse_pdf / (n * n) as f32;
+        assert!((area - 4.0 * PI * PI * 4.0 * 1.0).abs() < 0.01 * area, "{area}");
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use crate::{coord::Coord, error::{Result, RtcError}, impl_getters, impl_renderable_base, impl_renderable_tests, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, RenderableType}, tex::color::Color};
+
+static EPSILON: f32 = 0.00001;
+
+/// a triangle with a normal at each corner, blended across it so a mesh of them shades like the
+/// curved surface it was cut from
+#[derive(Clone, PartialEq)]
+pub struct SmoothTriangle {
+    transformation: Matrix,
+    inverse: Matrix,
+    material: Arc<Material>,
+    points: [Coord; 3],
+    normals: [Coord; 3],
+}
+
+impl_getters!(SmoothTriangle, points: [Coord; 3], normals: [Coord; 3]);
+
+impl SmoothTriangle {
+    /// returns an error if a corner isn't a point, a normal isn't a vec or the transformation
+    /// can't be inverted
+    pub fn new(transformation: Matrix, material: Material, points: [Coord; 3], normals: [Coord; 3]) -> Result<Self> {
+        if points.iter().any(|p| !p.is_point()) {
+            return Err(RtcError::ExpectedPoint);
+        }
+        if normals.iter().any(|n| !n.is_vec()) {
+            return Err(RtcError::ExpectedVector);
+        }
+        Ok(Self {
+            inverse: transformation.transformation_inverse()?,
+            transformation,
+            material: Arc::new(material),
+            points,
+            normals: normals.map(|n| n.normalized()),
+        })
+    }
+
+    /// a flat triangle, every corner gets the face normal `(p3 - p1) x (p2 - p1)`
+    pub fn flat(transformation: Matrix, material: Material, points: [Coord; 3]) -> Result<Self> {
+        let normal = (points[2] - points[0]).cross(&(points[1] - points[0]));
+        if normal.magnitude() < f32::EPSILON {
+            return Err(RtcError::SingularTransformation);
+        }
+        Self::new(transformation, material, points, [normal; 3])
+    }
+
+    /// weights of the three corners at object space `pos`, projected onto the triangle's plane
+    fn barycentric(&self, pos: Coord) -> [f32; 3] {
+        let [a, b, c] = self.points;
+        let (e1, e2, p) = (b - a, c - a, pos - a);
+        let (d11, d12, d22) = (e1.dot(e1), e1.dot(e2), e2.dot(e2));
+        let (dp1, dp2) = (p.dot(e1), p.dot(e2));
+        let denom = d11 * d22 - d12 * d12;
+        if denom.abs() < f32::EPSILON {
+            return [1.0, 0.0, 0.0];
+        }
+        let u = (d22 * dp1 - d12 * dp2) / denom;
+        let v = (d11 * dp2 - d12 * dp1) / denom;
+        [1.0 - u - v, u, v]
+    }
+
+    fn normal_at_local_space(&self, pos: Coord) -> Coord {
+        let [w0, w1, w2] = self.barycentric(pos);
+        let local_normal = self.normals[0] * w0 + self.normals[1] * w1 + self.normals[2] * w2;
+        let mut out = self.get_inverse().transpose() * local_normal;
+        out.set_w(0.0);
+        out.normalized()
+    }
+}
+
+/// where a ray hits triangle abc as (t, u, v), with u and v the weights of b and c
+/// (Moller-Trumbore)
+pub(crate) fn intersect_triangle(origin: Coord, dir: Coord, a: Coord, b: Coord, c: Coord) -> Option<(f32, f32, f32)> {
+    let (e1, e2) = (b - a, c - a);
+    let p = dir.cross(&e2);
+    let det = e1.dot(p);
+    if det.abs() < f32::EPSILON * f32::EPSILON {
+        return None;
+    }
+    let inv = 1.0 / det;
+    let s = origin - a;
+    let u = s.dot(p) * inv;
+    if !(-EPSILON..=1.0 + EPSILON).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let v = dir.dot(q) * inv;
+    if v < -EPSILON || u + v > 1.0 + EPSILON {
+        return None;
+    }
+    Some((e2.dot(q) * inv, u, v))
+}
+
+impl_renderable_base!(SmoothTriangle, RenderableType::Triangle);
+
+impl_renderable_tests!(crate::primitives::triangle::SmoothTriangle, RenderableType::Triangle);
+
+impl Renderable for SmoothTriangle {
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        let ray = ray.transform(self.get_inverse());
+        let [a, b, c] = self.points;
+        match intersect_triangle(ray.get_origin(), ray.get_direction(), a, b, c) {
+            Some((t, _, _)) => {
+                (ray, Some(vec![Intersection::new(t, this.clone())]))
+            }
+            None => (ray, None),
+        }
+    }
+
+    fn normal_at(&self, pos: Coord) -> Coord {
+        let pos = self.get_inverse() * pos;
+        self.normal_at_local_space(pos)
+    }
+
+    /// the weights of the second and third corners
+    fn uv_at(&self, pos: Coord) -> (f32, f32) {
+        let [_, u, v] = self.barycentric(self.get_inverse() * pos);
+        (u, v)
+    }
+
+    /// the triangle (0, 1, 0), (-1, 0, 0), (1, 0, 0) facing -z
+    fn default() -> Self {
+        let normal = Coord::vec(0.0, 0.0, -1.0);
+        Self {
+            transformation: Matrix::identity(4),
+            inverse: Matrix::identity(4),
+            material: Arc::new(Material::default()),
+            points: [Coord::point(0.0, 1.0, 0.0), Coord::point(-1.0, 0.0, 0.0), Coord::point(1.0, 0.0, 0.0)],
+            normals: [normal; 3],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn close(a: Coord, b: Coord) -> bool {
+        (a - b).magnitude() < 0.0001
+    }
+
+    fn smooth() -> SmoothTriangle {
+        SmoothTriangle::new(
+            Matrix::identity(4),
+            Material::default(),
+            [Coord::point(0.0, 1.0, 0.0), Coord::point(-1.0, 0.0, 0.0), Coord::point(1.0, 0.0, 0.0)],
+            [Coord::vec(0.0, 1.0, 0.0), Coord::vec(-1.0, 0.0, 0.0), Coord::vec(1.0, 0.0, 0.0)],
+        ).unwrap()
+    }
+
+    #[test_case(Coord::point(0.0, -1.0, -2.0), Coord::vec(0.0, 1.0, 0.0) ; "parallel")]
+    #[test_case(Coord::point(1.0, 1.0, -2.0), Coord::vec(0.0, 0.0, 1.0) ; "past p1 p3 edge")]
+    #[test_case(Coord::point(-1.0, 1.0, -2.0), Coord::vec(0.0, 0.0, 1.0) ; "past p1 p2 edge")]
+    #[test_case(Coord::point(0.0, -1.0, -2.0), Coord::vec(0.0, 0.0, 1.0) ; "past p2 p3 edge")]
+    fn test_miss(origin: Coord, direction: Coord) {
+        assert!(SmoothTriangle::default().intersect(Ray::new(origin, direction)).is_none());
+    }
+
+    #[test]
+    fn test_hit() {
+        let t = SmoothTriangle::default();
+        let xs = t.intersect(Ray::new(Coord::point(0.0, 0.5, -2.0), Coord::vec(0.0, 0.0, 1.0))).unwrap();
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].get_time(), 2.0);
+        assert!(close(t.normal_at(Coord::point(0.0, 0.5, 0.0)), Coord::vec(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_smooth_normals() {
+        let t = smooth();
+        assert!(close(t.normal_at(Coord::point(0.0, 1.0, 0.0)), Coord::vec(0.0, 1.0, 0.0)));
+        // halfway along the bottom edge the side normals cancel out
+        let n = t.normal_at(Coord::point(-0.2, 0.3, 0.0));
+        let [w0, w1, w2] = t.barycentric(Coord::point(-0.2, 0.3, 0.0));
+        assert!((w0 - 0.3).abs() < 0.0001 && (w1 - 0.45).abs() < 0.0001 && (w2 - 0.25).abs() < 0.0001);
+        assert!(close(n, Coord::vec(-0.2, 0.3, 0.0).normalized()));
+        let (u, v) = t.uv_at(Coord::point(-0.2, 0.3, 0.0));
+        assert!((u - 0.45).abs() < 0.0001 && (v - 0.25).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_new() {
+        let p = Coord::point(0.0, 0.0, 0.0);
+        let n = Coord::vec(0.0, 1.0, 0.0);
+        assert_eq!(SmoothTriangle::new(Matrix::identity(4), Material::default(), [p, n, p], [n; 3]).err(), Some(RtcError::ExpectedPoint));
+        assert_eq!(SmoothTriangle::new(Matrix::identity(4), Material::default(), [p; 3], [n, p, n]).err(), Some(RtcError::ExpectedVector));
+        assert!(SmoothTriangle::flat(Matrix::identity(4), Material::default(), [p; 3]).is_err());
+        let flat = SmoothTriangle::flat(Matrix::identity(4), Material::default(), SmoothTriangle::default().get_points()).unwrap();
+        assert!(close(flat.normal_at(Coord::point(0.0, 0.5, 0.0)), Coord::vec(0.0, 0.0, -1.0)));
+    }
+}
@@ -6,10 +6,12 @@ use super::Coord;
 pub struct Ray {
     origin: Coord,
     direction: Coord,
-    norm_dir: Coord
+    norm_dir: Coord,
+    // moment within the camera shutter the ray was sent at, moving objects are placed by it
+    time: f32
 }
 
-impl_getters!(Ray, origin: Coord, direction: Coord, norm_dir: Coord);
+impl_getters!(Ray, origin: Coord, direction: Coord, norm_dir: Coord, time: f32);
 
 #[allow(dead_code)]
 impl Ray {
@@ -18,7 +20,13 @@ impl Ray {
         let direction = direction.to_vec();
         assert!(origin.is_point());
         assert!(direction.is_vec());
-        Ray { origin, direction: direction, norm_dir: direction.normalized() }
+        Ray { origin, direction: direction, norm_dir: direction.normalized(), time: 0.0 }
+    }
+
+    /// same ray sent at `time`
+    pub fn with_time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
     }
 
     pub fn position(&self, time: f32) -> Coord {
@@ -33,7 +41,7 @@ impl Ray {
         Self::new(
             mat.clone() * self.origin,
             mat * self.direction
-        )
+        ).with_time(self.time)
     }
 }
 
@@ -78,6 +86,13 @@ mod tests {
         assert_eq!(new.get_direction(), Coord::vec(0.0, 3.0, 0.0));
     }
 
+    #[test]
+    fn test_transform_keeps_time() {
+        let r = Ray::new(Coord::point(1.0, 2.0, 3.0), Coord::vec(0.0, 1.0, 0.0)).with_time(0.25);
+        assert_eq!(r.get_time(), 0.25);
+        assert_eq!(r.transform(Matrix::translation(3.0, 4.0, 5.0)).get_time(), 0.25);
+    }
+
     #[test]
     fn test_is_vec() {
         let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::point(0.0, 1.0, 1.0));
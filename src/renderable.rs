@@ -1,5 +1,6 @@
 use std::{fmt::Debug, sync::Arc};
-use crate::{coord::Coord, material::Material, matrix::Matrix, ray::Ray, tex::color::Color};
+use crate::impl_getters;
+use crate::{coord::Coord, error::Result, material::{Material, MaterialParam}, matrix::Matrix, ray::Ray, tex::color::Color};
 
 #[derive(PartialEq, Debug)]
 pub enum RenderableType {
@@ -8,21 +9,41 @@ pub enum RenderableType {
     Cube,
     Cylinder,
     Cone,
+    Torus,
+    Disk,
+    Quad,
+    Sdf,
+    Heightfield,
+    Quadric,
+    Triangle,
+    BezierPatch,
+    Moving,
     Mesh
 }
 
 pub trait RenderableBase {
+    /// a copy of the material, `get_shared_material` hands out the shared one without copying it
     fn get_material(&self) -> Material;
 
+    /// gives the object a material of its own
     fn set_material(&mut self, mat: Material);
 
+    fn get_shared_material(&self) -> Arc<Material>;
+
+    /// uses a material shared with other objects, see `World::add_material`
+    fn set_shared_material(&mut self, mat: Arc<Material>);
+
     fn get_pos(&self) -> Coord;
 
     fn get_transformation(&self) -> Matrix;
 
-    fn set_transformation(&mut self, transform: Matrix);
+    /// inverse of the transformation, cached when the transformation is set
+    fn get_inverse(&self) -> Matrix;
+
+    /// returns an error if the transformation isn't 4x4 or can't be inverted
+    fn set_transformation(&mut self, transform: Matrix) -> Result<()>;
 
-    fn apply_transformation(&mut self, transform: Matrix);
+    fn apply_transformation(&mut self, transform: Matrix) -> Result<()>;
 
     fn get_type(&self) -> RenderableType;
 
@@ -32,6 +53,11 @@ pub trait RenderableBase {
 
     fn get_color_at(&self, pos: Coord) -> Color;
 
+    /// material parameter at a world space pos, following any `ScalarMap` on it
+    fn get_param_at(&self, param: MaterialParam, pos: Coord) -> f32 {
+        self.get_shared_material().get_param_at(param, self.get_inverse() * pos)
+    }
+
     fn as_any(&self) -> &dyn std::any::Any;
 
     fn compare(&self, other: Arc<dyn Renderable>) -> bool;
@@ -44,20 +70,31 @@ pub trait RenderableBase {
 macro_rules! impl_renderable_base {
     ($type:ty, $variant:expr) => {
         impl crate::renderable::RenderableBase for $type {
-            // TODO: should return reference not actual material? ie Material should be Arc<Material>
-            fn get_material(&self) -> Material { self.material.clone() }
-            fn set_material(&mut self, mat: Material) { self.material = mat; }
+            fn get_material(&self) -> Material { self.material.as_ref().clone() }
+            fn set_material(&mut self, mat: Material) { self.material = Arc::new(mat); }
+            fn get_shared_material(&self) -> Arc<Material> { self.material.clone() }
+            fn set_shared_material(&mut self, mat: Arc<Material>) { self.material = mat; }
             fn get_pos(&self) -> Coord { self.transformation.to_point() }
             fn get_transformation(&self) -> Matrix { self.transformation.clone() }
-            fn set_transformation(&mut self, transform: Matrix) { self.transformation = transform }
-            fn apply_transformation(&mut self, transform: Matrix) { self.transformation = self.get_transformation() * transform }
+            fn get_inverse(&self) -> Matrix { self.inverse.clone() }
+            fn set_transformation(&mut self, transform: Matrix) -> crate::error::Result<()> {
+                self.inverse = transform.transformation_inverse()?;
+                self.transformation = transform;
+                Ok(())
+            }
+            fn apply_transformation(&mut self, transform: Matrix) -> crate::error::Result<()> {
+                if transform.get_size() != 4 {
+                    return Err(crate::error::RtcError::InvalidMatrixSize(transform.get_size()));
+                }
+                self.set_transformation(self.get_transformation() * transform)
+            }
             fn get_type(&self) -> RenderableType { $variant }
             fn clone_rc(&self) -> Arc<dyn Renderable> { Arc::new(self.clone()) }
             fn clone_dyn(&self) -> Box<dyn Renderable> { Box::new(self.clone()) }
             
             fn get_color_at(&self, pos: Coord) -> Color {
-                let local_pos = self.get_transformation().inverse().unwrap() * pos;
-                self.get_material().get_color_at(local_pos)
+                let local_pos = self.get_inverse() * pos;
+                self.get_shared_material().get_color_at(local_pos)
             }
             
             fn as_any(&self) -> &dyn std::any::Any {
@@ -80,15 +117,57 @@ macro_rules! impl_renderable_base {
  * requires RenderableBase implementation (use impl_renderable_base(Type, RenderableBase:Type))
  */
 pub trait Renderable: RenderableBase + Send + Sync {
-    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection>>;
+    fn intersect(&self, ray: Ray) -> Option<Vec<Intersection>> {
+        let (_, out) = self.intersect_get_ray(ray);
+        out
+    }
 
-    fn intersect_get_ray(&self, ray: Ray) -> (Ray, Option<Vec<Intersection>>);
+    /// the ray moved into object space and its hits, which hold a copy of this object. the world
+    /// uses `intersect_shared` with its own handle instead so nothing is copied per ray
+    fn intersect_get_ray(&self, ray: Ray) -> (Ray, Option<Vec<Intersection>>) {
+        self.intersect_shared(&self.clone_rc(), ray)
+    }
+
+    /// like `intersect_get_ray` but the hits point at `this`, which has to be an `Arc` holding
+    /// this object
+    fn intersect_shared(&self, this: &Arc<dyn Renderable>, ray: Ray) -> (Ray, Option<Vec<Intersection>>);
 
     fn normal_at(&self, pos: Coord) -> Coord;
 
+    /// texture coords (u, v), both in [0, 1], of a world space pos on the surface
+    fn uv_at(&self, pos: Coord) -> (f32, f32);
+
+    /// a random point on the surface picked from 3 uniform numbers in [0, 1), used to sample
+    /// glowing objects as lights. `None` for shapes without a finite area
+    fn sample_surface(&self, _u: [f32; 3]) -> Option<SurfaceSample> {
+        None
+    }
+
     fn default() -> Self where Self: Sized;
 }
 
+/// a point picked on an object's surface, see `Renderable::sample_surface`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceSample {
+    point: Coord,
+    normal: Coord,
+    pdf: f32,   // per unit of world space area
+}
+
+impl_getters!(SurfaceSample, point: Coord, normal: Coord, pdf: f32);
+
+impl SurfaceSample {
+    /// moves a sample picked in object space, `pdf` being per unit of object space area, into
+    /// world space
+    pub fn from_local(object: &dyn RenderableBase, point: Coord, normal: Coord, pdf: f32) -> Self {
+        let mut world_normal = object.get_inverse().transpose() * normal;
+        world_normal.set_w(0.0);
+        // a bit of surface with normal n grows by |det M| * |M^-T n| going to world space
+        let stretch = object.get_transformation().determinate().abs() * world_normal.magnitude();
+        Self { point: object.get_transformation() * point, normal: world_normal.normalized(), pdf: pdf / stretch }
+    }
+}
+
 impl Clone for Box<dyn Renderable> {
   fn clone(&self) -> Self {
       self.clone_dyn()
@@ -106,22 +185,23 @@ pub fn compare_renderables(a: &dyn Renderable, b: &dyn Renderable) {
 pub struct Intersection {
     t: f32,
     object: Arc<dyn Renderable>,
-    reflectv: Coord
+    owner: Arc<dyn Renderable>      // the object that was hit, `object` unless that's a snapshot of a wrapper like `Moving`
 }
 
 #[allow(dead_code)]
 impl Intersection {
-    pub fn new(t: f32, object: Arc<dyn Renderable>, reflectv: Coord) -> Self {
-        assert!(reflectv.is_vec());
-        Self { t, object, reflectv }
+    pub fn new(t: f32, object: Arc<dyn Renderable>) -> Self {
+        Self { t, owner: object.clone(), object }
     }
 
-    pub fn get_time(&self) -> f32 {
-        self.t
+    /// the same hit belonging to `owner`, for wrappers that intersect a stand-in for themselves
+    pub fn with_owner(mut self, owner: Arc<dyn Renderable>) -> Self {
+        self.owner = owner;
+        self
     }
 
-    pub fn get_reflectv(&self) -> Coord {
-        self.reflectv
+    pub fn get_time(&self) -> f32 {
+        self.t
     }
 
     pub fn get_object(&self) -> Arc<dyn Renderable> {
@@ -132,6 +212,17 @@ impl Intersection {
         self.object.clone()
     }
 
+    /// the object that was hit, as it is in the world
+    pub fn get_owner(&self) -> Arc<dyn Renderable> {
+        self.owner.clone()
+    }
+
+    /// whether both hits are on the same object, going by identity rather than value so two equal
+    /// objects in a world are still told apart
+    pub fn same_object(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.owner, &other.owner)
+    }
+
     /**
      * returns a sorted list of intersections, the earliest returned first
      */
@@ -157,7 +248,7 @@ impl Intersection {
 
 impl PartialEq for Intersection{
     fn eq(&self, other: &Self) -> bool {
-        self.t == other.t && self.object.compare(other.object.clone()) && self.reflectv == other.reflectv
+        self.t == other.t && self.same_object(other)
     }
 }
 
@@ -198,8 +289,20 @@ macro_rules! impl_renderable_tests {
             #[test]
             fn test_assign_transform() {
                 let mut o = <$type>::default();
-                o.set_transformation(Matrix::translation(2.0, 3.0, 4.0));
-                assert_eq!(o.get_transformation(), Matrix::translation(2.0, 3.0, 4.0))
+                o.set_transformation(Matrix::translation(2.0, 3.0, 4.0)).unwrap();
+                assert_eq!(o.get_transformation(), Matrix::translation(2.0, 3.0, 4.0));
+                assert_eq!(o.get_inverse(), Matrix::translation(-2.0, -3.0, -4.0));
+            }
+
+            #[test]
+            fn test_assign_singular_transform() {
+                let mut o = <$type>::default();
+                let e = o.set_transformation(Matrix::scaling(0.0, 1.0, 1.0));
+                assert_eq!(e, Err(crate::error::RtcError::SingularTransformation));
+                assert_eq!(o.get_transformation(), Matrix::identity(4));
+                let e = o.apply_transformation(Matrix::scaling(1.0, 1.0, 0.0));
+                assert_eq!(e, Err(crate::error::RtcError::SingularTransformation));
+                assert_eq!(o.get_transformation(), Matrix::identity(4));
             }
 
             #[test]
@@ -211,7 +314,7 @@ macro_rules! impl_renderable_tests {
             #[test]
             fn test_set_material() {
                 let mut m = Material::default();
-                m.set_ambient(1.0);
+                m.set_ambient(1.0).unwrap();
                 let mut o = <$type>::default();
                 o.set_material(m.clone());
                 assert_eq!(o.get_material(), m);
@@ -221,7 +324,7 @@ macro_rules! impl_renderable_tests {
             fn test_intersect_scaled() {
                 let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
                 let mut s = <$type>::default();
-                s.set_transformation(Matrix::scaling(2.0, 2.0, 2.0));
+                s.set_transformation(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
                 let (sr, _xs) = s.intersect_get_ray(r);
                 assert_eq!(sr.get_origin(), Coord::point(0.0, 0.0, -2.5));
                 assert_eq!(sr.get_direction(), Coord::vec(0.0, 0.0, 0.5));
@@ -231,7 +334,7 @@ macro_rules! impl_renderable_tests {
             fn test_intersect_translated() {
                 let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
                 let mut s = <$type>::default();
-                s.set_transformation(Matrix::translation(5.0, 0.0, 0.0));
+                s.set_transformation(Matrix::translation(5.0, 0.0, 0.0)).unwrap();
                 let (sr, _xs) = s.intersect_get_ray(r);
                 assert_eq!(sr.get_origin(), Coord::point(-5.0, 0.0, -5.0));
                 assert_eq!(sr.get_direction(), Coord::vec(0.0, 0.0, 1.0));
@@ -263,7 +366,7 @@ use crate::{coord::Coord, ray::Ray, renderable::{Renderable, RenderableBase}, pr
     #[test]
     fn test_creation() {
         let s = Arc::new(Sphere::default());
-        let intersection = Intersection::new(3.5, s.clone(), Coord::vec(0.0, 0.0, 0.0));
+        let intersection = Intersection::new(3.5, s.clone());
         assert_eq!(intersection.t, 3.5);
         //compare(intersection.object, s);
         assert_eq!(intersection.object.get_material(), s.get_material());
@@ -286,6 +389,19 @@ use crate::{coord::Coord, ray::Ray, renderable::{Renderable, RenderableBase}, pr
         assert_eq!(xs[1].get_time(), 6.0);
     }
 
+    #[test]
+    fn test_intersect_shared() {
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        let s: Arc<dyn Renderable> = Arc::new(Sphere::default());
+        let xs = s.intersect_shared(&s, r).1.unwrap();
+        assert!(xs.iter().all(|x| Arc::ptr_eq(&x.get_object(), &s) && Arc::ptr_eq(&x.get_owner(), &s)));
+        assert!(xs[0].same_object(&xs[1]));
+        // a copy of the sphere is equal to it but isn't the same object
+        let copy = s.intersect(r).unwrap();
+        assert!(!xs[0].same_object(&copy[0]));
+        assert_ne!(xs[0], copy[0]);
+    }
+
     #[test]
     fn test_intersection_aggregation() {
         let s = Sphere::default();
@@ -297,12 +413,12 @@ use crate::{coord::Coord, ray::Ray, renderable::{Renderable, RenderableBase}, pr
         assert!(s.intersect(ray).is_none());
         let data = Intersection::aggregate_intersections(intersections);
         assert_eq!(data.len(), 4);
-        let test = Intersection::new(4.0, Arc::new(s.clone()), Coord::vec(0.0, 0.0, 0.0));
+        let test = Intersection::new(4.0, Arc::new(s.clone()));
         compare_intersection(&data[0], &test);
         compare_intersection(&data[1], &test);
         //assert_eq!(data[0], test);
         //assert_eq!(data[2], test);
-        let test = Intersection::new(6.0, Arc::new(s), Coord::vec(0.0, 0.0, 0.0));
+        let test = Intersection::new(6.0, Arc::new(s));
         compare_intersection(&data[2], &test);
         compare_intersection(&data[3], &test);
         //assert_eq!(data[1], test);
@@ -312,27 +428,27 @@ use crate::{coord::Coord, ray::Ray, renderable::{Renderable, RenderableBase}, pr
     #[test]
     fn test_detect_hit() {
         let s = Arc::new(Sphere::default());
-        let i1 = Intersection::new(1.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
-        let i2 = Intersection::new(2.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
+        let i1 = Intersection::new(1.0, s.clone());
+        let i2 = Intersection::new(2.0, s.clone());
         let data = vec![i1.clone(), i2];
         compare_intersection(Intersection::find_hit(&data).unwrap(), &i1);
         //assert_eq!(Intersection::find_hit(&data).unwrap(), &i1);
 
-        let i1 = Intersection::new(-1.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
-        let i2 = Intersection::new(1.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
+        let i1 = Intersection::new(-1.0, s.clone());
+        let i2 = Intersection::new(1.0, s.clone());
         let data = vec![i1, i2.clone()];
         compare_intersection(Intersection::find_hit(&data).unwrap(), &i2);
         //assert_eq!(Intersection::find_hit(&data).unwrap(), &i2);
 
-        let i1 = Intersection::new(-1.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
-        let i2 = Intersection::new(-2.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
+        let i1 = Intersection::new(-1.0, s.clone());
+        let i2 = Intersection::new(-2.0, s.clone());
         let data = vec![i1, i2];
         assert!(Intersection::find_hit(&data).is_none());
     
-        let i1 = Intersection::new(5.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
-        let i2 = Intersection::new(7.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
-        let i3 = Intersection::new(-3.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
-        let i4 = Intersection::new(2.0, s.clone(), Coord::vec(0.0, 0.0, 0.0));
+        let i1 = Intersection::new(5.0, s.clone());
+        let i2 = Intersection::new(7.0, s.clone());
+        let i3 = Intersection::new(-3.0, s.clone());
+        let i4 = Intersection::new(2.0, s.clone());
         let data = vec![i1, i2, i3, i4.clone()];
         compare_intersection(Intersection::find_hit(&data).unwrap(), &i4);
         //assert_eq!(Intersection::find_hit(&data).unwrap(), &i4);
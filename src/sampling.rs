@@ -0,0 +1,39 @@
+//! seeded random number streams, everything random in a render (sample positions, path
+//! directions, pattern jitter) draws from one of these so the same seed gives the same image
+
+use rand::SeedableRng;
+use rand::rngs::Xoshiro256PlusPlus;
+
+/// random number generator used for sampling, a named algorithm rather than `StdRng` so the
+/// numbers don't change with the rand version
+pub type SampleRng = Xoshiro256PlusPlus;
+
+/// seeds a generator from `seed` and `keys` mixed together, neighbouring keys give unrelated
+/// streams and the number of keys is mixed in too, so `[1]` and `[1, 0]` differ
+pub fn stream(seed: u64, keys: &[u64]) -> SampleRng {
+    let hash = keys.iter().fold(splitmix64(seed ^ keys.len() as u64), |h, &k| splitmix64(h ^ k));
+    SampleRng::seed_from_u64(hash)
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngExt;
+
+    use super::*;
+
+    #[test]
+    fn test_stream() {
+        let first = stream(0, &[3, 4]).random::<u64>();
+        assert_eq!(stream(0, &[3, 4]).random::<u64>(), first);
+        assert_ne!(stream(0, &[4, 3]).random::<u64>(), first);
+        assert_ne!(stream(1, &[3, 4]).random::<u64>(), first);
+        assert_ne!(stream(0, &[1]).random::<u64>(), stream(0, &[1, 0]).random::<u64>());
+    }
+}
@@ -0,0 +1,747 @@
+//! JSON save/load of a whole `World` plus `Camera`, only built with the `serde` feature
+//!
+//! trait objects can't be serialized directly, so each one is mirrored by a tagged enum
+//! (`ObjectDesc` for `Renderable`s, `TexDesc` for `Tex`) that is converted to and from the real
+//! type. Everything is rebuilt through the normal constructors, so a hand written file gets the
+//! same validation as code.
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, RtcError};
+use crate::{camera::Camera, coord::Coord, light::Light, material::{Material, MaterialParam, ShadingModel}, matrix::Matrix, medium::{Medium, Volume}, motion::Motion, renderable::{Renderable, RenderableBase}, world::World};
+use crate::primitives::{bezier::BezierPatch, cube::Cube, cylinder::Cylinder, disk::Disk, heightfield::Heightfield, moving::Moving, plane::Plane, quad::Quad, quadric::Quadric, sdf::{Sdf, SdfNode}, sphere::Sphere, torus::Torus, triangle::SmoothTriangle};
+use crate::tex::{Tex, color::Color, noise::Noise, normal::NormalPerturbation, pattern::{Pattern, PatternType}, scalar::{Channel, ScalarMap}};
+
+/// a 4x4 transformation, row by row
+type MatrixDesc = [[f32; 4]; 4];
+
+fn matrix_to_desc(mat: &Matrix) -> Result<MatrixDesc> {
+    if mat.get_size() != 4 {
+        return Err(RtcError::InvalidMatrixSize(mat.get_size()));
+    }
+    let mut out = [[0.0; 4]; 4];
+    for (i, row) in mat.get_data().iter().enumerate() {
+        out[i].copy_from_slice(row);
+    }
+    Ok(out)
+}
+
+fn matrix_from_desc(desc: MatrixDesc) -> Matrix {
+    Matrix::new(desc.iter().map(|row| row.to_vec()).collect())
+}
+
+fn invalid(msg: &str) -> RtcError {
+    RtcError::InvalidScene(msg.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TexDesc {
+    Color { color: Color },
+    Pattern {
+        pattern_type: PatternType,
+        color_a: Box<TexDesc>,
+        color_b: Box<TexDesc>,
+        transformation: MatrixDesc,
+    },
+    Noise {
+        color_a: Color,
+        color_b: Color,
+        octaves: usize,
+        seed: u64,
+        transformation: MatrixDesc,
+    },
+}
+
+impl TexDesc {
+    pub fn from_tex(tex: &Arc<dyn Tex>) -> Result<Self> {
+        let any = tex.as_any();
+        if let Some(color) = any.downcast_ref::<Color>() {
+            return Ok(Self::Color { color: *color });
+        }
+        if let Some(pattern) = any.downcast_ref::<Pattern>() {
+            return Ok(Self::Pattern {
+                pattern_type: pattern.get_pattern_type(),
+                color_a: Box::new(Self::from_tex(&pattern.get_color_a())?),
+                color_b: Box::new(Self::from_tex(&pattern.get_color_b())?),
+                transformation: matrix_to_desc(&pattern.get_transformation())?,
+            });
+        }
+        if let Some(noise) = any.downcast_ref::<Noise>() {
+            return Ok(Self::Noise {
+                color_a: noise.get_color_a(),
+                color_b: noise.get_color_b(),
+                octaves: noise.get_octaves(),
+                seed: noise.get_seed(),
+                transformation: matrix_to_desc(&noise.get_transformation())?,
+            });
+        }
+        Err(invalid("unsupported texture type"))
+    }
+
+    pub fn into_tex(self) -> Result<Arc<dyn Tex>> {
+        match self {
+            Self::Color { color } => Ok(Arc::new(color)),
+            Self::Pattern { pattern_type, color_a, color_b, transformation } => Ok(Arc::new(Pattern::new(
+                pattern_type,
+                color_a.into_tex()?,
+                color_b.into_tex()?,
+                matrix_from_desc(transformation)
+            )?)),
+            Self::Noise { color_a, color_b, octaves, seed, transformation } => Ok(Arc::new(
+                Noise::new(color_a, color_b, octaves, seed, matrix_from_desc(transformation))?
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterialDesc {
+    ambient: f32,
+    diffuse: f32,
+    specular: f32,
+    shininess: f32,
+    reflection: f32,
+    refractive_index: f32,
+    transparency: f32,
+    texture: TexDesc,
+    #[serde(default)]
+    model: ShadingModel,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    maps: Vec<MapDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    normal: Option<NormalDesc>,
+    #[serde(default = "Color::black", skip_serializing_if = "is_black")]
+    absorption: Color,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    thin_walled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    emission: Option<EmissionDesc>,
+}
+
+/// glow of a material, left out for materials that don't glow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmissionDesc {
+    color: Color,
+    strength: f32,
+}
+
+fn is_black(c: &Color) -> bool {
+    *c == Color::black()
+}
+
+fn is_zero(val: &f32) -> bool {
+    *val == 0.0
+}
+
+/// a `ScalarMap` driving one material parameter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapDesc {
+    param: MaterialParam,
+    channel: Channel,
+    texture: TexDesc,
+}
+
+/// a `NormalPerturbation`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NormalDesc {
+    Bump { channel: Channel, texture: TexDesc, strength: f32 },
+    TangentSpace { texture: TexDesc, strength: f32 },
+}
+
+impl NormalDesc {
+    pub fn from_normal(normal: &NormalPerturbation) -> Result<Self> {
+        Ok(match normal {
+            NormalPerturbation::Bump { map, strength } => Self::Bump {
+                channel: map.get_channel(),
+                texture: TexDesc::from_tex(&map.get_tex())?,
+                strength: *strength,
+            },
+            NormalPerturbation::TangentSpace { tex, strength } => Self::TangentSpace {
+                texture: TexDesc::from_tex(tex)?,
+                strength: *strength,
+            },
+        })
+    }
+
+    pub fn into_normal(self) -> Result<NormalPerturbation> {
+        Ok(match self {
+            Self::Bump { channel, texture, strength } => NormalPerturbation::bump(ScalarMap::new(texture.into_tex()?, channel), strength),
+            Self::TangentSpace { texture, strength } => NormalPerturbation::tangent_space(texture.into_tex()?, strength),
+        })
+    }
+}
+
+impl MaterialDesc {
+    pub fn from_material(mat: &Material) -> Result<Self> {
+        Ok(Self {
+            ambient: mat.get_ambient(),
+            diffuse: mat.get_diffuse(),
+            specular: mat.get_specular(),
+            shininess: mat.get_shininess(),
+            reflection: mat.get_reflection(),
+            refractive_index: mat.get_refractive_index(),
+            transparency: mat.get_transparency(),
+            texture: TexDesc::from_tex(&mat.get_texture())?,
+            model: mat.get_model(),
+            maps: mat.get_maps().into_iter()
+                .map(|(param, map)| Ok(MapDesc { param, channel: map.get_channel(), texture: TexDesc::from_tex(&map.get_tex())? }))
+                .collect::<Result<_>>()?,
+            normal: mat.get_normal().as_ref().map(NormalDesc::from_normal).transpose()?,
+            absorption: mat.get_absorption(),
+            thin_walled: mat.get_thin_walled(),
+            emission: mat.is_emissive().then(|| EmissionDesc { color: mat.get_emission(), strength: mat.get_emission_strength() }),
+        })
+    }
+
+    pub fn into_material(self) -> Result<Material> {
+        let mut out = Material::new(
+            self.ambient,
+            self.diffuse,
+            self.specular,
+            self.shininess,
+            self.reflection,
+            self.refractive_index,
+            self.transparency,
+            self.texture.into_tex()?
+        )?;
+        out.set_model(self.model)?;
+        for map in self.maps {
+            out.set_map(map.param, ScalarMap::new(map.texture.into_tex()?, map.channel));
+        }
+        out.set_normal(self.normal.map(NormalDesc::into_normal).transpose()?);
+        out.set_absorption(self.absorption)?;
+        out.set_thin_walled(self.thin_walled);
+        if let Some(emission) = self.emission {
+            out.set_emission(emission.color, emission.strength)?;
+        }
+        Ok(out)
+    }
+}
+
+/// one renderable, `min` and `max` of a cylinder are left out when unbounded as JSON has no infinity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ObjectDesc {
+    Sphere { transformation: MatrixDesc, material: MaterialDesc },
+    Plane { transformation: MatrixDesc, material: MaterialDesc },
+    Cube { transformation: MatrixDesc, material: MaterialDesc },
+    Cylinder {
+        transformation: MatrixDesc,
+        material: MaterialDesc,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f32>,
+        closed: bool,
+    },
+    Torus { transformation: MatrixDesc, material: MaterialDesc, major: f32, minor: f32 },
+    /// `inner` is left out for a full disk
+    Disk {
+        transformation: MatrixDesc,
+        material: MaterialDesc,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        inner: f32,
+    },
+    Quad { transformation: MatrixDesc, material: MaterialDesc },
+    /// coefficients `[xx, yy, zz, xy, xz, yz, x, y, z, 1]`, `min` and `max` left out like a cylinder's
+    Quadric {
+        transformation: MatrixDesc,
+        material: MaterialDesc,
+        coefficients: [f32; 10],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max: Option<f32>,
+        closed: bool,
+    },
+    /// `heights` row by row, `width` samples along x for each of the `depth` rows along z
+    Heightfield { transformation: MatrixDesc, material: MaterialDesc, width: usize, depth: usize, heights: Vec<f32> },
+    /// corners as points and the normals at them as vecs, `[x, y, z]`
+    SmoothTriangle { transformation: MatrixDesc, material: MaterialDesc, points: [[f32; 3]; 3], normals: [[f32; 3]; 3] },
+    /// the 16 control points row by row along v, each row along u
+    BezierPatch { transformation: MatrixDesc, material: MaterialDesc, control: Vec<[f32; 3]>, resolution: usize, refine: bool },
+    /// a tree of `SdfNode`s, custom distance functions can't be saved
+    Sdf {
+        transformation: MatrixDesc,
+        material: MaterialDesc,
+        root: SdfNode,
+        max_steps: usize,
+        epsilon: f32,
+        max_distance: f32,
+        step_scale: f32,
+    },
+    /// another object following a `Motion`
+    Moving { object: Box<ObjectDesc>, keys: Vec<KeyframeDesc> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyframeDesc {
+    time: f32,
+    transformation: MatrixDesc,
+}
+
+impl ObjectDesc {
+    pub fn from_renderable(obj: &Arc<dyn Renderable>) -> Result<Self> {
+        if let Some(moving) = obj.as_any().downcast_ref::<Moving>() {
+            let keys = moving.get_motion().get_keys().iter()
+                .map(|(time, mat)| Ok(KeyframeDesc { time: *time, transformation: matrix_to_desc(mat)? }))
+                .collect::<Result<_>>()?;
+            return Ok(Self::Moving { object: Box::new(Self::from_renderable(&moving.get_object())?), keys });
+        }
+        let transformation = matrix_to_desc(&obj.get_transformation())?;
+        let material = MaterialDesc::from_material(&obj.get_material())?;
+        let any = obj.as_any();
+        if any.is::<Sphere>() {
+            return Ok(Self::Sphere { transformation, material });
+        }
+        if any.is::<Plane>() {
+            return Ok(Self::Plane { transformation, material });
+        }
+        if any.is::<Cube>() {
+            return Ok(Self::Cube { transformation, material });
+        }
+        if let Some(cyl) = any.downcast_ref::<Cylinder>() {
+            return Ok(Self::Cylinder {
+                transformation,
+                material,
+                min: Some(cyl.get_min()).filter(|val| val.is_finite()),
+                max: Some(cyl.get_max()).filter(|val| val.is_finite()),
+                closed: cyl.get_closed(),
+            });
+        }
+        if let Some(torus) = any.downcast_ref::<Torus>() {
+            return Ok(Self::Torus { transformation, material, major: torus.get_major(), minor: torus.get_minor() });
+        }
+        if let Some(disk) = any.downcast_ref::<Disk>() {
+            return Ok(Self::Disk { transformation, material, inner: disk.get_inner() });
+        }
+        if any.is::<Quad>() {
+            return Ok(Self::Quad { transformation, material });
+        }
+        if let Some(quadric) = any.downcast_ref::<Quadric>() {
+            return Ok(Self::Quadric {
+                transformation,
+                material,
+                coefficients: quadric.get_coefficients(),
+                min: Some(quadric.get_min()).filter(|val| val.is_finite()),
+                max: Some(quadric.get_max()).filter(|val| val.is_finite()),
+                closed: quadric.get_closed(),
+            });
+        }
+        if let Some(terrain) = any.downcast_ref::<Heightfield>() {
+            return Ok(Self::Heightfield {
+                transformation,
+                material,
+                width: terrain.get_width(),
+                depth: terrain.get_depth(),
+                heights: terrain.get_heights().to_vec(),
+            });
+        }
+        if let Some(triangle) = any.downcast_ref::<SmoothTriangle>() {
+            let xyz = |c: Coord| [c.get_x(), c.get_y(), c.get_z()];
+            return Ok(Self::SmoothTriangle {
+                transformation,
+                material,
+                points: triangle.get_points().map(xyz),
+                normals: triangle.get_normals().map(xyz),
+            });
+        }
+        if let Some(patch) = any.downcast_ref::<BezierPatch>() {
+            return Ok(Self::BezierPatch {
+                transformation,
+                material,
+                control: patch.get_control().iter().map(|c| [c.get_x(), c.get_y(), c.get_z()]).collect(),
+                resolution: patch.get_resolution(),
+                refine: patch.get_refine(),
+            });
+        }
+        if let Some(sdf) = any.downcast_ref::<Sdf>() {
+            return Ok(Self::Sdf {
+                transformation,
+                material,
+                root: sdf.get_root(),
+                max_steps: sdf.get_max_steps(),
+                epsilon: sdf.get_epsilon(),
+                max_distance: sdf.get_max_distance(),
+                step_scale: sdf.get_step_scale(),
+            });
+        }
+        Err(invalid(&format!("unsupported object type {:?}", obj.get_type())))
+    }
+
+    pub fn into_renderable(self) -> Result<Arc<dyn Renderable>> {
+        Ok(match self {
+            Self::Sphere { transformation, material } => {
+                let mut sphere = Sphere::default();
+                sphere.set_transformation(matrix_from_desc(transformation))?;
+                sphere.set_material(material.into_material()?);
+                Arc::new(sphere)
+            },
+            Self::Plane { transformation, material } => Arc::new(Plane::new(matrix_from_desc(transformation), material.into_material()?)?),
+            Self::Cube { transformation, material } => Arc::new(Cube::new(matrix_from_desc(transformation), material.into_material()?)?),
+            Self::Cylinder { transformation, material, min, max, closed } => Arc::new(Cylinder::new(
+                matrix_from_desc(transformation),
+                material.into_material()?,
+                min.unwrap_or(-f32::INFINITY),
+                max.unwrap_or(f32::INFINITY),
+                closed
+            )?),
+            Self::Torus { transformation, material, major, minor } => {
+                Arc::new(Torus::new(matrix_from_desc(transformation), material.into_material()?, major, minor)?)
+            },
+            Self::Disk { transformation, material, inner } => {
+                Arc::new(Disk::annulus(matrix_from_desc(transformation), material.into_material()?, inner)?)
+            },
+            Self::Quad { transformation, material } => Arc::new(Quad::new(matrix_from_desc(transformation), material.into_material()?)?),
+            Self::Quadric { transformation, material, coefficients, min, max, closed } => {
+                let mut quadric = Quadric::new(matrix_from_desc(transformation), material.into_material()?, coefficients)?;
+                quadric.set_min(min.unwrap_or(-f32::INFINITY));
+                quadric.set_max(max.unwrap_or(f32::INFINITY));
+                quadric.set_closed(closed);
+                Arc::new(quadric)
+            },
+            Self::Heightfield { transformation, material, width, depth, heights } => {
+                Arc::new(Heightfield::new(matrix_from_desc(transformation), material.into_material()?, width, depth, heights)?)
+            },
+            Self::SmoothTriangle { transformation, material, points, normals } => Arc::new(SmoothTriangle::new(
+                matrix_from_desc(transformation),
+                material.into_material()?,
+                points.map(|[x, y, z]| Coord::point(x, y, z)),
+                normals.map(|[x, y, z]| Coord::vec(x, y, z))
+            )?),
+            Self::BezierPatch { transformation, material, control, resolution, refine } => {
+                let control: Vec<Coord> = control.into_iter().map(|[x, y, z]| Coord::point(x, y, z)).collect();
+                let control = control.try_into().map_err(|c: Vec<Coord>| invalid(&format!("a bezier patch needs 16 control points, got {}", c.len())))?;
+                let mut patch = BezierPatch::new(matrix_from_desc(transformation), material.into_material()?, control, resolution)?;
+                patch.set_refine(refine);
+                Arc::new(patch)
+            },
+            Self::Sdf { transformation, material, root, max_steps, epsilon, max_distance, step_scale } => {
+                let mut sdf = Sdf::new(matrix_from_desc(transformation), material.into_material()?, root)?;
+                sdf.set_limits(max_steps, epsilon, max_distance, step_scale)?;
+                Arc::new(sdf)
+            },
+            Self::Moving { object, keys } => {
+                let keys = keys.into_iter().map(|k| (k.time, matrix_from_desc(k.transformation))).collect();
+                Arc::new(Moving::new(object.into_renderable()?, Motion::new(keys)?))
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedObjectDesc {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// the library material the object shares, its own `material` is saved as a copy of it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shared_material: Option<String>,
+    #[serde(flatten)]
+    object: ObjectDesc,
+}
+
+/// an entry in the world's material library
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedMaterialDesc {
+    name: String,
+    material: MaterialDesc,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LightDesc {
+    position: [f32; 3],
+    intensity: Color,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraDesc {
+    hsize: usize,
+    vsize: usize,
+    field_of_view: f32,
+    transformation: MatrixDesc,
+    #[serde(default)]
+    shutter_open: f32,
+    #[serde(default)]
+    shutter_close: f32,
+}
+
+/// a complete scene, what gets written to and read from JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneDesc {
+    camera: CameraDesc,
+    max_depth: usize,
+    lights: Vec<LightDesc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    materials: Vec<NamedMaterialDesc>,
+    objects: Vec<NamedObjectDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fog: Option<Medium>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    volumes: Vec<VolumeDesc>,
+    #[serde(default = "default_volume_steps")]
+    volume_steps: usize,
+    #[serde(default)]
+    seed: u64,
+}
+
+fn default_volume_steps() -> usize {
+    World::new().get_volume_steps()
+}
+
+/// a `Volume`, the boundary's material is ignored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolumeDesc {
+    boundary: ObjectDesc,
+    medium: Medium,
+}
+
+/// goes through `Medium::new` so loaded values are checked
+fn validated(medium: Medium) -> Result<Medium> {
+    Medium::new(medium.get_absorption(), medium.get_scattering(), medium.get_phase())
+}
+
+impl SceneDesc {
+    pub fn from_scene(world: &World, camera: &Camera) -> Result<Self> {
+        let lights = world.get_light().iter().map(|l| {
+            let pos = l.get_pos();
+            LightDesc { position: [pos.get_x(), pos.get_y(), pos.get_z()], intensity: l.get_intensity() }
+        }).collect();
+        let library: Vec<(String, Arc<Material>)> = world.get_material_names().into_iter()
+            .filter_map(|name| Some((name.clone(), world.get_material(&name)?)))
+            .collect();
+        let mut objects = Vec::new();
+        for id in world.get_ids() {
+            let obj = world.get_obj(id).ok_or(RtcError::UnknownObject(id))?;
+            let shared = obj.get_shared_material();
+            objects.push(NamedObjectDesc {
+                name: world.get_name(id),
+                shared_material: library.iter().find(|(_, m)| Arc::ptr_eq(m, &shared)).map(|(name, _)| name.clone()),
+                object: ObjectDesc::from_renderable(&obj)?,
+            });
+        }
+        let materials = library.iter()
+            .map(|(name, m)| Ok(NamedMaterialDesc { name: name.clone(), material: MaterialDesc::from_material(m)? }))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            camera: CameraDesc {
+                hsize: camera.get_hsize(),
+                vsize: camera.get_vsize(),
+                field_of_view: camera.get_field_of_view(),
+                transformation: matrix_to_desc(&camera.get_transformation())?,
+                shutter_open: camera.get_shutter().0,
+                shutter_close: camera.get_shutter().1,
+            },
+            max_depth: world.get_max_depth(),
+            lights,
+            materials,
+            objects,
+            fog: world.get_fog(),
+            volumes: world.get_volumes().iter()
+                .map(|v| Ok(VolumeDesc { boundary: ObjectDesc::from_renderable(&v.get_boundary())?, medium: v.get_medium() }))
+                .collect::<Result<_>>()?,
+            volume_steps: world.get_volume_steps(),
+            seed: world.get_seed(),
+        })
+    }
+
+    pub fn into_scene(self) -> Result<(World, Camera)> {
+        let mut camera = Camera::new(self.camera.hsize, self.camera.vsize, self.camera.field_of_view);
+        camera.set_transformation(matrix_from_desc(self.camera.transformation))?;
+        camera.set_shutter(self.camera.shutter_open, self.camera.shutter_close)?;
+
+        let mut world = World::new();
+        world.set_max_depth(self.max_depth);
+        world.set_volume_steps(self.volume_steps);
+        world.set_seed(self.seed);
+        world.set_fog(self.fog.map(validated).transpose()?);
+        for v in self.volumes {
+            world.add_volume(Volume::new(v.boundary.into_renderable()?, validated(v.medium)?));
+        }
+        for l in self.lights {
+            let [x, y, z] = l.position;
+            world.add_light(Light::new(Coord::point(x, y, z), l.intensity)?);
+        }
+        for m in self.materials {
+            world.add_material(&m.name, m.material.into_material()?)?;
+        }
+        for obj in self.objects {
+            let renderable = obj.object.into_renderable()?;
+            let id = match obj.name {
+                Some(name) => world.add_named_obj(&name, renderable)?,
+                None => world.add_obj(renderable),
+            };
+            if let Some(name) = obj.shared_material {
+                world.assign_material(id, &name)?;
+            }
+        }
+        Ok((world, camera))
+    }
+}
+
+/// pretty printed JSON of the world and camera
+pub fn to_json(world: &World, camera: &Camera) -> Result<String> {
+    serde_json::to_string_pretty(&SceneDesc::from_scene(world, camera)?).map_err(|e| invalid(&e.to_string()))
+}
+
+pub fn from_json(json: &str) -> Result<(World, Camera)> {
+    let desc: SceneDesc = serde_json::from_str(json).map_err(|e| invalid(&e.to_string()))?;
+    desc.into_scene()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{medium::PhaseFunction, renderable::compare_renderables};
+
+    use super::*;
+
+    fn scene() -> (World, Camera) {
+        let mut w = World::default();
+        w.add_presets().unwrap();
+        w.add_light(Light::new(Coord::point(5.0, 10.0, -2.0), Color::new(0.5, 0.25, 0.125, 0.0)).unwrap());
+        w.set_max_depth(4);
+
+        let stripe = Pattern::new_stripe(Arc::new(Color::red()), Arc::new(Color::blue()), Matrix::scaling(0.25, 1.0, 1.0)).unwrap();
+        let checker = Pattern::new_checker(Arc::new(stripe), Arc::new(Color::gray()), Matrix::rotation_y(0.5)).unwrap();
+        let mut mat = Material::default();
+        mat.set_texture(Arc::new(checker));
+        mat.set_reflection(0.3).unwrap();
+        let spots = Pattern::new_checker(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::scaling(0.5, 0.5, 0.5)).unwrap();
+        mat.set_map(MaterialParam::Reflection, ScalarMap::new(Arc::new(spots), Channel::Luminance));
+        w.add_named_obj("floor", Arc::new(Plane::new(Matrix::translation(0.0, -1.0, 0.0), mat).unwrap())).unwrap();
+        let mut pane = Material::tinted_glass(Color::new(0.8, 0.9, 0.8, 0.0), 0.5).unwrap();
+        pane.set_thin_walled(true);
+        w.add_named_obj("glass", Arc::new(Cube::new(Matrix::identity(4).scale(0.5, 0.5, 0.05).translate(2.0, 0.0, 0.0), pane).unwrap())).unwrap();
+        w.add_obj(Arc::new(Cylinder::new(Matrix::identity(4), Material::default(), -1.0, 2.0, true).unwrap()));
+        w.add_obj(Arc::new(Cylinder::new(Matrix::translation(0.0, 0.0, 3.0), Material::metallic_roughness(Color::yellow(), 1.0, 0.3).unwrap(), -1.0, 1.0, false).unwrap()));
+        w.add_obj(Arc::new(Cylinder::default()));
+        w.add_obj(Arc::new(Heightfield::from_fn(Matrix::scaling(8.0, 1.0, 8.0), Material::default(), 4, 3, |x, z| x * z).unwrap()));
+        let mut dish = Quadric::paraboloid();
+        dish.set_transformation(Matrix::translation(-4.0, 0.0, -4.0)).unwrap();
+        dish.set_max(1.0);
+        dish.set_closed(true);
+        w.add_obj(Arc::new(dish));
+        w.add_obj(Arc::new(Quadric::hyperboloid_one_sheet()));
+        let blob = SdfNode::smooth_union(SdfNode::Sphere { radius: 1.0 }, SdfNode::Cuboid { half_extents: [0.5, 2.0, 0.5] }.twist(0.5), 0.3);
+        w.add_obj(Arc::new(Sdf::new(Matrix::translation(5.0, 1.0, 0.0), Material::default(), blob).unwrap()));
+        w.add_obj(Arc::new(Disk::new(Matrix::translation(0.0, 3.0, 0.0), Material::default()).unwrap()));
+        w.add_obj(Arc::new(Disk::annulus(Matrix::scaling(4.0, 1.0, 4.0), Material::default(), 0.75).unwrap()));
+        w.add_obj(Arc::new(Quad::from_corner(Coord::point(-1.0, 0.0, 4.0), Coord::vec(2.0, 0.0, 0.0), Coord::vec(0.0, 1.0, 0.0), Material::default()).unwrap()));
+        w.add_obj(Arc::new(SmoothTriangle::flat(Matrix::translation(0.0, 2.0, 6.0), Material::default(), SmoothTriangle::default().get_points()).unwrap()));
+        let mut patch = BezierPatch::default();
+        patch.set_transformation(Matrix::translation(3.0, 0.5, 3.0)).unwrap();
+        patch.set_resolution(4).unwrap();
+        patch.set_refine(false);
+        w.add_obj(Arc::new(patch));
+        let ring = w.add_named_obj("ring", Arc::new(Torus::new(Matrix::translation(0.0, 1.0, -2.0), Material::default(), 1.5, 0.3).unwrap())).unwrap();
+        w.assign_material(ring, "gold").unwrap();
+        w.assign_material(w.get_ids()[0], "gold").unwrap();
+        w.add_named_obj("neon", Arc::new(Cylinder::new(Matrix::translation(-3.0, 0.0, 2.0), Material::emissive(Color::new(1.0, 0.2, 0.6, 0.0), 3.0).unwrap(), 0.0, 2.0, true).unwrap())).unwrap();
+        let noise = Noise::new(Color::black(), Color::white(), 3, 42, Matrix::scaling(0.1, 0.1, 0.1)).unwrap();
+        let mut bumpy = Material::default();
+        bumpy.set_texture(Arc::new(noise.clone()));
+        bumpy.set_normal(Some(NormalPerturbation::bump(ScalarMap::new(Arc::new(noise), Channel::R), 0.2)));
+        let mut sphere = Sphere::new(Coord::point(-2.0, 0.0, 0.0)).unwrap();
+        sphere.set_material(bumpy);
+        w.add_obj(Arc::new(sphere));
+        let motion = Motion::linear(Matrix::identity(4), Matrix::translation(0.0, 1.0, 0.0)).unwrap();
+        w.add_named_obj("bouncing", Arc::new(Moving::new(Arc::new(Sphere::default()), motion))).unwrap();
+
+        let mut cam = Camera::new(40, 20, 1.2);
+        cam.set_transformation(Matrix::view_transformation(
+            Coord::point(0.0, 1.5, -5.0),
+            Coord::point(0.0, 1.0, 0.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        )).unwrap();
+        cam.set_shutter(0.0, 0.5).unwrap();
+        w.set_fog(Some(Medium::fog(0.01).unwrap()));
+        let smoke = Medium::new(Color::new(0.1, 0.1, 0.1, 0.0), Color::new(0.5, 0.4, 0.3, 0.0), PhaseFunction::HenyeyGreenstein { g: 0.3 }).unwrap();
+        w.add_volume(Volume::new(Arc::new(Sphere::new(Coord::point(0.0, 2.0, 0.0)).unwrap()), smoke));
+        w.set_volume_steps(4);
+        w.set_seed(99);
+        (w, cam)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (w, cam) = scene();
+        let json = to_json(&w, &cam).unwrap();
+        let (w2, cam2) = from_json(&json).unwrap();
+        assert_eq!(to_json(&w2, &cam2).unwrap(), json);
+
+        assert_eq!(cam2, cam);
+        assert_eq!(w2.get_max_depth(), 4);
+        assert_eq!(w2.get_light(), w.get_light());
+        assert_eq!(w2.get_fog(), w.get_fog());
+        assert_eq!(w2.get_volumes()[0].get_medium(), w.get_volumes()[0].get_medium());
+        assert_eq!(w2.get_volume_steps(), 4);
+        assert_eq!(w2.get_seed(), 99);
+        assert_eq!(w2.get_ids().len(), w.get_ids().len());
+        for (a, b) in w.get_ids().into_iter().zip(w2.get_ids()) {
+            let (obj_a, obj_b) = (w.get_obj(a).unwrap(), w2.get_obj(b).unwrap());
+            assert_eq!(obj_a.get_type(), obj_b.get_type());
+            assert_eq!(obj_a.get_transformation(), obj_b.get_transformation());
+            assert_eq!(obj_a.get_material(), obj_b.get_material());
+            assert_eq!(w.get_name(a), w2.get_name(b));
+        }
+        assert_eq!(w2.get_obj(w2.find_by_name("glass").unwrap()).unwrap().get_material().get_refractive_index(), 1.5);
+
+        // the library comes back and objects still share from it
+        assert_eq!(w2.get_material_names(), w.get_material_names());
+        let gold = w2.get_material("gold").unwrap();
+        assert!(Arc::ptr_eq(&w2.get_obj(w2.find_by_name("ring").unwrap()).unwrap().get_shared_material(), &gold));
+        assert!(Arc::ptr_eq(&w2.get_obj(w2.get_ids()[0]).unwrap().get_shared_material(), &gold));
+    }
+
+    #[test]
+    fn test_renders_the_same() {
+        let (w, cam) = scene();
+        let (w2, cam2) = from_json(&to_json(&w, &cam).unwrap()).unwrap();
+        assert_eq!(w2.render_world_multi(&cam2), w.render_world_multi(&cam));
+    }
+
+    #[test]
+    fn test_tagged_json() {
+        let json = r#"{
+            "camera": { "hsize": 10, "vsize": 10, "field_of_view": 1.0,
+                "transformation": [[1,0,0,0],[0,1,0,0],[0,0,1,0],[0,0,0,1]] },
+            "max_depth": 5,
+            "lights": [{ "position": [0, 10, 0], "intensity": { "r": 1, "g": 1, "b": 1, "a": 0 } }],
+            "objects": [{
+                "name": "ball",
+                "type": "sphere",
+                "transformation": [[1,0,0,0],[0,1,0,0],[0,0,1,0],[0,0,0,1]],
+                "material": { "ambient": 0.1, "diffuse": 0.9, "specular": 0.9, "shininess": 200,
+                    "reflection": 0, "refractive_index": 1, "transparency": 0,
+                    "texture": { "type": "color", "color": { "r": 1, "g": 0, "b": 0, "a": 0 } } }
+            }]
+        }"#;
+        let (w, _) = from_json(json).unwrap();
+        let ball = w.get_obj(w.find_by_name("ball").unwrap()).unwrap();
+        let mut expected = Sphere::default();
+        let mut mat = Material::default();
+        mat.set_color(Color::red());
+        expected.set_material(mat);
+        compare_renderables(ball.as_ref(), &expected);
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let (w, cam) = scene();
+        let json = to_json(&w, &cam).unwrap().replacen("\"ambient\": 0.1", "\"ambient\": -0.1", 1);
+        assert_eq!(from_json(&json).err(), Some(RtcError::NegativeValue { field: "ambient", value: -0.1 }));
+        assert!(matches!(from_json("{}"), Err(RtcError::InvalidScene(_))));
+        let json = to_json(&w, &cam).unwrap().replacen("\"g\": 0.3", "\"g\": 1.5", 1);
+        assert_eq!(from_json(&json).err(), Some(RtcError::OutOfRange { field: "g", value: 1.5, min: -0.99, max: 0.99 }));
+    }
+
+    #[test]
+    fn test_custom_sdf_is_rejected() {
+        let (mut w, cam) = scene();
+        let slab = SdfNode::custom(|p| p.get_y().abs() - 0.5);
+        w.add_obj(Arc::new(Sdf::new(Matrix::identity(4), Material::default(), slab).unwrap()));
+        assert!(matches!(to_json(&w, &cam), Err(RtcError::InvalidScene(_))));
+    }
+}
@@ -1,9 +1,13 @@
 pub mod color;
+pub mod image;
+pub mod noise;
+pub mod normal;
 pub mod pattern;
+pub mod scalar;
 
 use std::{any::Any, fmt, ops::{Add, Mul}, sync::Arc};
 
-use crate::{coord::Coord, matrix::Matrix};
+use crate::{coord::Coord, error::Result, matrix::Matrix};
 use color::Color;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -21,7 +25,7 @@ pub trait Tex: fmt::Debug + Send + Sync {
     fn compare(&self, other: Arc<dyn Tex>) -> bool;
     fn as_any(&self) -> &dyn Any;
     fn get_transformation(&self) -> Matrix;
-    fn set_transformation(&mut self, mat: Matrix);
+    fn set_transformation(&mut self, mat: Matrix) -> Result<()>;
 }
 
 impl Mul<Color> for Arc<dyn Tex> {
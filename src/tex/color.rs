@@ -5,6 +5,7 @@ use crate::impl_getters_setters;
 use crate::{coord::Coord, matrix::Matrix, tex::Tex};
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     r: f32, // red
     g: f32, // green
@@ -156,8 +157,8 @@ impl Tex for Color {
     } 
 
     /// dose not make sense to call give a transformation dose not effect a solid color
-    fn set_transformation(&mut self, _mat: Matrix) {
-    
+    fn set_transformation(&mut self, _mat: Matrix) -> crate::error::Result<()> {
+        Ok(())
     }
 }
 
@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::{canvas::Canvas, coord::Coord, matrix::Matrix, tex::{Tex, TextureType, color::Color}};
+
+/// a picture looked up by texture coords, x of the pos is u and y is v, both wrapping around [0, 1]
+///
+/// (0, 0) is the bottom left of the image, and colors are bilinearly filtered between pixels.
+/// made for lookups by uv (like `NormalPerturbation::TangentSpace`); used as a material texture it
+/// is projected along z in object space
+#[derive(Debug, Clone)]
+pub struct Image {
+    canvas: Arc<Canvas>,
+    transformation: Matrix,
+    inverse: Matrix,
+}
+
+#[allow(dead_code)]
+impl Image {
+    pub fn new(canvas: Canvas) -> Self {
+        Self { canvas: Arc::new(canvas), transformation: Matrix::identity(4), inverse: Matrix::identity(4) }
+    }
+
+    /// reads a ppm file, see `Canvas::from_ppm`
+    pub fn from_ppm(filename: &str) -> Result<Self> {
+        Ok(Self::new(Canvas::from_ppm(filename)?))
+    }
+
+    pub fn get_canvas(&self) -> Arc<Canvas> {
+        self.canvas.clone()
+    }
+
+    /// filtered color at texture coords (u, v)
+    pub fn color_at_uv(&self, u: f32, v: f32) -> Color {
+        let (width, height) = (self.canvas.get_width(), self.canvas.get_height());
+        if width == 0 || height == 0 {
+            return Color::black();
+        }
+        // pixel centers sit at half pixel offsets
+        let x = u.rem_euclid(1.0) * width as f32 - 0.5;
+        let y = (1.0 - v.rem_euclid(1.0)) * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let px = |x: f32, y: f32| {
+            let x = (x as isize).rem_euclid(width as isize) as usize;
+            let y = (y as isize).rem_euclid(height as isize) as usize;
+            self.canvas.get_pixel(x, y)
+        };
+        let top = px(x0, y0) * (1.0 - fx) + px(x0 + 1.0, y0) * fx;
+        let bottom = px(x0, y0 + 1.0) * (1.0 - fx) + px(x0 + 1.0, y0 + 1.0) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn map_pixels(&self, f: impl Fn(Color) -> Color) -> Self {
+        let mut canvas = Canvas::new(self.canvas.get_width(), self.canvas.get_height());
+        for y in 0..canvas.get_height() {
+            for x in 0..canvas.get_width() {
+                canvas.set_pixel(x, y, f(self.canvas.get_pixel(x, y)));
+            }
+        }
+        Self { canvas: Arc::new(canvas), ..self.clone() }
+    }
+}
+
+impl Tex for Image {
+    fn get_color_at(&self, pos: Coord) -> Color {
+        let pos = self.inverse.clone() * pos;
+        self.color_at_uv(pos.get_x(), pos.get_y())
+    }
+
+    fn mul_helper_color(&self, rhs: Color) -> Arc<dyn Tex> {
+        Arc::new(self.map_pixels(|c| c * rhs))
+    }
+
+    fn add_helper(&self, rhs: Color) -> Arc<dyn Tex> {
+        Arc::new(self.map_pixels(|c| c + rhs))
+    }
+
+    fn get_texture_type(&self) -> TextureType {
+        TextureType::Texture
+    }
+
+    fn compare(&self, other: Arc<dyn Tex>) -> bool {
+        match other.as_any().downcast_ref::<Image>() {
+            Some(p) => self == p,
+            None => false
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_transformation(&self) -> Matrix {
+        self.transformation.clone()
+    }
+
+    fn set_transformation(&mut self, mat: Matrix) -> Result<()> {
+        self.inverse = mat.transformation_inverse()?;
+        self.transformation = mat;
+        Ok(())
+    }
+}
+
+impl PartialEq for Image {
+    fn eq(&self, other: &Self) -> bool {
+        self.transformation == other.transformation && self.canvas == other.canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Image {
+        // red green on the top row, blue white on the bottom
+        let mut c = Canvas::new(2, 2);
+        c.set_pixel(0, 0, Color::red());
+        c.set_pixel(1, 0, Color::green());
+        c.set_pixel(0, 1, Color::blue());
+        c.set_pixel(1, 1, Color::white());
+        Image::new(c)
+    }
+
+    #[test]
+    fn test_pixel_centers() {
+        let img = image();
+        assert_eq!(img.color_at_uv(0.25, 0.75), Color::red());
+        assert_eq!(img.color_at_uv(0.75, 0.75), Color::green());
+        assert_eq!(img.color_at_uv(0.25, 0.25), Color::blue());
+        assert_eq!(img.color_at_uv(0.75, 0.25), Color::white());
+    }
+
+    #[test]
+    fn test_filtering_and_wrapping() {
+        let img = image();
+        assert_eq!(img.color_at_uv(0.5, 0.75), Color::new(0.5, 0.5, 0.0, 0.0));
+        assert_eq!(img.color_at_uv(1.25, -0.25), Color::red());
+        assert_eq!(img.get_color_at(Coord::point(0.25, 0.25, 3.0)), Color::blue());
+    }
+
+    #[test]
+    fn test_mul() {
+        let img: Arc<dyn Tex> = Arc::new(image());
+        let dark = img * Color::new(0.5, 0.5, 0.5, 0.0);
+        assert_eq!(dark.get_color_at(Coord::point(0.75, 0.25, 0.0)), Color::new(0.5, 0.5, 0.5, 0.0));
+    }
+}
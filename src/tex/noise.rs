@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::{coord::Coord, matrix::Matrix, tex::{Tex, TextureType, color::Color}};
+
+/// fractal Perlin noise blending between two colors, a at 0 and b at 1
+///
+/// the same seed always gives the same noise, each octave doubles the frequency and halves the
+/// amplitude of the one before
+#[derive(Debug, Clone)]
+pub struct Noise {
+    color_a: Color,
+    color_b: Color,
+    octaves: usize,
+    seed: u64,
+    perm: Arc<Vec<u8>>,  // shuffled 0..256, repeated so lookups can run past 255
+    transformation: Matrix,
+    inverse: Matrix,
+}
+
+#[allow(dead_code)]
+impl Noise {
+    /// returns an error if the transformation can't be inverted
+    pub fn new(color_a: Color, color_b: Color, octaves: usize, seed: u64, transformation: Matrix) -> Result<Self> {
+        Ok(Self {
+            color_a,
+            color_b,
+            octaves: octaves.max(1),
+            seed,
+            perm: Arc::new(permutation(seed)),
+            inverse: transformation.transformation_inverse()?,
+            transformation,
+        })
+    }
+
+    pub fn get_color_a(&self) -> Color {
+        self.color_a
+    }
+
+    pub fn get_color_b(&self) -> Color {
+        self.color_b
+    }
+
+    pub fn get_octaves(&self) -> usize {
+        self.octaves
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// noise value in [0, 1] at a pos in the noise's own space
+    pub fn value_at(&self, pos: Coord) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut total = 0.0;
+        for _ in 0..self.octaves {
+            sum += self.perlin(pos.get_x() * frequency, pos.get_y() * frequency, pos.get_z() * frequency) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        (0.5 + 0.5 * sum / total).clamp(0.0, 1.0)
+    }
+
+    /// Ken Perlin's improved noise, roughly in [-1, 1]
+    fn perlin(&self, x: f32, y: f32, z: f32) -> f32 {
+        let p = &self.perm;
+        let (xi, yi, zi) = (x.floor() as i32 & 255, y.floor() as i32 & 255, z.floor() as i32 & 255);
+        let (x, y, z) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let (xi, yi, zi) = (xi as usize, yi as usize, zi as usize);
+
+        let a = p[xi] as usize + yi;
+        let aa = p[a] as usize + zi;
+        let ab = p[a + 1] as usize + zi;
+        let b = p[xi + 1] as usize + yi;
+        let ba = p[b] as usize + zi;
+        let bb = p[b + 1] as usize + zi;
+
+        lerp(w,
+            lerp(v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(u, grad(p[ab], x, y - 1.0, z), grad(p[bb], x - 1.0, y - 1.0, z))),
+            lerp(v,
+                lerp(u, grad(p[aa + 1], x, y, z - 1.0), grad(p[ba + 1], x - 1.0, y, z - 1.0)),
+                lerp(u, grad(p[ab + 1], x, y - 1.0, z - 1.0), grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0))))
+    }
+}
+
+/// 0..256 shuffled by a small xorshift generator seeded with `seed`, then repeated once
+fn permutation(seed: u64) -> Vec<u8> {
+    let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut perm: Vec<u8> = (0..=255).collect();
+    for i in (1..256).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        perm.swap(i, j);
+    }
+    perm.extend_from_within(..);
+    perm
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// dot product of (x, y, z) with one of 12 edge directions of a cube, picked by the hash
+fn grad(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+impl Tex for Noise {
+    fn get_color_at(&self, pos: Coord) -> Color {
+        let f = self.value_at(self.inverse.clone() * pos);
+        self.color_a + (self.color_b - self.color_a) * f
+    }
+
+    fn mul_helper_color(&self, rhs: Color) -> Arc<dyn Tex> {
+        Arc::new(Self { color_a: self.color_a * rhs, color_b: self.color_b * rhs, ..self.clone() })
+    }
+
+    fn add_helper(&self, rhs: Color) -> Arc<dyn Tex> {
+        Arc::new(Self { color_a: self.color_a + rhs, color_b: self.color_b + rhs, ..self.clone() })
+    }
+
+    fn get_texture_type(&self) -> TextureType {
+        TextureType::Texture
+    }
+
+    fn compare(&self, other: Arc<dyn Tex>) -> bool {
+        match other.as_any().downcast_ref::<Noise>() {
+            Some(p) => self == p,
+            None => false
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_transformation(&self) -> Matrix {
+        self.transformation.clone()
+    }
+
+    fn set_transformation(&mut self, mat: Matrix) -> Result<()> {
+        self.inverse = mat.transformation_inverse()?;
+        self.transformation = mat;
+        Ok(())
+    }
+}
+
+impl PartialEq for Noise {
+    fn eq(&self, other: &Self) -> bool {
+        self.color_a == other.color_a
+            && self.color_b == other.color_b
+            && self.octaves == other.octaves
+            && self.seed == other.seed
+            && self.transformation == other.transformation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(seed: u64) -> Noise {
+        Noise::new(Color::black(), Color::white(), 3, seed, Matrix::identity(4)).unwrap()
+    }
+
+    #[test]
+    fn test_permutation() {
+        let p = permutation(7);
+        assert_eq!(p.len(), 512);
+        let mut sorted = p[..256].to_vec();
+        sorted.sort();
+        assert_eq!(sorted, (0..=255).collect::<Vec<u8>>());
+        assert_eq!(p[..256], p[256..]);
+        assert_ne!(permutation(7), permutation(8));
+    }
+
+    #[test]
+    fn test_deterministic_and_in_range() {
+        let (a, b) = (noise(1), noise(1));
+        for i in 0..50 {
+            let pos = Coord::point(i as f32 * 0.37, i as f32 * -0.21, i as f32 * 0.13);
+            let val = a.value_at(pos);
+            assert!((0.0..=1.0).contains(&val));
+            assert_eq!(val, b.value_at(pos));
+        }
+    }
+
+    #[test]
+    fn test_lattice_points_are_mid_gray() {
+        // perlin noise is 0 on integer lattice points, so every octave is too
+        assert_eq!(noise(3).value_at(Coord::point(2.0, -1.0, 5.0)), 0.5);
+    }
+
+    #[test]
+    fn test_varies_and_is_continuous() {
+        let n = noise(5);
+        let a = n.value_at(Coord::point(0.3, 0.6, 0.2));
+        let b = n.value_at(Coord::point(0.3001, 0.6, 0.2));
+        assert!((a - b).abs() < 0.01);
+        let values: Vec<f32> = (0..20).map(|i| n.value_at(Coord::point(i as f32 * 0.45 + 0.1, 0.3, 0.7))).collect();
+        assert!(values.iter().any(|v| (v - values[0]).abs() > 0.05));
+    }
+
+    #[test]
+    fn test_colors() {
+        let n = Noise::new(Color::red(), Color::blue(), 1, 0, Matrix::identity(4)).unwrap();
+        assert_eq!(n.get_color_at(Coord::point(1.0, 1.0, 1.0)), Color::new(0.5, 0.0, 0.5, 0.0));
+        let doubled = Arc::new(n.clone()) as Arc<dyn Tex> * Color::new(2.0, 2.0, 2.0, 0.0);
+        assert_eq!(doubled.get_color_at(Coord::point(1.0, 1.0, 1.0)), Color::new(1.0, 0.0, 1.0, 0.0));
+        assert!(n.compare(Arc::new(n.clone())));
+        assert!(!n.compare(Arc::new(noise(0))));
+    }
+}
@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use crate::{coord::Coord, renderable::Renderable};
+
+use super::{Tex, scalar::ScalarMap};
+
+/// step used for finite differences, in world units
+const EPSILON: f32 = 0.001;
+
+/// tilts the surface normal of a material to fake small bumps without extra geometry
+///
+/// the tilted normal is used for lighting, reflection and refraction, while the geometric normal
+/// still decides which side of the surface a ray is on and where shadow and secondary rays start
+#[derive(Debug, Clone)]
+pub enum NormalPerturbation {
+    /// height field read from a scalar map (a `Noise` works well), the normal leans away from
+    /// the direction the height grows in. the map is looked up in object space like textures
+    Bump { map: ScalarMap, strength: f32 },
+    /// tangent-space normal map, usually an `Image`, looked up at the object's uv coords
+    ///
+    /// red is along the direction u grows in, green along v and blue along the normal, each
+    /// mapped from [0, 1] to [-1, 1], so a flat map is (0.5, 0.5, 1). `strength` scales the tilt
+    TangentSpace { tex: Arc<dyn Tex>, strength: f32 },
+}
+
+#[allow(dead_code)]
+impl NormalPerturbation {
+    pub fn bump(map: ScalarMap, strength: f32) -> Self {
+        Self::Bump { map, strength }
+    }
+
+    pub fn tangent_space(tex: Arc<dyn Tex>, strength: f32) -> Self {
+        Self::TangentSpace { tex, strength }
+    }
+
+    pub fn get_strength(&self) -> f32 {
+        match self {
+            Self::Bump { strength, .. } | Self::TangentSpace { strength, .. } => *strength,
+        }
+    }
+
+    /// the tilted version of `normalv`, the world space normal of `object` at world space `pos`
+    pub fn apply(&self, object: &dyn Renderable, pos: Coord, normalv: Coord) -> Coord {
+        let perturbed = match self {
+            Self::Bump { map, strength } => {
+                let height = |p: Coord| map.value_at(object.get_inverse() * p);
+                let diff = |d: Coord| (height(pos + d) - height(pos - d)) / (2.0 * EPSILON);
+                let gradient = Coord::vec(
+                    diff(Coord::vec(EPSILON, 0.0, 0.0)),
+                    diff(Coord::vec(0.0, EPSILON, 0.0)),
+                    diff(Coord::vec(0.0, 0.0, EPSILON)),
+                );
+                // only the part of the gradient along the surface tilts the normal
+                let surface_gradient = gradient - normalv * gradient.dot(normalv);
+                normalv - surface_gradient * *strength
+            }
+            Self::TangentSpace { tex, strength } => {
+                let (u, v) = object.uv_at(pos);
+                let (tangent, bitangent) = tangent_frame(object, pos, normalv);
+                let c = tex.get_color_at(Coord::point(u, v, 0.0));
+                let x = (c.get_r() * 2.0 - 1.0) * strength;
+                let y = (c.get_g() * 2.0 - 1.0) * strength;
+                let z = c.get_b() * 2.0 - 1.0;
+                tangent * x + bitangent * y + normalv * z
+            }
+        };
+        if perturbed.magnitude() < f32::EPSILON {
+            return normalv;
+        }
+        perturbed.normalized()
+    }
+}
+
+/// two directions along the surface, following u and v, found from how uv changes around `pos`
+fn tangent_frame(object: &dyn Renderable, pos: Coord, normalv: Coord) -> (Coord, Coord) {
+    // any two directions across the surface to sample uv along
+    let helper = if normalv.get_x().abs() < 0.9 { Coord::vec(1.0, 0.0, 0.0) } else { Coord::vec(0.0, 1.0, 0.0) };
+    let s = helper.cross(&normalv).normalized();
+    let t = normalv.cross(&s);
+
+    // uv wraps around at seams, so a jump of more than half is really a small step the other way
+    let wrap = |d: f32| d - d.round();
+    let diff = |d: Coord| {
+        let (u0, v0) = object.uv_at(pos - d * EPSILON);
+        let (u1, v1) = object.uv_at(pos + d * EPSILON);
+        (wrap(u1 - u0), wrap(v1 - v0))
+    };
+    let (du_s, dv_s) = diff(s);
+    let (du_t, dv_t) = diff(t);
+    let grad_u = s * du_s + t * du_t;
+    let grad_v = s * dv_s + t * dv_t;
+
+    let tangent = if grad_u.magnitude() > f32::EPSILON { grad_u.normalized() } else { s };
+    let bitangent = normalv.cross(&tangent);
+    if bitangent.dot(grad_v) < 0.0 {
+        (tangent, -bitangent)
+    } else {
+        (tangent, bitangent)
+    }
+}
+
+impl PartialEq for NormalPerturbation {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Bump { map: a, strength: sa }, Self::Bump { map: b, strength: sb }) => a == b && sa == sb,
+            (Self::TangentSpace { tex: a, strength: sa }, Self::TangentSpace { tex: b, strength: sb }) => {
+                sa == sb && a.compare(b.clone())
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{canvas::Canvas, matrix::Matrix, primitives::{plane::Plane, sphere::Sphere}, tex::{color::Color, image::Image, noise::Noise, pattern::Pattern, scalar::Channel}};
+
+    use super::*;
+
+    fn close(a: Coord, b: Coord) -> bool {
+        (a - b).magnitude() < 0.001
+    }
+
+    #[test]
+    fn test_flat_maps_keep_normal() {
+        let plane = Plane::default();
+        let up = Coord::vec(0.0, 1.0, 0.0);
+        let p = Coord::point(0.3, 0.0, 0.7);
+        let bump = NormalPerturbation::bump(ScalarMap::new(Arc::new(Color::white()), Channel::R), 1.0);
+        assert!(close(bump.apply(&plane, p, up), up));
+        let flat = NormalPerturbation::tangent_space(Arc::new(Color::new(0.5, 0.5, 1.0, 0.0)), 1.0);
+        assert!(close(flat.apply(&plane, p, up), up));
+    }
+
+    #[test]
+    fn test_bump_leans_downhill() {
+        // height grows along x, so the normal tilts towards -x
+        let ramp = Pattern::new_gradient(Arc::new(Color::black()), Arc::new(Color::white()), Matrix::identity(4)).unwrap();
+        let bump = NormalPerturbation::bump(ScalarMap::new(Arc::new(ramp), Channel::R), 1.0);
+        let n = bump.apply(&Plane::default(), Coord::point(0.5, 0.0, 0.5), Coord::vec(0.0, 1.0, 0.0));
+        assert!(close(n, Coord::vec(-1.0, 1.0, 0.0).normalized()), "{n:?}");
+    }
+
+    #[test]
+    fn test_bump_noise_is_unit_and_facing_out() {
+        let noise = Noise::new(Color::black(), Color::white(), 4, 2, Matrix::scaling(0.2, 0.2, 0.2)).unwrap();
+        let bump = NormalPerturbation::bump(ScalarMap::new(Arc::new(noise), Channel::R), 0.05);
+        let sphere = Sphere::default();
+        for i in 0..20 {
+            let p = Coord::point((i as f32 * 0.3).cos(), 0.0, (i as f32 * 0.3).sin());
+            let n = sphere.normal_at(p);
+            let tilted = bump.apply(&sphere, p, n);
+            assert!((tilted.magnitude() - 1.0).abs() < 0.0001);
+            assert!(tilted.dot(n) > 0.5);
+        }
+    }
+
+    #[test]
+    fn test_tangent_space_follows_uv() {
+        // on a plane u follows x and v follows z
+        let plane = Plane::default();
+        let up = Coord::vec(0.0, 1.0, 0.0);
+        let p = Coord::point(0.3, 0.0, 0.7);
+        let towards_u = NormalPerturbation::tangent_space(Arc::new(Color::new(1.0, 0.5, 0.5, 0.0)), 1.0);
+        assert!(close(towards_u.apply(&plane, p, up), Coord::vec(1.0, 0.0, 0.0)));
+        let towards_v = NormalPerturbation::tangent_space(Arc::new(Color::new(0.5, 1.0, 0.5, 0.0)), 1.0);
+        assert!(close(towards_v.apply(&plane, p, up), Coord::vec(0.0, 0.0, 1.0)));
+        // at the seam of the texture the frame stays the same
+        assert!(close(towards_u.apply(&plane, Coord::point(1.0, 0.0, 1.0), up), Coord::vec(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_tangent_space_strength_and_image() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.set_pixel(0, 0, Color::new(1.0, 0.5, 1.0, 0.0));
+        let image = Image::new(canvas);
+        let plane = Plane::default();
+        let up = Coord::vec(0.0, 1.0, 0.0);
+        let full = NormalPerturbation::tangent_space(Arc::new(image.clone()), 1.0);
+        assert!(close(full.apply(&plane, Coord::point(0.2, 0.0, 0.2), up), Coord::vec(1.0, 1.0, 0.0).normalized()));
+        let none = NormalPerturbation::tangent_space(Arc::new(image), 0.0);
+        assert!(close(none.apply(&plane, Coord::point(0.2, 0.0, 0.2), up), up));
+    }
+
+    #[test]
+    fn test_eq() {
+        let map = ScalarMap::new(Arc::new(Color::white()), Channel::R);
+        assert_eq!(NormalPerturbation::bump(map.clone(), 0.5), NormalPerturbation::bump(map.clone(), 0.5));
+        assert_ne!(NormalPerturbation::bump(map.clone(), 0.5), NormalPerturbation::bump(map, 1.0));
+        assert_ne!(
+            NormalPerturbation::tangent_space(Arc::new(Color::white()), 0.5),
+            NormalPerturbation::tangent_space(Arc::new(Color::black()), 0.5)
+        );
+    }
+}
@@ -1,10 +1,14 @@
 use std::sync::Arc;
 
+use rand::{RngExt, SeedableRng};
+
 use crate::impl_getters_setters;
-use crate::{coord::Coord, matrix::Matrix, tex::{Tex, color::Color}};
+use crate::error::Result;
+use crate::{coord::Coord, matrix::Matrix, sampling::SampleRng, tex::{Tex, color::Color}};
 
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
 pub enum PatternType {
     Stripe, 
     Gradient,
@@ -21,7 +25,8 @@ pub struct Pattern {
     pattern_type: PatternType,
     color_a: Arc<dyn Tex>,
     color_b: Arc<dyn Tex>,
-    transformation: Matrix
+    transformation: Matrix,
+    inverse: Matrix
 }
 
 impl_getters_setters!(
@@ -33,50 +38,57 @@ impl_getters_setters!(
 
 #[allow(dead_code)]
 impl Pattern {
-    pub fn new(pattern_type: PatternType, color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Self {
-        Self { pattern_type, color_a, color_b, transformation }
+    /// returns an error if the transformation can't be inverted
+    pub fn new(pattern_type: PatternType, color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Result<Self> {
+        Ok(Self { pattern_type, color_a, color_b, inverse: transformation.transformation_inverse()?, transformation })
+    }
+
+    /// pattern with an identity transformation, which can't fail to invert
+    fn untransformed(pattern_type: PatternType, color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>) -> Self {
+        Self { pattern_type, color_a, color_b, transformation: Matrix::identity(4), inverse: Matrix::identity(4) }
     }
 
     pub fn debug_pattern() -> Self {
-        Self::new_checker(Arc::new(Color::purple()), Arc::new(Color::black()), Matrix::identity(4))
+        Self::untransformed(PatternType::Checker, Arc::new(Color::purple()), Arc::new(Color::black()))
     }
 
-    pub fn new_stripe(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Self {
-        Self { pattern_type: PatternType::Stripe, color_a, color_b, transformation }
+    pub fn new_stripe(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Result<Self> {
+        Self::new(PatternType::Stripe, color_a, color_b, transformation)
     }
 
-    pub fn new_gradient(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Self {
-        Self { pattern_type: PatternType::Gradient, color_a, color_b, transformation }
+    pub fn new_gradient(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Result<Self> {
+        Self::new(PatternType::Gradient, color_a, color_b, transformation)
     }
 
-    pub fn new_checker(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Self {
-        Self { pattern_type: PatternType::Checker, color_a, color_b, transformation }
+    pub fn new_checker(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Result<Self> {
+        Self::new(PatternType::Checker, color_a, color_b, transformation)
     }
 
-    pub fn new_bullseye(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Self {
-        Self { pattern_type: PatternType::Bullseye, color_a, color_b, transformation }
+    pub fn new_bullseye(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Result<Self> {
+        Self::new(PatternType::Bullseye, color_a, color_b, transformation)
     }
 
-    pub fn test_pattern(transformation: Matrix) -> Self {
-        Self { pattern_type: PatternType::Test, color_a: Arc::new(Color::purple()), color_b: Arc::new(Color::black()), transformation }
+    pub fn test_pattern(transformation: Matrix) -> Result<Self> {
+        Self::new(PatternType::Test, Arc::new(Color::purple()), Arc::new(Color::black()), transformation)
     }
     
-    pub fn new_solid(color_a: Arc<dyn Tex>, transformation: Matrix) -> Self {
-        Self { pattern_type: PatternType::Solid, color_a, color_b: Arc::new(Color::white()), transformation }
+    pub fn new_solid(color_a: Arc<dyn Tex>, transformation: Matrix) -> Result<Self> {
+        Self::new(PatternType::Solid, color_a, Arc::new(Color::white()), transformation)
     }  
 
-    /// adds organic jitter to a subpattern
+    /// adds organic jitter to a subpattern, the same seed always gives the same jitter
     /// color b is used to store the rng val, so do not use
-    pub fn new_perturbed(color_a: Arc<dyn Tex>) -> Self {
-        Self { pattern_type: PatternType::Perturbed, 
+    pub fn new_perturbed(color_a: Arc<dyn Tex>, seed: u64) -> Self {
+        Self::untransformed(
+            PatternType::Perturbed, 
             color_a, 
-            color_b: Arc::new(Color::new(rand::random_range(0.0..=1.0), 0.0, 0.0, 0.0)), 
-            transformation: Matrix::identity(4) }
+            Arc::new(Color::new(SampleRng::seed_from_u64(seed).random_range(0.0..=1.0), 0.0, 0.0, 0.0))
+        )
     }
 
     /// blends 2 sub patterns for every pixel by summing them
-    pub fn new_blended(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Self {
-        Self { pattern_type: PatternType::Blended, color_a, color_b, transformation }
+    pub fn new_blended(color_a: Arc<dyn Tex>, color_b: Arc<dyn Tex>, transformation: Matrix) -> Result<Self> {
+        Self::new(PatternType::Blended, color_a, color_b, transformation)
     }
 
     fn stripe_at(&self, pos: Coord) -> Color {
@@ -138,7 +150,7 @@ impl Pattern {
 impl Tex for Pattern {
 
     fn get_color_at(&self, pos: Coord) -> Color {
-        let local_pos = self.get_transformation().inverse().unwrap() * pos;
+        let local_pos = self.inverse.clone() * pos;
         match self.get_pattern_type() {
             PatternType::Solid => self.get_color_a().get_color_at(local_pos),
             PatternType::Stripe => self.stripe_at(local_pos),
@@ -156,7 +168,8 @@ impl Tex for Pattern {
             color_a: self.get_color_a() * rhs,
             color_b: self.get_color_b() * rhs,
             pattern_type: self.get_pattern_type(),
-            transformation: self.get_transformation()
+            transformation: self.get_transformation(),
+            inverse: self.inverse.clone()
         })
     }
 
@@ -165,7 +178,8 @@ impl Tex for Pattern {
             color_a: self.get_color_a() + rhs,
             color_b: self.get_color_b() + rhs,
             pattern_type: self.get_pattern_type(),
-            transformation: self.get_transformation()
+            transformation: self.get_transformation(),
+            inverse: self.inverse.clone()
         })
     }
 
@@ -188,8 +202,10 @@ impl Tex for Pattern {
         self.transformation.clone()
     }
 
-    fn set_transformation(&mut self, mat: Matrix) {
+    fn set_transformation(&mut self, mat: Matrix) -> Result<()> {
+        self.inverse = mat.transformation_inverse()?;
         self.transformation = mat;
+        Ok(())
     }
 }
 
@@ -214,14 +230,21 @@ mod test {
 
     #[test]
     fn test_new() {
-        let p = Pattern::new_stripe(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_stripe(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.get_color_a().get_color_at(Coord::point(0.0, 0.0, 0.0)), Color::white());
         assert_eq!(p.get_color_b().get_color_at(Coord::point(0.0, 0.0, 0.0)), Color::black());
     }
 
+    #[test]
+    fn test_perturbed_seed() {
+        let a = Pattern::new_perturbed(Arc::new(Color::white()), 7);
+        assert!(a == Pattern::new_perturbed(Arc::new(Color::white()), 7));
+        assert!(a != Pattern::new_perturbed(Arc::new(Color::white()), 8));
+    }
+
     #[test]
     fn test_stripe_y() {
-        let p = Pattern::new_stripe(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_stripe(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.stripe_at(Coord::point(0.0, 0.0, 0.0)), Color::white());
         assert_eq!(p.stripe_at(Coord::point(0.0, 1.0, 0.0)), Color::white());
         assert_eq!(p.stripe_at(Coord::point(0.0, 2.0, 0.0)), Color::white());
@@ -229,7 +252,7 @@ mod test {
 
     #[test]
     fn test_stripe_z() {
-        let p = Pattern::new_stripe(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_stripe(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.stripe_at(Coord::point(0.0, 0.0, 0.0)), Color::white());
         assert_eq!(p.stripe_at(Coord::point(0.0, 0.0, 1.0)), Color::white());
         assert_eq!(p.stripe_at(Coord::point(0.0, 0.0, 2.0)), Color::white());
@@ -237,7 +260,7 @@ mod test {
 
     #[test]
     fn test_stripe_x() {
-        let p = Pattern::new_stripe(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_stripe(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.stripe_at(Coord::point(0.0, 0.0, 0.0)), Color::white());
         assert_eq!(p.stripe_at(Coord::point(0.9, 0.0, 0.0)), Color::white());
         assert_eq!(p.stripe_at(Coord::point(1.0, 0.0, 0.0)), Color::black());
@@ -248,7 +271,7 @@ mod test {
 
     #[test]
     fn test_gradient() {
-        let p = Pattern::new_gradient(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_gradient(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.0, 0.0)), Color::white());
         assert_eq!(p.get_color_at(Coord::point(0.25, 0.0, 0.0)), Color::new(0.75, 0.75, 0.75, 0.0));
         assert_eq!(p.get_color_at(Coord::point(0.5, 0.0, 0.0)), Color::new(0.5, 0.5, 0.5, 0.0));
@@ -258,7 +281,7 @@ mod test {
 
     #[test]
     fn test_bullseye() {
-        let p = Pattern::new_bullseye(Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_bullseye(Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.0, 0.0)), Color::red());
         assert_eq!(p.get_color_at(Coord::point(1.0, 0.0, 0.0)), Color::black());
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.0, 1.0)), Color::black());
@@ -267,7 +290,7 @@ mod test {
 
     #[test]
     fn test_checker_x() {
-        let p = Pattern::new_checker(Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_checker(Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.0, 0.0)), Color::red());
         assert_eq!(p.get_color_at(Coord::point(0.99, 0.0, 0.0)), Color::red());
         assert_eq!(p.get_color_at(Coord::point(1.1, 0.0, 0.0)), Color::black());
@@ -275,7 +298,7 @@ mod test {
 
     #[test]
     fn test_checker_y() {
-        let p = Pattern::new_checker(Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_checker(Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.0, 0.0)), Color::red());
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.99, 0.0)), Color::red());
         assert_eq!(p.get_color_at(Coord::point(0.0, 1.1, 0.0)), Color::black());
@@ -283,7 +306,7 @@ mod test {
 
     #[test]
     fn test_checker_z() {
-        let p = Pattern::new_checker(Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4));
+        let p = Pattern::new_checker(Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.0, 0.0)), Color::red());
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.0, 0.99)), Color::red());
         assert_eq!(p.get_color_at(Coord::point(0.0, 0.0, 1.1)), Color::black());
@@ -296,9 +319,9 @@ mod test {
     #[test_case(Solid,    Color::red()                    ; "solid")]
     fn test_obj_transformed(pattern: PatternType, expected: Color) {
         let mut o = Sphere::default();
-        o.set_transformation(Matrix::scaling(2.0, 2.0, 2.0));
-        let p = Pattern::new(pattern, Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4));
-        let m = Material::new(1.0, 0.0, 0.0, 10.0, 0.0, 1.0, 0.0, Arc::new(p));
+        o.set_transformation(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
+        let p = Pattern::new(pattern, Arc::new(Color::red()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
+        let m = Material::new(1.0, 0.0, 0.0, 10.0, 0.0, 1.0, 0.0, Arc::new(p)).unwrap();
         o.set_material(m);
         
         let c = o.get_color_at(Coord::point(1.5, 2.5, 0.0));
@@ -311,8 +334,8 @@ mod test {
     #[test_case(Bullseye, Color::red()                    ; "bullseye")]
     #[test_case(Solid,    Color::red()                    ; "solid")]
     fn test_stripe_pattern_transformed(pattern: PatternType, expected: Color) {
-        let p = Pattern::new(pattern, Arc::new(Color::red()), Arc::new(Color::black()), Matrix::scaling(2.0, 2.0, 2.0));
-        let m = Material::new(1.0, 0.0, 0.0, 10.0, 0.0, 1.0, 0.0, Arc::new(p));
+        let p = Pattern::new(pattern, Arc::new(Color::red()), Arc::new(Color::black()), Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
+        let m = Material::new(1.0, 0.0, 0.0, 10.0, 0.0, 1.0, 0.0, Arc::new(p)).unwrap();
         let mut o = Sphere::default();
         o.set_material(m);
         assert_eq!(o.get_color_at(Coord::point(1.5, 0.0, 0.0)), expected)
@@ -324,11 +347,11 @@ mod test {
     #[test_case(Bullseye, Color::red()                    ; "bullseye")]
     #[test_case(Solid,    Color::red()                    ; "solid")]
     fn test_stripe_both_transformed(pattern: PatternType, expected: Color) {
-        let p = Pattern::new(pattern, Arc::new(Color::red()), Arc::new(Color::black()), Matrix::translation(0.5, 0.0, 0.0));
-        let m = Material::new(1.0, 0.0, 0.0, 10.0, 0.0, 1.0, 0.0, Arc::new(p));
+        let p = Pattern::new(pattern, Arc::new(Color::red()), Arc::new(Color::black()), Matrix::translation(0.5, 0.0, 0.0)).unwrap();
+        let m = Material::new(1.0, 0.0, 0.0, 10.0, 0.0, 1.0, 0.0, Arc::new(p)).unwrap();
         let mut o = Sphere::default();
         o.set_material(m);
-        o.set_transformation(Matrix::scaling(2.0, 2.0, 2.0));
+        o.set_transformation(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
         assert_eq!(o.get_color_at(Coord::point(2.5, 0.0, 0.0)), expected);
     }
 }
\ No newline at end of file
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use crate::coord::Coord;
+
+use super::{Tex, color::Color};
+
+/// which part of a texture's color is read as a single number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Channel {
+    R,
+    G,
+    B,
+    A,
+    /// perceived brightness, Rec. 709 weights
+    Luminance,
+}
+
+impl Channel {
+    pub fn of(&self, c: Color) -> f32 {
+        match self {
+            Channel::R => c.get_r(),
+            Channel::G => c.get_g(),
+            Channel::B => c.get_b(),
+            Channel::A => c.get_a(),
+            Channel::Luminance => 0.2126 * c.get_r() + 0.7152 * c.get_g() + 0.0722 * c.get_b(),
+        }
+    }
+}
+
+/// a texture read as a scalar, used to vary a material parameter over a surface
+#[derive(Debug, Clone)]
+pub struct ScalarMap {
+    tex: Arc<dyn Tex>,
+    channel: Channel,
+}
+
+#[allow(dead_code)]
+impl ScalarMap {
+    pub fn new(tex: Arc<dyn Tex>, channel: Channel) -> Self {
+        Self { tex, channel }
+    }
+
+    pub fn get_tex(&self) -> Arc<dyn Tex> {
+        self.tex.clone()
+    }
+
+    pub fn get_channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// value at an object space pos, the same space textures are looked up in
+    pub fn value_at(&self, pos: Coord) -> f32 {
+        self.channel.of(self.tex.get_color_at(pos))
+    }
+}
+
+impl PartialEq for ScalarMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.channel == other.channel && self.tex.compare(other.tex.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{matrix::Matrix, tex::pattern::Pattern};
+
+    use super::*;
+
+    #[test]
+    fn test_channels() {
+        let c = Color::new(0.1, 0.2, 0.3, 0.4);
+        assert_eq!(Channel::R.of(c), 0.1);
+        assert_eq!(Channel::G.of(c), 0.2);
+        assert_eq!(Channel::B.of(c), 0.3);
+        assert_eq!(Channel::A.of(c), 0.4);
+        assert!((Channel::Luminance.of(Color::white()) - 1.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_value_at() {
+        let checker = Pattern::new_checker(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::identity(4)).unwrap();
+        let map = ScalarMap::new(Arc::new(checker), Channel::Luminance);
+        assert!((map.value_at(Coord::point(0.5, 0.0, 0.5)) - 1.0).abs() < 0.0001);
+        assert_eq!(map.value_at(Coord::point(1.5, 0.0, 0.5)), 0.0);
+    }
+
+    #[test]
+    fn test_eq() {
+        let a = ScalarMap::new(Arc::new(Color::white()), Channel::R);
+        assert_eq!(a, ScalarMap::new(Arc::new(Color::white()), Channel::R));
+        assert_ne!(a, ScalarMap::new(Arc::new(Color::white()), Channel::G));
+        assert_ne!(a, ScalarMap::new(Arc::new(Color::black()), Channel::R));
+    }
+}
@@ -1,8 +1,21 @@
-use std::sync::Arc;
+use std::{fmt, sync::Arc};
 use crate::impl_getters_setters;
-use crate::{camera::Camera, canvas::Canvas, tex::color::Color, coord::Coord, light::{Light, lighting}, material::Material, matrix::Matrix, ray::Ray, renderable::{Intersection, Renderable, RenderableBase}, primitives::sphere::Sphere};
+use crate::error::{Result, RtcError};
+use crate::{camera::Camera, canvas::Canvas, tex::color::Color, coord::Coord, light::{Light, lighting}, material::{Material, MaterialParam, beer_lambert}, matrix::Matrix, medium::{Medium, Volume}, ray::Ray, renderable::{Intersection, Renderable, RenderableBase}, primitives::sphere::Sphere};
 use rayon::prelude::*;
 
+mod integrator;
+mod materials;
+mod media;
+mod passes;
+mod path_tracer;
+mod query;
+mod sampling;
+pub use integrator::{DebugIntegrator, DebugMode, Integrator, Multisample, Whitted};
+pub use passes::RenderPasses;
+pub use path_tracer::PathTracer;
+pub use query::RayHit;
+
 // I'm going to need to re-work this to add all objects, not just renderable ones aren't I
 // probably just make a node type or something
 
@@ -11,14 +24,18 @@ static EPSILON: f32 = 0.005; // this needs to be surprisingly big
 #[derive(Clone)]
 pub(crate) struct Comps {
     object: Arc<dyn Renderable>,
+    owner: Arc<dyn Renderable>, // the object in the world, see `Intersection::get_owner`
     point: Coord,
     eyev: Coord,
-    normalv: Coord,
+    normalv: Coord,             // shading normal, tilted by the material's `NormalPerturbation`
+    geometric_normalv: Coord,   // true surface normal, used to offset secondary rays
+    medium_absorption: Color,   // absorption of the object the ray travelled through to get here
     time: f32,
     inside: bool,
     reflectv: Coord,
     n1: f32,
-    n2: f32
+    n2: f32,
+    ray_time: f32   // shutter time of the incoming ray, carried on to secondary rays
 }
 
 impl_getters_setters!(Comps,
@@ -26,30 +43,37 @@ impl_getters_setters!(Comps,
     point: Coord,
     eyev: Coord,
     normalv: Coord,
+    geometric_normalv: Coord,
+    medium_absorption: Color,
     time: f32,
     inside: bool,
     reflectv: Coord,
     n1: f32,
-    n2: f32
+    n2: f32,
+    ray_time: f32
 );
 
 // precomputed data about an intersection of ray and renderable
 #[allow(dead_code)]
 impl Comps {
     fn new(object: Arc<dyn Renderable>, point: Coord, eyev: Coord, normalv: Coord, time: f32, inside: bool, reflectv: Coord, n1: f32, n2: f32) -> Self {
-        Self { object, point, eyev, normalv, time, inside, reflectv, n1, n2 }
+        Self { owner: object.clone(), object, point, eyev, normalv, geometric_normalv: normalv, medium_absorption: Color::black(), time, inside, reflectv, n1, n2, ray_time: 0.0 }
     }
 
     fn get_object(&self) -> Arc<dyn Renderable> {
         self.object.clone()
     }
 
+    fn get_owner(&self) -> Arc<dyn Renderable> {
+        self.owner.clone()
+    }
+
     fn get_over_point(&self) -> Coord {
-        self.get_point() + self.get_normalv() * EPSILON
+        self.get_point() + self.get_geometric_normalv() * EPSILON
     }
 
     fn get_under_point(&self) -> Coord {
-        self.get_point() - self.get_normalv() * EPSILON
+        self.get_point() - self.get_geometric_normalv() * EPSILON
     }
 
     pub(crate) fn prepare_computations(intersection: Intersection, ray: Ray, inter_list: Vec<Intersection>) -> Self {
@@ -65,51 +89,80 @@ impl Comps {
         let mut containers: Vec<Intersection> = Vec::new();
         let mut n1 = 1.0;
         let mut n2 = 1.0;
-        // TODO: there's got to be a more optimal way to do this
+        let mut medium_absorption = Color::black();
+        // thin walled objects don't bend light, so they are skipped when looking for the index
+        let index_of = |containers: &[Intersection]| containers.iter().rev()
+            .map(|x| x.get_object().get_shared_material())
+            .find(|m| !m.get_thin_walled())
+            .map_or(1.0, |m| m.get_refractive_index());
         for obj in inter_list {
             if obj == intersection {
-                if containers.len() != 0 {
-                    n1 = containers[containers.len()-1].get_object().get_material().get_refractive_index();
-                } 
-            }
-            let mut skip_push = true;
-            for i in 0..containers.len() {
-                if containers[i].get_object().compare(obj.get_object()) {
-                    containers.remove(i);
-                    skip_push = false;
-                    break;
+                n1 = index_of(&containers);
+                if let Some(last) = containers.last() {
+                    medium_absorption = last.get_object().get_shared_material().get_absorption();
                 }
             }
-            if  skip_push {
-                containers.push(obj.clone());
+            match containers.iter().position(|x| x.same_object(&obj)) {
+                Some(i) => { containers.remove(i); },
+                None => containers.push(obj.clone()),
             }
             if obj == intersection {
-                if containers.len() != 0 {
-                    n2 = containers[containers.len()-1].get_object().get_material().get_refractive_index();
-                }
+                n2 = index_of(&containers);
                 break
             }
         }
 
-        Self::new(
+        let mut comps = Self::new(
             intersection.get_object(), 
             ray.position(intersection.get_time()), 
             -ray.get_direction(), 
             normalv, 
             intersection.get_time(),
             inside,
-            intersection.get_reflectv(),
+            ray.get_direction().reflect(normalv),
             n1, 
             n2
-        )
+        );
+        comps.set_medium_absorption(medium_absorption);
+        comps.set_ray_time(ray.get_time());
+        comps.owner = intersection.get_owner();
+        if let Some(perturbation) = intersection.get_object().get_shared_material().get_normal() {
+            // inside is decided by the true normal, the tilted one is only used for shading
+            let object = intersection.get_object();
+            let outward = if inside { -normalv } else { normalv };
+            let mut shading = perturbation.apply(object.as_ref(), comps.get_point(), outward);
+            if inside {
+                shading = -shading;
+            }
+            let reflectv = ray.get_direction().reflect(shading);
+            // a steep tilt can't send the reflection back into the surface
+            if reflectv.dot(normalv) > 0.0 {
+                comps.set_reflectv(reflectv);
+            }
+            comps.set_normalv(shading);
+        }
+        comps
+    }
+
+    /// light left after travelling from the intersected point back to where the ray started,
+    /// absorbed by the medium it went through (see `Material::transmittance`)
+    fn transmittance(&self) -> Color {
+        beer_lambert(self.get_medium_absorption(), self.get_time() * self.get_eyev().magnitude())
     }
 
     /// Fresnel effect factor
     fn schlick(&self) -> f32 {
         let mut cos = self.get_eyev().dot(self.get_normalv());
-
-        if self.get_n1() > self.get_n2() {
-            let n = self.get_n1() / self.get_n2();
+        // a thin wall is crossed in one go, so it reflects like the surface of a solid entered from outside
+        let mat = self.get_object().get_shared_material();
+        let (n1, n2) = if mat.get_thin_walled() {
+            (self.get_n1(), mat.get_refractive_index())
+        } else {
+            (self.get_n1(), self.get_n2())
+        };
+
+        if n1 > n2 {
+            let n = n1 / n2;
             let sin2_t = n.powi(2) * (1.0 - cos.powi(2));
             if sin2_t > 1.0 {
                 return 1.0;
@@ -120,40 +173,80 @@ impl Comps {
             cos = cos_t;
         }
 
-        let r0 = ((self.get_n1() - self.get_n2()) / (self.get_n1() + self.get_n2())).powi(2);
+        let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
         r0 + (1.0 - r0) * (1.0 - cos).powi(5)
     }
+
+    /// direction of the refracted ray via Snell's law, None on total internal reflection
+    fn refracted_direction(&self) -> Option<Coord> {
+        if self.get_object().get_shared_material().get_thin_walled() {
+            return Some(-self.get_eyev());
+        }
+        let ratio = self.get_n1() / self.get_n2();
+        let cosi = self.get_eyev().dot(self.get_normalv());
+        let sin2 = ratio.powi(2) * (1.0 - cosi.powi(2));
+
+        if sin2 > 1.0 {
+            return None;
+        }
+
+        let cost = (1.0 - sin2).sqrt();
+        Some(self.get_normalv() * (ratio * cosi - cost) - self.get_eyev() * ratio)
+    }
 }
 
+/// stable handle to an object in a `World`, stays valid as other objects are added or removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(u64);
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Clone)]
 pub struct World {
     light: Vec<Light>,
+    // objects, ids and names are parallel, index i of each describes the same object
     objects: Vec<Arc<dyn Renderable>>,
-    max_depth: usize
+    ids: Vec<ObjectId>,
+    names: Vec<Option<String>>,
+    next_id: u64,
+    materials: Vec<(String, Arc<Material>)>,   // the material library, see `add_material`
+    max_depth: usize,
+    fog: Option<Medium>,
+    volumes: Vec<Volume>,
+    volume_steps: usize,    // ray marching steps per stretch of media, see `media_along`
+    seed: u64               // every random choice made while rendering derives from it, see `pixel_rng`
 }
 
-// TODO: need to implement way to remove objects
-
-impl_getters_setters!(World, max_depth: usize);
+impl_getters_setters!(World, max_depth: usize, volume_steps: usize, seed: u64);
 
 #[allow(dead_code)]
 impl World {
     pub fn new() -> Self {
-        Self { light: Vec::new(), objects: Vec::<Arc<dyn Renderable>>::new(), max_depth: 10 }
+        Self { light: Vec::new(), objects: Vec::<Arc<dyn Renderable>>::new(), ids: Vec::new(), names: Vec::new(), next_id: 0, materials: Vec::new(), max_depth: 10, fog: None, volumes: Vec::new(), volume_steps: 16, seed: 0 }
     }
 
     pub fn default() -> Self {
-        let l = Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white());
-        let mut s1 = Sphere::new(Coord::point(0.0, 0.0, 0.0));
-        let mut s2 = Sphere::new(Coord::point(0.0, 0.0, 0.0));
-        s2.set_transformation(Matrix::scaling(0.5, 0.5, 0.5));
-        let mat = Material::new(0.1, 0.7, 0.2, 200.0, 0.0, 1.0, 0.0, Arc::new(Color::new(0.8, 1.0, 0.6, 0.0)));
+        Self::default_scene().expect("default world is built from valid values")
+    }
+
+    fn default_scene() -> Result<Self> {
+        let l = Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white())?;
+        let mut s1 = Sphere::default();
+        let mut s2 = Sphere::default();
+        s2.set_transformation(Matrix::scaling(0.5, 0.5, 0.5))?;
+        let mat = Material::new(0.1, 0.7, 0.2, 200.0, 0.0, 1.0, 0.0, Arc::new(Color::new(0.8, 1.0, 0.6, 0.0)))?;
         s1.set_material(mat);        
 
 
-        let s1 = Arc::new(s1) as Arc<dyn Renderable>;
-        let s2 = Arc::new(s2) as Arc<dyn Renderable>;
-        let objs = vec![s1, s2];
-        Self { light: vec![l], objects: objs, max_depth: 10 }
+        let mut out = Self::new();
+        out.set_light(l);
+        out.add_obj(Arc::new(s1));
+        out.add_obj(Arc::new(s2));
+        Ok(out)
     }
 
     pub fn get_light(&self) -> &Vec<Light> {
@@ -168,18 +261,106 @@ impl World {
         self.light.push(light);
     }
 
+    /// replaces the light at `index`, in the order they were added
+    pub fn replace_light(&mut self, index: usize, light: Light) -> Result<()> {
+        let slot = self.light.get_mut(index).ok_or(RtcError::UnknownLight(index))?;
+        *slot = light;
+        Ok(())
+    }
+
     fn get_object(&self) -> Vec<Arc<dyn Renderable>> {
         self.objects.clone()
     }
 
-    pub fn add_obj(&mut self, obj: Arc<dyn Renderable>) {
+    pub fn add_obj(&mut self, obj: Arc<dyn Renderable>) -> ObjectId {
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
         self.objects.push(obj);
+        self.ids.push(id);
+        self.names.push(None);
+        id
+    }
+
+    /// returns an error if another object already has this name
+    pub fn add_named_obj(&mut self, name: &str, obj: Arc<dyn Renderable>) -> Result<ObjectId> {
+        if self.find_by_name(name).is_some() {
+            return Err(RtcError::DuplicateName(name.to_string()));
+        }
+        let id = self.add_obj(obj);
+        self.names[self.ids.len() - 1] = Some(name.to_string());
+        Ok(id)
+    }
+
+    /// ids of every object, in the order they were added
+    pub fn get_ids(&self) -> Vec<ObjectId> {
+        self.ids.clone()
+    }
+
+    fn position(&self, id: ObjectId) -> Result<usize> {
+        self.ids.iter().position(|val| *val == id).ok_or(RtcError::UnknownObject(id))
+    }
+
+    pub fn get_obj(&self, id: ObjectId) -> Option<Arc<dyn Renderable>> {
+        let i = self.position(id).ok()?;
+        Some(self.objects[i].clone())
+    }
+
+    pub fn get_name(&self, id: ObjectId) -> Option<String> {
+        let i = self.position(id).ok()?;
+        self.names[i].clone()
+    }
+
+    /// returns an error if the id isn't in this world or another object already has this name
+    pub fn set_name(&mut self, id: ObjectId, name: &str) -> Result<()> {
+        let i = self.position(id)?;
+        if self.find_by_name(name).is_some_and(|other| other != id) {
+            return Err(RtcError::DuplicateName(name.to_string()));
+        }
+        self.names[i] = Some(name.to_string());
+        Ok(())
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<ObjectId> {
+        let i = self.names.iter().position(|val| val.as_deref() == Some(name))?;
+        Some(self.ids[i])
+    }
+
+    /// takes the object out of the world, its id is never reused
+    pub fn remove_obj(&mut self, id: ObjectId) -> Result<Arc<dyn Renderable>> {
+        let i = self.position(id)?;
+        self.ids.remove(i);
+        self.names.remove(i);
+        Ok(self.objects.remove(i))
+    }
+
+    /// swaps in a new object under the same id and name, returning the old one
+    pub fn replace_obj(&mut self, id: ObjectId, obj: Arc<dyn Renderable>) -> Result<Arc<dyn Renderable>> {
+        let i = self.position(id)?;
+        Ok(std::mem::replace(&mut self.objects[i], obj))
+    }
+
+    /// the object is copied before being changed, so anyone else holding it (eg a render in progress) is unaffected
+    pub fn set_obj_transformation(&mut self, id: ObjectId, transformation: Matrix) -> Result<()> {
+        let i = self.position(id)?;
+        let mut obj = self.objects[i].clone_dyn();
+        obj.set_transformation(transformation)?;
+        self.objects[i] = Arc::from(obj);
+        Ok(())
+    }
+
+    /// the object is copied before being changed, so anyone else holding it (eg a render in progress) is unaffected
+    pub fn set_obj_material(&mut self, id: ObjectId, material: Material) -> Result<()> {
+        let i = self.position(id)?;
+        let mut obj = self.objects[i].clone_dyn();
+        obj.set_material(material);
+        self.objects[i] = Arc::from(obj);
+        Ok(())
     }
 
     fn get_intersections(&self, ray: Ray) -> Vec<Intersection> {
         let mut data = Vec::<Intersection>::new();
-        for obj in self.get_object() {
-            match obj.intersect(ray) {
+        for obj in &self.objects {
+            match obj.intersect_shared(obj, ray).1 {
                 None => continue,
                 Some(mut val) => data.append(&mut val),
             }
@@ -188,28 +369,35 @@ impl World {
     }
 
     fn shade_hit(&self, comps: Comps, depth: usize) -> Color {
-        let mut color = Color::black();
+        let (direct, reflected, refracted) = self.shade_hit_parts(comps, depth);
+        direct + reflected + refracted
+    }
+
+    /// the surface's own lighting and glow, then the reflected and refracted colors already weighted by
+    /// the material (and Fresnel), these always sum to `shade_hit`
+    fn shade_hit_parts(&self, comps: Comps, depth: usize) -> (Color, Color, Color) {
+        // glowing surfaces show up here and in reflections, but only light the scene when path traced
+        let mut color = comps.get_object().get_shared_material().emitted();
         for light in self.get_light() {
             color = color + lighting(
             comps.get_object(), 
-            *light, 
+            self.attenuated_light(comps.get_over_point(), light), 
             comps.get_point(), 
             comps.get_eyev(), 
             comps.get_normalv(),
-            self.in_shadow(comps.get_over_point())
+            self.in_shadow_of(comps.get_over_point(), light, comps.get_ray_time())
             );
         }
         let reflected =  self.reflected_color(comps.clone(), depth + 1);
         let refracted = self.refracted_color(comps.clone(), depth + 1);
 
-        let mat = comps.get_object().get_material();
-        if mat.get_reflection() > 0.0 && mat.get_transparency() > 0.0 {
+        let object = comps.get_object();
+        let point = comps.get_point();
+        if object.get_param_at(MaterialParam::Reflection, point) > 0.0 && object.get_param_at(MaterialParam::Transparency, point) > 0.0 {
             let reflectance = comps.schlick();
-            return color + 
-                reflected * reflectance + 
-                refracted * (1.0 - reflectance);
+            return (color, reflected * reflectance, refracted * (1.0 - reflectance));
         }
-        color + reflected + refracted
+        (color, reflected, refracted)
     }
 
     fn color_at(&self, ray: Ray, depth: usize) -> Color {
@@ -219,10 +407,12 @@ impl World {
         let intersections = self.get_intersections(ray);
         let hit = Intersection::find_hit(&intersections);
         if hit.is_none() {
-            return Color::black();
+            return self.media_along(ray, None).1;
         }
+        let (fog, scattered) = self.media_along(ray, Some(hit.unwrap().get_time()));
         let comps = Comps::prepare_computations(hit.unwrap().clone(), ray, intersections);
-        self.shade_hit(comps, depth)
+        let transmittance = comps.transmittance() * fog;
+        self.shade_hit(comps, depth) * transmittance + scattered
     }
 
     pub fn render_world(&self, cam: &Camera) -> Canvas {
@@ -230,8 +420,7 @@ impl World {
         // TODO: multithread this
         for y in 0..(cam.get_vsize()-1) {
             for x in 0..(cam.get_hsize()-1) {
-                let ray = cam.ray_for_pixel(x, y);
-                let color = self.color_at(ray, 0);
+                let color = Whitted.pixel_color(self, cam, x, y);
                 out.set_pixel(x, y, color);
             }
         }
@@ -239,16 +428,17 @@ impl World {
     }
 
     pub fn render_world_multi(&self, cam: &Camera) -> Canvas {
-        let pixels: Vec<(usize, usize, Color)> = (0..cam.get_vsize()-1)
+        self.render_with(cam, &Whitted)
+    }
+
+    /// renders every pixel in parallel with the given integrator, eg `Whitted`, `PathTracer` or a `DebugIntegrator`
+    pub fn render_with(&self, cam: &Camera, integrator: &dyn Integrator) -> Canvas {
+        let pixels: Vec<(usize, usize, Color)> = (0..cam.get_vsize())
             .into_par_iter()
             .flat_map(|y| {
-                (0..cam.get_hsize()-1)
+                (0..cam.get_hsize())
                     .into_par_iter()
-                    .map(move |x| {
-                        let ray = cam.ray_for_pixel(x, y);
-                        let color = self.color_at(ray, 0);
-                        (x, y, color)
-                    })  
+                    .map(move |x| (x, y, integrator.pixel_color(self, cam, x, y)))
             })
             .collect();
         let mut out = Canvas::new(cam.get_hsize(), cam.get_vsize());
@@ -260,11 +450,15 @@ impl World {
 
     fn in_shadow(&self, p: Coord) -> bool { // TODO: change this to a float that is the inverse of the intersected object's transparency, and keep going until it is above 1.0 or hit's the object in question (don't count exiting just entering)
         // TODO: current impl only supports 1 light source
-        let l = self.get_light()[0];
+        self.in_shadow_of(p, &self.get_light()[0], 0.0)
+    }
+
+    /// whether anything blocks `l` from `p` at shutter time `time`
+    fn in_shadow_of(&self, p: Coord, l: &Light, time: f32) -> bool {
         let dir = l.get_pos() - p;
         let dist = dir.magnitude();
         let dir = dir.normalized();
-        let ray = Ray::new(p, dir);
+        let ray = Ray::new(p, dir).with_time(time);
         let intersections = self.get_intersections(ray);    // T of all of these is < 0
         let intersections = Intersection::find_hit(&intersections);
         match intersections {
@@ -274,34 +468,27 @@ impl World {
     }
 
     fn reflected_color(&self, data: Comps, depth: usize) ->  Color {
-        let reflective = data.get_object().get_material().get_reflection();
+        let reflective = data.get_object().get_param_at(MaterialParam::Reflection, data.get_point());
         if reflective <= 0.0 {
             return Color::black();
         }
-        let ray = Ray::new(data.get_over_point(), data.get_reflectv());
+        let ray = Ray::new(data.get_over_point(), data.get_reflectv()).with_time(data.get_ray_time());
         let color = self.color_at(ray, depth);
         color * reflective
     }
 
     fn refracted_color(&self, data: Comps, depth: usize) -> Color {
-        if depth > self.get_max_depth() || data.get_object().get_material().get_transparency() == 0.0 {
+        let transparency = data.get_object().get_param_at(MaterialParam::Transparency, data.get_point());
+        if depth > self.get_max_depth() || transparency == 0.0 {
             return Color::black();
         }
 
-        // find refraction angle via Snell's law
-        let ratio = data.get_n1() / data.get_n2();
-        let cosi = data.get_eyev().dot(data.get_normalv());
-        let sin2 = ratio.powi(2) * (1.0 - cosi.powi(2));
-
-        if sin2 > 1.0 {
-            // full internal refraction
-            return Color::black();
-        }
-
-        let cost = (1.0 - sin2).sqrt();
-        let direction = data.get_normalv() * (ratio * cosi - cost) - data.get_eyev() * ratio;
-        let refracted_ray = Ray::new(data.get_under_point(), direction);
-        self.color_at(refracted_ray, depth) * data.get_object().get_material().get_transparency()
+        let direction = match data.refracted_direction() {
+            Some(val) => val,
+            None => return Color::black(), // full internal refraction
+        };
+        let refracted_ray = Ray::new(data.get_under_point(), direction).with_time(data.get_ray_time());
+        self.color_at(refracted_ray, depth) * transparency
     }
 }
 
@@ -310,9 +497,11 @@ impl World {
 mod tests {
     use std::sync::Arc;
 
-use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matrix::Matrix, primitives::plane::Plane, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, compare_renderables}, primitives::sphere::Sphere, tex::{color::Color, pattern::Pattern}, world::EPSILON};
+use crate::{camera::Camera, coord::Coord, light::Light, material::{Material, Preset}, matrix::Matrix, primitives::{cube::Cube, plane::Plane}, ray::Ray, renderable::{Intersection, Renderable, RenderableBase, compare_renderables}, primitives::sphere::Sphere, tex::{color::Color, normal::NormalPerturbation, pattern::Pattern, scalar::{Channel, ScalarMap}}, world::EPSILON};
+
+    use crate::error::RtcError;
 
-    use super::{Comps, World};
+    use super::{Comps, ObjectId, World};
 
 
     fn test_colors_roughly_equal(a: &Color, b: &Color) {
@@ -337,22 +526,88 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         assert_eq!(w.get_object().len(), 0);
     }
 
+    #[test]
+    fn test_ids_and_names() {
+        let mut w = World::new();
+        let a = w.add_obj(Arc::new(Sphere::default()));
+        let b = w.add_named_obj("floor", Arc::new(Plane::default())).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(w.get_ids(), vec![a, b]);
+        assert_eq!(w.find_by_name("floor"), Some(b));
+        assert_eq!(w.find_by_name("wall"), None);
+        assert_eq!(w.get_name(a), None);
+        assert_eq!(w.get_name(b), Some("floor".to_string()));
+        assert_eq!(w.get_obj(b).unwrap().get_type(), Plane::default().get_type());
+
+        assert_eq!(w.add_named_obj("floor", Arc::new(Plane::default())).err(), Some(RtcError::DuplicateName("floor".to_string())));
+        assert_eq!(w.set_name(a, "floor"), Err(RtcError::DuplicateName("floor".to_string())));
+        w.set_name(a, "ball").unwrap();
+        w.set_name(b, "floor").unwrap();
+        assert_eq!(w.find_by_name("ball"), Some(a));
+    }
+
+    #[test]
+    fn test_remove_obj() {
+        let mut w = World::default();
+        let ids = w.get_ids();
+        let removed = w.remove_obj(ids[0]).unwrap();
+        compare_renderables(removed.as_ref(), World::default().get_object()[0].as_ref());
+        assert_eq!(w.get_ids(), vec![ids[1]]);
+        assert!(w.get_obj(ids[0]).is_none());
+        assert_eq!(w.remove_obj(ids[0]).err(), Some(RtcError::UnknownObject(ids[0])));
+
+        // ids are never reused
+        let c = w.add_obj(Arc::new(Sphere::default()));
+        assert!(!ids.contains(&c));
+    }
+
+    #[test]
+    fn test_replace_obj() {
+        let mut w = World::default();
+        let id = w.get_ids()[0];
+        w.set_name(id, "outer").unwrap();
+        w.replace_obj(id, Arc::new(Plane::default())).unwrap();
+        assert_eq!(w.get_obj(id).unwrap().get_type(), Plane::default().get_type());
+        assert_eq!(w.find_by_name("outer"), Some(id));
+        assert_eq!(w.replace_obj(ObjectId(100), Arc::new(Plane::default())).err(), Some(RtcError::UnknownObject(ObjectId(100))));
+    }
+
+    #[test]
+    fn test_update_obj_in_place() {
+        let mut w = World::default();
+        let id = w.get_ids()[1];
+        let held = w.get_obj(id).unwrap();
+        w.set_obj_transformation(id, Matrix::translation(0.0, 5.0, 0.0)).unwrap();
+        assert_eq!(w.get_obj(id).unwrap().get_transformation(), Matrix::translation(0.0, 5.0, 0.0));
+        assert_eq!(held.get_transformation(), Matrix::scaling(0.5, 0.5, 0.5));
+        assert_eq!(w.set_obj_transformation(id, Matrix::scaling(0.0, 1.0, 1.0)), Err(RtcError::SingularTransformation));
+
+        let mut mat = Material::default();
+        mat.set_color(Color::red());
+        w.set_obj_material(id, mat.clone()).unwrap();
+        assert_eq!(w.get_obj(id).unwrap().get_material(), mat);
+
+        // the moved sphere is no longer hit by a ray through the origin
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        assert_eq!(w.get_intersections(r).len(), 2);
+    }
+
     #[test]
     fn test_default() {
         let w = World::default();
         
         assert_eq!(w.get_light().len(), 1);
-        assert_eq!(w.get_light()[0], Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white()));
+        assert_eq!(w.get_light()[0], Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white()).unwrap());
         
         let mut s1 = Sphere::default();
         let objs = w.get_object();
-        let mat = Material::new(0.1, 0.7, 0.2, 200.0, 0.0, 1.0, 0.0, Arc::new(Color::new(0.8, 1.0, 0.6, 0.0)));
+        let mat = Material::new(0.1, 0.7, 0.2, 200.0, 0.0, 1.0, 0.0, Arc::new(Color::new(0.8, 1.0, 0.6, 0.0))).unwrap();
         s1.set_material(mat);
         assert_eq!(objs.len(), 2);
         compare_renderables(objs[0].as_ref(), &s1);
 
         let mut s2 = Sphere::default();
-        s2.set_transformation(Matrix::scaling(0.5, 0.5, 0.5));
+        s2.set_transformation(Matrix::scaling(0.5, 0.5, 0.5)).unwrap();
 
         compare_renderables(objs[1].as_ref(), &s2);
     }
@@ -378,7 +633,7 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     fn test_prepare_computations() {
         let ray = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
         let shape = Arc::new(Sphere::default());
-        let i = Intersection::new(4.0, shape.clone(), Coord::vec(0.0, 0.0, 0.0));
+        let i = Intersection::new(4.0, shape.clone());
         let comp = Comps::prepare_computations(i.clone(), ray, vec![i.clone()]);
         assert_eq!(comp.get_time(), i.get_time());
         assert_eq!(comp.get_object().get_transformation(), shape.get_transformation());
@@ -394,7 +649,7 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     fn test_prepare_computations_inside() {
         let ray = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0));
         let shape = Arc::new(Sphere::default());
-        let i = Intersection::new(1.0, shape.clone(), Coord::vec(0.0, 0.0, 0.0));
+        let i = Intersection::new(1.0, shape.clone());
         let comp = Comps::prepare_computations(i.clone(), ray, vec![i.clone()]);
 
         assert_eq!(comp.get_point(), Coord::point(0.0, 0.0, 1.0));
@@ -408,16 +663,16 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         let w = World::default();
         let ray = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
         let shape = w.get_object()[0].clone();
-        let i = Intersection::new(4.0, shape, Coord::vec(0.0, 0.0, 0.0));
+        let i = Intersection::new(4.0, shape);
         let comps = Comps::prepare_computations(i.clone(), ray, vec![i.clone()]);
         let c = w.shade_hit(comps, 0);
         assert_eq!(c, Color::new(0.38066125, 0.4758265, 0.28549594, 0.0));
 
         let mut w = World::default();
-        w.set_light(Light::new(Coord::point(0.0, 0.25, 0.0), Color::white()));
+        w.set_light(Light::new(Coord::point(0.0, 0.25, 0.0), Color::white()).unwrap());
         let ray = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0));
         let shape = w.get_object()[1].clone();
-        let i = Intersection::new(0.5, shape, Coord::vec(0.0, 0.0, 0.0));
+        let i = Intersection::new(0.5, shape);
         let comps = Comps::prepare_computations(i.clone(), ray, vec![i.clone()]);
         let c = w.shade_hit(comps, 0);
         assert_eq!(c, Color::new(0.9049845, 0.9049845, 0.9049845, 0.0));
@@ -439,8 +694,8 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         // I think I need to change getters to return reference, not clone (unless rc)
         // probably need to use RefCells not Rcs https://stackoverflow.com/questions/52994205/what-is-the-standard-way-to-call-a-mutable-method-in-a-rc-wrapped-object
         // alternatively, use Box and world class is the holder of all objects
-        w.get_object()[0].get_material().set_ambient(1.0);
-        w.get_object()[1].get_material().set_ambient(1.0);
+        w.get_object()[0].get_material().set_ambient(1.0).unwrap();
+        w.get_object()[1].get_material().set_ambient(1.0).unwrap();
         let ray = Ray::new(Coord::point(0.0, 0.0, 0.75), Coord::vec(0.0, 0.0, -1.0));
         let c = w.color_at(ray, 0);
         //assert_eq!(c, w.get_object()[0].get_material().get_color());
@@ -454,7 +709,7 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         let from = Coord::point(0.0, 0.0, -5.0);
         let to = Coord::point(0.0, 0.0, 0.0);
         let up = Coord::vec(0.0, 1.0, 0.0);
-        c.transform(Matrix::view_transformation(from, to, up));
+        c.transform(Matrix::view_transformation(from, to, up)).unwrap();
 
         let image = w.render_world(&c);
         assert_eq!(image.get_pixel(5, 5), Color::new(0.38066125, 0.4758265, 0.28549594, 0.0));
@@ -488,20 +743,45 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         assert!(!w.in_shadow(p));
     }
 
+    #[test]
+    fn test_shadow_of_moving_object() {
+        // the blocker slides out from between the point and the light over the shutter
+        let mut w = World::new();
+        let light = Light::new(Coord::point(0.0, 10.0, 0.0), Color::white()).unwrap();
+        w.add_light(light);
+        let motion = crate::motion::Motion::linear(Matrix::translation(0.0, 5.0, 0.0), Matrix::translation(5.0, 5.0, 0.0)).unwrap();
+        w.add_obj(Arc::new(crate::primitives::moving::Moving::new(Arc::new(Sphere::default()), motion)));
+        let p = Coord::point(0.0, 0.0, 0.0);
+        assert!(w.in_shadow_of(p, &light, 0.0));
+        assert!(!w.in_shadow_of(p, &light, 1.0));
+    }
+
+    #[test]
+    fn test_secondary_rays_keep_time() {
+        let mut w = World::default();
+        let mut mat = Material::default();
+        mat.set_reflection(0.5).unwrap();
+        w.add_obj(Arc::new(Plane::new(Matrix::translation(0.0, -1.0, 0.0), mat).unwrap()));
+        let r = Ray::new(Coord::point(0.0, 0.0, -3.0), Coord::vec(0.0, -2_f32.sqrt()/2.0, 2_f32.sqrt()/2.0)).with_time(0.75);
+        let xs = w.get_intersections(r);
+        let comps = Comps::prepare_computations(Intersection::find_hit(&xs).unwrap().clone(), r, xs.clone());
+        assert_eq!(comps.get_ray_time(), 0.75);
+    }
+
     #[test]
     fn test_shadow() {
         let mut w = World::new();
-        let l = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white());
+        let l = Light::new(Coord::point(0.0, 0.0, -10.0), Color::white()).unwrap();
         w.set_light(l);
 
         let s1 = Sphere::default();
         w.add_obj(Arc::new(s1));
 
-        let s2 = Arc::new(Sphere::new(Coord::point(0.0, 0.0, 10.0)));
+        let s2 = Arc::new(Sphere::new(Coord::point(0.0, 0.0, 10.0)).unwrap());
         w.add_obj(s2.clone());
 
         let r = Ray::new(Coord::point(0.0, 0.0, 5.0), Coord::vec(0.0, 0.0, 1.0));
-        let i = Intersection::new(4.0, s2, Coord::vec(0.0, 0.0, 0.0));
+        let i = Intersection::new(4.0, s2);
 
         let comps = Comps::prepare_computations(i.clone(), r, vec![i.clone()]);
         let c = w.shade_hit(comps, 0);
@@ -511,20 +791,52 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     #[test]
     fn test_shadow_over_point() {
         let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
-        let s = Arc::new(Sphere::new(Coord::point(0.0, 0.0, 1.0)));
-        let i = Intersection::new(5.0, s, Coord::vec(0.0, 0.0, 0.0));
+        let s = Arc::new(Sphere::new(Coord::point(0.0, 0.0, 1.0)).unwrap());
+        let i = Intersection::new(5.0, s);
         let comps = Comps::prepare_computations(i.clone(), r, vec![i.clone()]);
         assert!(comps.get_over_point().get_z() < -EPSILON/2.0);
         assert!(comps.get_point().get_z() > comps.get_over_point().get_z());
     }
 
+    #[test]
+    fn test_reflectv_of_transformed_object() {
+        // reflected in world space, not in the object's own space
+        let cube = Arc::new(Cube::new(Matrix::rotation_z(0.3) * Matrix::scaling(2.0, 1.0, 1.0), Material::default()).unwrap());
+        let r = Ray::new(Coord::point(0.5, 3.0, -3.0), Coord::vec(0.0, -1.0, 1.0).normalized());
+        let xs = cube.intersect(r).unwrap();
+        let hit = Intersection::find_hit(&xs).unwrap().clone();
+        let n = cube.normal_at(r.position(hit.get_time()));
+        let comps = Comps::prepare_computations(hit, r, xs.clone());
+        assert!((comps.get_reflectv() - r.get_direction().reflect(n)).magnitude() < 0.0001);
+    }
+
+    #[test]
+    fn test_perturbed_normal() {
+        // height grows along x, so the shading normal of the floor leans towards -x
+        let ramp = Pattern::new_gradient(Arc::new(Color::black()), Arc::new(Color::white()), Matrix::identity(4)).unwrap();
+        let mut m = Material::default();
+        m.set_normal(Some(NormalPerturbation::bump(ScalarMap::new(Arc::new(ramp), Channel::R), 1.0)));
+        let mut p = Plane::default();
+        p.set_material(m);
+        let r = Ray::new(Coord::point(0.5, 1.0, 0.5), Coord::vec(0.0, -1.0, 0.0));
+        let i = Intersection::new(1.0, Arc::new(p));
+        let comps = Comps::prepare_computations(i.clone(), r, vec![i]);
+        let tilted = Coord::vec(-1.0, 1.0, 0.0).normalized();
+        assert!((comps.get_normalv() - tilted).magnitude() < 0.001);
+        assert_eq!(comps.get_geometric_normalv(), Coord::vec(0.0, 1.0, 0.0));
+        assert!((comps.get_reflectv() - Coord::vec(-1.0, 0.0, 0.0)).magnitude() < 0.001);
+        // secondary rays still start off the true surface
+        assert!((comps.get_over_point() - Coord::point(0.5, EPSILON, 0.5)).magnitude() < 0.0001);
+        assert!(!comps.get_inside());
+    }
+
     #[test]
     fn test_reflection_of_mat() {
-        let l = Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white());
-        let mut s2 = Sphere::new(Coord::point(0.0, 0.0, 0.0));
-        s2.set_transformation(Matrix::scaling(0.5, 0.5, 0.5));
+        let l = Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white()).unwrap();
+        let mut s2 = Sphere::new(Coord::point(0.0, 0.0, 0.0)).unwrap();
+        s2.set_transformation(Matrix::scaling(0.5, 0.5, 0.5)).unwrap();
         let mut m = Material::default();
-        m.set_ambient(1.0);
+        m.set_ambient(1.0).unwrap();
 
         let mut w = World::new();
         w.add_light(l);
@@ -541,8 +853,8 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     fn test_reflection() {
         let mut w = World::default();
         let mut mat = Material::default();
-        mat.set_reflection(0.5);
-        let p = Plane::new(Matrix::translation(0.0, -1.0, 0.0), mat);
+        mat.set_reflection(0.5).unwrap();
+        let p = Plane::new(Matrix::translation(0.0, -1.0, 0.0), mat).unwrap();
         w.add_obj(Arc::new(p.clone()));
 
         let ray = Ray::new(Coord::point(0.0, 0.0, -3.0), Coord::vec(0.0, -2_f32.sqrt()/2.0, 2_f32.sqrt()/2.0));
@@ -552,17 +864,37 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         assert_eq!(w.reflected_color(comps, 10), Color::black())
     }
 
+    #[test]
+    fn test_mapped_reflection() {
+        // a checker floor that only reflects on its white squares, the ray lands at (0, -1, -2)
+        use crate::tex::scalar::{Channel, ScalarMap};
+        let ray = Ray::new(Coord::point(0.0, 0.0, -3.0), Coord::vec(0.0, -2_f32.sqrt()/2.0, 2_f32.sqrt()/2.0));
+        for (offset, reflects) in [(0.0, true), (1.0, false)] {
+            let mut w = World::default();
+            let mut mat = Material::default();
+            mat.set_reflection(0.5).unwrap();
+            let checker = Pattern::new_checker(Arc::new(Color::white()), Arc::new(Color::black()), Matrix::translation(offset, 0.0, 0.0)).unwrap();
+            mat.set_map(crate::material::MaterialParam::Reflection, ScalarMap::new(Arc::new(checker), Channel::R));
+            let p = Plane::new(Matrix::translation(0.0, -1.0, 0.0), mat).unwrap();
+            w.add_obj(Arc::new(p.clone()));
+            let i = p.intersect(ray).unwrap();
+            let comps = Comps::prepare_computations(i[0].clone(), ray, i.clone());
+            let expected = if reflects { Color::new(0.1911927, 0.23899086, 0.14339453, 0.0) } else { Color::black() };
+            assert_eq!(w.reflected_color(comps, 0), expected);
+        }
+    }
+
     #[test]
     fn test_infinite_reflection() {
         // TODO: need a more ergonomic way to create worlds/objects/etc.
         let mut w = World::new();
         let mut mat = Material::default();
-        mat.set_reflection(1.0);
-        let upper = Plane::new(Matrix::translation(0.0, 1.0, 0.0), mat.clone());
-        let lower = Plane::new(Matrix::translation(1.0, -1.0, 1.0), mat);
+        mat.set_reflection(1.0).unwrap();
+        let upper = Plane::new(Matrix::translation(0.0, 1.0, 0.0), mat.clone()).unwrap();
+        let lower = Plane::new(Matrix::translation(1.0, -1.0, 1.0), mat).unwrap();
         w.add_obj(Arc::new(upper));
         w.add_obj(Arc::new(lower));
-        w.add_light(Light::new(Coord::point(0.0, 0.0, 0.0), Color::white()));
+        w.add_light(Light::new(Coord::point(0.0, 0.0, 0.0), Color::white()).unwrap());
         let ray = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 1.0, 0.0));
         assert_ne!(w.color_at(ray, 0), Color::black())
     }
@@ -571,20 +903,20 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     fn test_refraction() {
         // TODO: this test is a good example of what is bad about the current impl of getters/setters/etc.
         let mut mat = Material::default();
-        mat.set_transparency(1.0);
-        mat.set_refractive_index(1.5);
+        mat.set_transparency(1.0).unwrap();
+        mat.set_refractive_index(1.5).unwrap();
         let mut s1 = Sphere::default();
         s1.set_material(mat.clone());
-        s1.set_transformation(Matrix::scaling(2.0, 2.0, 2.0));
+        s1.set_transformation(Matrix::scaling(2.0, 2.0, 2.0)).unwrap();
 
         let mut s2 = Sphere::default();
-        s2.set_transformation(Matrix::translation(0.0, 0.0, -0.25));
-        mat.set_refractive_index(2.0);
+        s2.set_transformation(Matrix::translation(0.0, 0.0, -0.25)).unwrap();
+        mat.set_refractive_index(2.0).unwrap();
         s2.set_material(mat.clone());
 
         let mut s3 = Sphere::default();
-        s3.set_transformation(Matrix::translation(0.0, 0.0, 0.25));
-        mat.set_refractive_index(2.5);
+        s3.set_transformation(Matrix::translation(0.0, 0.0, 0.25)).unwrap();
+        mat.set_refractive_index(2.5).unwrap();
         s3.set_material(mat);
 
         let s1 = Arc::new(s1);
@@ -592,14 +924,13 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         let s3 = Arc::new(s3);
 
         let ray = Ray::new(Coord::point(0.0, 0.0, -4.0), Coord::vec(0.0, 0.0, 1.0));
-        let reflectv = Coord::vec(0.0, 0.0, 0.0);
         let xs = vec![
-            (Intersection::new(2.0, s1.clone(), reflectv), 1.0, 1.5),
-            (Intersection::new(2.75, s2.clone(), reflectv), 1.5, 2.0),
-            (Intersection::new(3.25, s3.clone(), reflectv), 2.0, 2.5),
-            (Intersection::new(4.75, s2, reflectv), 2.5, 2.5),
-            (Intersection::new(5.25, s3, reflectv), 2.5, 1.5),
-            (Intersection::new(6.0, s1, reflectv), 1.5, 1.0)
+            (Intersection::new(2.0, s1.clone()), 1.0, 1.5),
+            (Intersection::new(2.75, s2.clone()), 1.5, 2.0),
+            (Intersection::new(3.25, s3.clone()), 2.0, 2.5),
+            (Intersection::new(4.75, s2), 2.5, 2.5),
+            (Intersection::new(5.25, s3), 2.5, 1.5),
+            (Intersection::new(6.0, s1), 1.5, 1.0)
         ];
 
         for (intersection, n1, n2) in xs.clone() {
@@ -613,8 +944,8 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     fn test_under_point() {
         let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
         let mut s = Sphere::glass_sphere();
-        s.apply_transformation(Matrix::translation(0.0, 0.0, 1.0));
-        let i = Intersection::new(5.0, Arc::new(s), Coord::vec(0.0, 0.0, 0.0));
+        s.apply_transformation(Matrix::translation(0.0, 0.0, 1.0)).unwrap();
+        let i = Intersection::new(5.0, Arc::new(s));
         let xs = vec![i.clone()];
         let comps = Comps::prepare_computations(i, r, xs);
         assert!(comps.get_under_point().get_z() > EPSILON / 2.0);
@@ -627,8 +958,8 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         let s = w.get_object()[0].clone();      // TODO: should be renamed to get_objects
         let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
         let xs = vec![
-            Intersection::new(4.0, s.clone(), Coord::vec(0.0, 0.0, 0.0)),
-            Intersection::new(6.0, s.clone(), Coord::vec(0.0, 0.0, 0.0))
+            Intersection::new(4.0, s.clone()),
+            Intersection::new(6.0, s.clone())
         ];
         let comps = Comps::prepare_computations(xs[0].clone(), r, xs);
         assert_eq!(w.refracted_color(comps, 5), Color::black())
@@ -645,8 +976,8 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
 
         let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
         let xs = vec![
-            Intersection::new(4.0, s.clone(), Coord::vec(0.0, 0.0, 0.0)),
-            Intersection::new(6.0, s.clone(), Coord::vec(0.0, 0.0, 0.0))
+            Intersection::new(4.0, s.clone()),
+            Intersection::new(6.0, s.clone())
         ];
         let comps = Comps::prepare_computations(xs[0].clone(), r, xs);
 
@@ -654,6 +985,97 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         assert_eq!(w.refracted_color(comps, 6), Color::black());
     }
 
+    #[test]
+    fn test_emissive_surface() {
+        // glows without any light, and shows up in a mirror
+        let mut w = World::new();
+        let mut lamp = Sphere::default();
+        lamp.set_material(Material::emissive(Color::new(1.0, 0.5, 0.25, 0.0), 2.0).unwrap());
+        w.add_obj(Arc::new(lamp));
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(r, 0), Color::new(2.0, 1.0, 0.5, 0.0));
+
+        let mut mirror_mat = Material::default();
+        mirror_mat.set_ambient(0.0).unwrap();
+        mirror_mat.set_reflection(1.0).unwrap();
+        w.add_obj(Arc::new(Plane::new(Matrix::translation(0.0, -1.0, 0.0), mirror_mat).unwrap()));
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::point(0.0, -1.0, -2.5) - Coord::point(0.0, 0.0, -5.0));
+        assert_eq!(w.color_at(r, 0), Color::new(2.0, 1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_medium_absorption() {
+        let mut s = Sphere::glass_sphere();
+        s.set_material(Material::tinted_glass(Color::new(0.5, 1.0, 1.0, 0.0), 1.0).unwrap());
+        let s = Arc::new(s);
+        let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0));
+        let xs = vec![
+            Intersection::new(-1.0, s.clone()),
+            Intersection::new(1.0, s.clone())
+        ];
+        // from inside the sphere the ray has gone through one unit of glass
+        let comps = Comps::prepare_computations(xs[1].clone(), r, xs.clone());
+        assert_eq!(comps.transmittance(), Color::new(0.5, 1.0, 1.0, 0.0));
+        // the first hit is reached through air
+        let r = Ray::new(Coord::point(0.0, 0.0, -3.0), Coord::vec(0.0, 0.0, 1.0));
+        let xs: Vec<Intersection> = xs.into_iter().map(|x| Intersection::new(x.get_time() + 3.0, s.clone())).collect();
+        let comps = Comps::prepare_computations(xs[0].clone(), r, xs);
+        assert_eq!(comps.transmittance(), Color::new(1.0, 1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_tinted_glass_depends_on_thickness() {
+        let color_through = |radius: f32| {
+            let mut glass = Material::tinted_glass(Color::new(0.5, 1.0, 1.0, 0.0), 1.0).unwrap();
+            glass.set_refractive_index(1.0).unwrap();
+            glass.set_ambient(0.0).unwrap();
+            glass.set_diffuse(0.0).unwrap();
+            glass.set_specular(0.0).unwrap();
+            glass.set_reflection(0.0).unwrap();
+            let mut s = Sphere::default();
+            s.set_transformation(Matrix::scaling(radius, radius, radius)).unwrap();
+            s.set_material(glass);
+
+            let mut backdrop_mat = Material::default();
+            backdrop_mat.set_ambient(1.0).unwrap();
+            backdrop_mat.set_diffuse(0.0).unwrap();
+            backdrop_mat.set_specular(0.0).unwrap();
+            let backdrop = Plane::new(Matrix::translation(0.0, 0.0, 5.0) * Matrix::rotation_x(std::f32::consts::FRAC_PI_2), backdrop_mat).unwrap();
+
+            let mut w = World::new();
+            w.add_obj(Arc::new(s));
+            w.add_obj(Arc::new(backdrop));
+            w.add_light(Light::new(Coord::point(0.0, 10.0, -10.0), Color::white()).unwrap());
+            w.color_at(Ray::new(Coord::point(0.0, 0.0, -4.0), Coord::vec(0.0, 0.0, 1.0)), 0)
+        };
+        // the ray crosses 2 * radius of glass, less a little from starting refracted rays under the surface
+        let (thin, thick) = (color_through(0.5), color_through(1.0));
+        assert!((thin.get_r() - 0.5).abs() < 0.01, "{thin:?}");
+        assert!((thick.get_r() - 0.25).abs() < 0.01, "{thick:?}");
+        assert_eq!((thick.get_g(), thick.get_b()), (1.0, 1.0));
+    }
+
+    #[test]
+    fn test_thin_walled() {
+        let mut mat = Material::preset(Preset::Glass);
+        mat.set_thin_walled(true);
+        let mut s = Sphere::default();
+        s.set_material(mat);
+        let s = Arc::new(s);
+        let dir = Coord::vec(0.0, 1.0, 1.0).normalized();
+        let r = Ray::new(Coord::point(0.0, -3.5, -3.0), dir);
+        let xs = s.intersect(r).unwrap();
+        let comps = Comps::prepare_computations(xs[0].clone(), r, xs.clone());
+        assert_eq!(comps.get_n1(), 1.0);
+        assert_eq!(comps.get_n2(), 1.0);
+        // goes straight through but still reflects some light
+        assert_eq!(comps.refracted_direction(), Some(dir));
+        assert!(comps.schlick() > 0.04);
+        let comps = Comps::prepare_computations(xs[1].clone(), r, xs);
+        assert_eq!(comps.get_n1(), 1.0);
+        assert_eq!(comps.refracted_direction(), Some(dir));
+    }
+
     #[test]
     fn test_refracted_total_internal() {
         let w = World::default();
@@ -668,8 +1090,8 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
             Coord::vec(0.0, 1.0, 0.0)
         );
         let xs = vec![
-            Intersection::new(-2_f32.sqrt()/2.0, s.clone(), Coord::vec(0.0, 0.0, 0.0)),
-            Intersection::new(2_f32.sqrt()/2.0, s.clone(), Coord::vec(0.0, 0.0, 0.0))
+            Intersection::new(-2_f32.sqrt()/2.0, s.clone()),
+            Intersection::new(2_f32.sqrt()/2.0, s.clone())
         ];
 
         let comps = Comps::prepare_computations(xs[1].clone(), r, xs);
@@ -680,30 +1102,30 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     fn test_refracted_color() {
         let mut a = Sphere::default();
         let mut mat = Material::default();
-        mat.set_ambient(1.0);
-        mat.set_texture(Arc::new(Pattern::test_pattern(Matrix::identity(4))));
+        mat.set_ambient(1.0).unwrap();
+        mat.set_texture(Arc::new(Pattern::test_pattern(Matrix::identity(4)).unwrap()));
         a.set_material(mat);
         let a = Arc::new(a);
 
         let mut b = Sphere::default();
         let mut mat = Material::default();
-        mat.set_transparency(1.0);
-        mat.set_refractive_index(1.5);
+        mat.set_transparency(1.0).unwrap();
+        mat.set_refractive_index(1.5).unwrap();
         b.set_material(mat);
         let b = Arc::new(b);
 
         let mut w = World::new();
         w.add_obj(a.clone());
         w.add_obj(b.clone());
-        let l= Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white());
+        let l= Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white()).unwrap();
         w.add_light(l);
 
         let r = Ray::new(Coord::point(0.0, 0.0, 0.1), Coord::vec(0.0, 1.0, 0.0));
         let xs = vec![
-            Intersection::new(-0.9899, a.clone(), Coord::vec(0.0, 0.0, 0.0)),
-            Intersection::new(-0.4899, b.clone(), Coord::vec(0.0, 0.0, 0.0)),
-            Intersection::new(0.4899, b.clone(), Coord::vec(0.0, 0.0, 0.0)),
-            Intersection::new(0.9899, a.clone(), Coord::vec(0.0, 0.0, 0.0))
+            Intersection::new(-0.9899, a.clone()),
+            Intersection::new(-0.4899, b.clone()),
+            Intersection::new(0.4899, b.clone()),
+            Intersection::new(0.9899, a.clone())
         ];
         let comps = Comps::prepare_computations(xs[2].clone(), r, xs);
         assert_eq!(w.refracted_color(comps, 0), Color::new(0.0, 0.9988119, 0.048732005, 0.0))
@@ -714,26 +1136,26 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     fn test_shade_hit_refracted() {
         let mut w = World::default();
         let mut mat = Plane::default().get_material();
-        mat.set_transparency(0.5);
-        mat.set_refractive_index(1.5);
+        mat.set_transparency(0.5).unwrap();
+        mat.set_refractive_index(1.5).unwrap();
         let mut p = Plane::default();
         p.set_material(mat);
-        p.apply_transformation(Matrix::translation(0.0, -1.0, 0.0));
+        p.apply_transformation(Matrix::translation(0.0, -1.0, 0.0)).unwrap();
         let p = Arc::new(p);
 
         let mut mat = Sphere::default().get_material();
         mat.set_color(Color::new(1.0, 0.0, 0.0, 0.0));
-        mat.set_ambient(0.5);
+        mat.set_ambient(0.5).unwrap();
         let mut s = Sphere::default();
         s.set_material(mat);
-        s.apply_transformation(Matrix::translation(0.0, -3.5, -0.5));
+        s.apply_transformation(Matrix::translation(0.0, -3.5, -0.5)).unwrap();
         let s = Arc::new(s);
 
         w.add_obj(p.clone());
         w.add_obj(s.clone());
 
         let r = Ray::new(Coord::point(0.0, 0.0, -3.0), Coord::vec(0.0, -2_f32.sqrt()/2.0, 2_f32.sqrt()/2.0));
-        let xs = vec![Intersection::new(2_f32.sqrt(), p.clone(), Coord::vec(0.0, 0.0, 0.0))];
+        let xs = vec![Intersection::new(2_f32.sqrt(), p.clone())];
         let comps = Comps::prepare_computations(xs[0].clone(), r, xs);
         assert_eq!(w.shade_hit(comps, 0), Color::new(0.93642543, 0.68642545, 0.68642545, 0.0));
     }
@@ -743,8 +1165,8 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         let s = Arc::new(Sphere::glass_sphere());
         let r = Ray::new(Coord::point(0.0, 0.0, 2_f32.sqrt()), Coord::vec(0.0, 1.0, 0.0));
         let xs = vec![
-            Intersection::new(-2_f32.sqrt()/2.0, s.clone(), Coord::vec(0.0, 0.0, 0.0)),
-            Intersection::new(2_f32.sqrt()/2.0, s.clone(), Coord::vec(0.0, 0.0, 0.0))
+            Intersection::new(-2_f32.sqrt()/2.0, s.clone()),
+            Intersection::new(2_f32.sqrt()/2.0, s.clone())
         ];
         let comps = Comps::prepare_computations(xs[1].clone(), r, xs);
         assert_eq!(comps.schlick(), 1.0)
@@ -755,8 +1177,8 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         let s = Arc::new(Sphere::glass_sphere());
         let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 1.0, 0.0));
         let xs = vec![
-            Intersection::new(-1.0, s.clone(), Coord::vec(0.0, 0.0, 0.0)),
-            Intersection::new(1.0, s.clone(), Coord::vec(0.0, 0.0, 0.0))
+            Intersection::new(-1.0, s.clone()),
+            Intersection::new(1.0, s.clone())
         ];
         let comps = Comps::prepare_computations(xs[1].clone(), r, xs);
         assert_eq!(comps.schlick(), 0.040000003) // dam floating point errors
@@ -767,7 +1189,7 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
         let s = Arc::new(Sphere::glass_sphere());
         let r = Ray::new(Coord::point(0.0, 0.99, -2.0), Coord::vec(0.0, 0.0, 1.0));
         let xs = vec![
-            Intersection::new(1.8589, s.clone(), Coord::vec(0.0, 0.0, 0.0))
+            Intersection::new(1.8589, s.clone())
         ];
         let comps = Comps::prepare_computations(xs[0].clone(), r, xs);
         assert_eq!(comps.schlick(), 0.48873067)
@@ -777,28 +1199,28 @@ use crate::{camera::Camera, coord::Coord, light::Light, material::Material, matr
     fn test_shade_hit_refracted_and_reflected() {
         let mut w = World::default();
         let mut mat = Plane::default().get_material();
-        mat.set_transparency(0.5);
-        mat.set_refractive_index(1.5);
-        mat.set_reflection(0.5);
+        mat.set_transparency(0.5).unwrap();
+        mat.set_refractive_index(1.5).unwrap();
+        mat.set_reflection(0.5).unwrap();
         let mut p = Plane::default();
         p.set_material(mat);
-        p.apply_transformation(Matrix::translation(0.0, -1.0, 0.0));
+        p.apply_transformation(Matrix::translation(0.0, -1.0, 0.0)).unwrap();
         let p = Arc::new(p);
 
         let mut mat = Sphere::default().get_material();
         mat.set_color(Color::new(1.0, 0.0, 0.0, 0.0));
-        mat.set_ambient(0.5);
+        mat.set_ambient(0.5).unwrap();
         let mut s = Sphere::default();
         s.set_material(mat);
-        s.apply_transformation(Matrix::translation(0.0, -3.5, -0.5));
+        s.apply_transformation(Matrix::translation(0.0, -3.5, -0.5)).unwrap();
         let s = Arc::new(s);
 
         w.add_obj(p.clone());
         w.add_obj(s.clone());
 
         let r = Ray::new(Coord::point(0.0, 0.0, -3.0), Coord::vec(0.0, -2_f32.sqrt()/2.0, 2_f32.sqrt()/2.0));
-        let xs = vec![Intersection::new(2_f32.sqrt(), p.clone(), Coord::vec(0.0, -2_f32.sqrt()/2.0, 2_f32.sqrt()/2.0))];
+        let xs = vec![Intersection::new(2_f32.sqrt(), p.clone())];
         let comps = Comps::prepare_computations(xs[0].clone(), r, xs);
-        assert_eq!(w.shade_hit(comps, 0), Color::new(0.945551, 0.70107293, 0.70098877, 0.0)); // TODO: some significant floating point error propigation here compared to correct value on p164
+        assert_eq!(w.shade_hit(comps, 0), Color::new(0.9339515, 0.6964796, 0.692458, 0.0)); // the book has 0.93391, 0.69643, 0.69243 on p164
     }
 }
\ No newline at end of file
@@ -0,0 +1,290 @@
+use rand::RngExt;
+
+use crate::impl_getters_setters;
+use crate::{camera::Camera, coord::Coord, ray::Ray, renderable::Intersection, tex::color::Color};
+
+use super::World;
+
+/// decides what color a camera ray sees, `World::render_with` calls it once per pixel
+pub trait Integrator: Send + Sync {
+    /// color seen along `ray`
+    fn ray_color(&self, world: &World, ray: Ray) -> Color;
+
+    /// color of pixel (x, y), by default a single ray through its center
+    fn pixel_color(&self, world: &World, cam: &Camera, x: usize, y: usize) -> Color {
+        self.ray_color(world, cam.ray_for_pixel(x, y))
+    }
+}
+
+/// Phong lighting with perfect mirror reflection and refraction, what `render_world` has always done
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Whitted;
+
+impl Integrator for Whitted {
+    fn ray_color(&self, world: &World, ray: Ray) -> Color {
+        world.color_at(ray, 0)
+    }
+}
+
+/// averages `samples` rays per pixel, each through a random point in the pixel and sent at a random
+/// time during the camera's shutter, which anti-aliases edges and blurs moving objects. the points
+/// and times come from `World::pixel_rng` so they're the same every render
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Multisample<I: Integrator> {
+    integrator: I,
+    samples: usize,
+}
+
+impl<I: Integrator + Clone> Multisample<I> {
+    pub fn new(integrator: I, samples: usize) -> Self {
+        Self { integrator, samples }
+    }
+
+    pub fn get_integrator(&self) -> I {
+        self.integrator.clone()
+    }
+
+    pub fn get_samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn set_samples(&mut self, samples: usize) {
+        self.samples = samples;
+    }
+}
+
+impl<I: Integrator> Integrator for Multisample<I> {
+    fn ray_color(&self, world: &World, ray: Ray) -> Color {
+        self.integrator.ray_color(world, ray)
+    }
+
+    fn pixel_color(&self, world: &World, cam: &Camera, x: usize, y: usize) -> Color {
+        let mut rng = world.pixel_rng(x, y);
+        let samples = self.samples.max(1);
+        let mut color = Color::black();
+        for _ in 0..samples {
+            let ray = cam.ray_for_pixel_offset(x, y, rng.random::<f32>(), rng.random::<f32>())
+                .with_time(cam.shutter_time(rng.random::<f32>()));
+            color = color + self.integrator.ray_color(world, ray);
+        }
+        color * (1.0 / samples as f32)
+    }
+}
+
+/// what a `DebugIntegrator` draws for each pixel, misses are always black
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugMode {
+    /// world space normal mapped from [-1, 1] to [0, 1], not flipped towards the camera
+    /// so normals pointing the wrong way show up
+    Normals,
+    /// white at the camera fading to black at `max_depth`
+    Depth { max_depth: f32 },
+    /// u as red and v as green
+    Uv,
+    /// texture color of the surface without any lighting
+    Albedo,
+    /// number of surfaces in front of the camera the ray crosses, blue for one up to red for `max_count`
+    IntersectionCount { max_count: usize },
+}
+
+/// renders a single property of the first hit instead of shading it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugIntegrator {
+    mode: DebugMode,
+}
+
+impl_getters_setters!(DebugIntegrator, mode: DebugMode);
+
+impl DebugIntegrator {
+    pub fn new(mode: DebugMode) -> Self {
+        Self { mode }
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn ray_color(&self, world: &World, ray: Ray) -> Color {
+        let intersections = world.get_intersections(ray);
+        if let DebugMode::IntersectionCount { max_count } = self.get_mode() {
+            let count = intersections.iter().filter(|i| i.get_time() >= 0.0).count();
+            return heat(count, max_count);
+        }
+
+        let hit = match Intersection::find_hit(&intersections) {
+            Some(val) => val,
+            None => return Color::black(),
+        };
+        let point = ray.position(hit.get_time());
+        let object = hit.get_object();
+        match self.get_mode() {
+            DebugMode::Normals => {
+                let n = object.normal_at(point);
+                color_from_coord(n * 0.5 + Coord::vec(0.5, 0.5, 0.5))
+            },
+            DebugMode::Depth { max_depth } => {
+                let dist = hit.get_time() * ray.get_direction().magnitude();
+                let shade = 1.0 - (dist / max_depth).clamp(0.0, 1.0);
+                Color::new(shade, shade, shade, 0.0)
+            },
+            DebugMode::Uv => {
+                let (u, v) = object.uv_at(point);
+                Color::new(u, v, 0.0, 0.0)
+            },
+            DebugMode::Albedo => object.get_color_at(point),
+            DebugMode::IntersectionCount { .. } => unreachable!(),
+        }
+    }
+}
+
+fn color_from_coord(c: Coord) -> Color {
+    Color::new(c.get_x(), c.get_y(), c.get_z(), 0.0)
+}
+
+/// black for 0, then blue through to red as count approaches max_count
+fn heat(count: usize, max_count: usize) -> Color {
+    if count == 0 {
+        return Color::black();
+    }
+    let f = (count as f32 / max_count.max(1) as f32).min(1.0);
+    Color::new(f, 0.0, 1.0 - f, 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{matrix::Matrix, primitives::{cube::Cube, plane::Plane}, renderable::{Renderable, RenderableBase}};
+
+    use super::*;
+
+    fn ray() -> Ray {
+        Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0))
+    }
+
+    #[test]
+    fn test_whitted_matches_color_at() {
+        let w = World::default();
+        assert_eq!(Whitted.ray_color(&w, ray()), w.color_at(ray(), 0));
+    }
+
+    #[test]
+    fn test_miss_is_black() {
+        let w = World::new();
+        let modes = [
+            DebugMode::Normals,
+            DebugMode::Depth { max_depth: 10.0 },
+            DebugMode::Uv,
+            DebugMode::Albedo,
+            DebugMode::IntersectionCount { max_count: 4 },
+        ];
+        for mode in modes {
+            assert_eq!(DebugIntegrator::new(mode).ray_color(&w, ray()), Color::black());
+        }
+    }
+
+    #[test]
+    fn test_normals() {
+        // ray hits the outer sphere of the default world at (0, 0, -1)
+        let w = World::default();
+        let c = DebugIntegrator::new(DebugMode::Normals).ray_color(&w, ray());
+        assert_eq!(c, Color::new(0.5, 0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_normals_of_rotated_cube() {
+        let mut w = World::new();
+        let mut cube = Cube::default();
+        cube.set_transformation(Matrix::rotation_y(std::f32::consts::PI / 2.0)).unwrap();
+        w.add_obj(Arc::new(cube));
+        let c = DebugIntegrator::new(DebugMode::Normals).ray_color(&w, ray());
+        assert!((c.get_r() - 0.5).abs() < 0.0001);
+        assert!((c.get_g() - 0.5).abs() < 0.0001);
+        assert!(c.get_b().abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_depth() {
+        let w = World::default();
+        let c = DebugIntegrator::new(DebugMode::Depth { max_depth: 8.0 }).ray_color(&w, ray());
+        assert_eq!(c, Color::new(0.5, 0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_uv() {
+        let mut w = World::new();
+        w.add_obj(Arc::new(Plane::default()));
+        let r = Ray::new(Coord::point(0.25, 1.0, 2.75), Coord::vec(0.0, -1.0, 0.0));
+        let c = DebugIntegrator::new(DebugMode::Uv).ray_color(&w, r);
+        assert_eq!(c, Color::new(0.25, 0.75, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_albedo() {
+        let w = World::default();
+        let c = DebugIntegrator::new(DebugMode::Albedo).ray_color(&w, ray());
+        assert_eq!(c, Color::new(0.8, 1.0, 0.6, 0.0));
+    }
+
+    #[test]
+    fn test_intersection_count() {
+        // default world has two spheres one inside the other, so 4 crossings
+        let w = World::default();
+        let c = DebugIntegrator::new(DebugMode::IntersectionCount { max_count: 4 }).ray_color(&w, ray());
+        assert_eq!(c, Color::red());
+        let c = DebugIntegrator::new(DebugMode::IntersectionCount { max_count: 8 }).ray_color(&w, ray());
+        assert_eq!(c, Color::new(0.5, 0.0, 0.5, 0.0));
+    }
+
+    fn moving_camera() -> Camera {
+        let mut c = Camera::new(11, 11, std::f32::consts::PI / 2.0);
+        c.set_transformation(Matrix::view_transformation(
+            Coord::point(0.0, 0.0, -5.0),
+            Coord::point(0.0, 0.0, 0.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        )).unwrap();
+        c
+    }
+
+    fn moving_world() -> World {
+        // a red sphere leaving the center of the image over the first quarter of the shutter
+        let mut w = World::new();
+        let mut sphere = <crate::primitives::sphere::Sphere as Renderable>::default();
+        let mut mat = crate::material::Material::default();
+        mat.set_color(Color::red());
+        sphere.set_material(mat);
+        let motion = crate::motion::Motion::linear(Matrix::identity(4), Matrix::translation(4.0, 0.0, 0.0)).unwrap();
+        w.add_obj(Arc::new(crate::primitives::moving::Moving::new(Arc::new(sphere), motion)));
+        w
+    }
+
+    #[test]
+    fn test_multisample_without_shutter() {
+        let w = moving_world();
+        let ms = Multisample::new(DebugIntegrator::new(DebugMode::Albedo), 16);
+        assert_eq!(ms.pixel_color(&w, &moving_camera(), 5, 5), Color::red());
+    }
+
+    #[test]
+    fn test_motion_blur() {
+        let w = moving_world();
+        let mut c = moving_camera();
+        c.set_shutter(0.0, 1.0).unwrap();
+        let ms = Multisample::new(DebugIntegrator::new(DebugMode::Albedo), 256);
+        let color = ms.pixel_color(&w, &c, 5, 5);
+        assert!(color.get_r() > 0.1 && color.get_r() < 0.5, "{color:?}");
+        assert_eq!(color.get_g(), 0.0);
+    }
+
+    #[test]
+    fn test_render_with() {
+        let w = World::default();
+        let mut c = Camera::new(11, 11, std::f32::consts::PI / 2.0);
+        c.set_transformation(Matrix::view_transformation(
+            Coord::point(0.0, 0.0, -5.0),
+            Coord::point(0.0, 0.0, 0.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        )).unwrap();
+        let image = w.render_with(&c, &DebugIntegrator::new(DebugMode::Albedo));
+        assert_eq!(image.get_pixel(5, 5), Color::new(0.8, 1.0, 0.6, 0.0));
+        assert_eq!(image.get_pixel(0, 0), Color::black());
+    }
+}
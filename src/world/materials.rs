@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use crate::error::{Result, RtcError};
+use crate::material::{Material, Preset};
+
+use super::{ObjectId, World};
+
+#[allow(dead_code)]
+impl World {
+    /// adds `material` to the world's library under `name` and returns the shared handle, objects
+    /// given it with `assign_material` or `RenderableBase::set_shared_material` all follow
+    /// `update_material`. returns an error if the name is taken
+    pub fn add_material(&mut self, name: &str, material: Material) -> Result<Arc<Material>> {
+        if self.get_material(name).is_some() {
+            return Err(RtcError::DuplicateMaterial(name.to_string()));
+        }
+        let shared = Arc::new(material);
+        self.materials.push((name.to_string(), shared.clone()));
+        Ok(shared)
+    }
+
+    /// adds every `Preset` under its name, returns an error if one of the names is taken
+    pub fn add_presets(&mut self) -> Result<()> {
+        for preset in Preset::ALL {
+            self.add_material(preset.name(), Material::preset(preset))?;
+        }
+        Ok(())
+    }
+
+    pub fn get_material(&self, name: &str) -> Option<Arc<Material>> {
+        self.materials.iter().find(|(n, _)| n == name).map(|(_, m)| m.clone())
+    }
+
+    /// names in the library, in the order they were added
+    pub fn get_material_names(&self) -> Vec<String> {
+        self.materials.iter().map(|(n, _)| n.clone()).collect()
+    }
+
+    /// gives the object the library's material, shared rather than copied
+    pub fn assign_material(&mut self, id: ObjectId, name: &str) -> Result<()> {
+        let material = self.get_material(name).ok_or_else(|| RtcError::UnknownMaterial(name.to_string()))?;
+        let i = self.position(id)?;
+        let mut obj = self.objects[i].clone_dyn();
+        obj.set_shared_material(material);
+        self.objects[i] = Arc::from(obj);
+        Ok(())
+    }
+
+    /// replaces a material in the library, every object sharing the old one gets the new one
+    pub fn update_material(&mut self, name: &str, material: Material) -> Result<()> {
+        let slot = self.materials.iter_mut()
+            .find(|(n, _)| n == name)
+            .ok_or_else(|| RtcError::UnknownMaterial(name.to_string()))?;
+        let old = std::mem::replace(&mut slot.1, Arc::new(material));
+        let new = slot.1.clone();
+        // objects can't be changed in place, so the ones using it are swapped for updated copies
+        for obj in self.objects.iter_mut().filter(|obj| Arc::ptr_eq(&obj.get_shared_material(), &old)) {
+            let mut updated = obj.clone_dyn();
+            updated.set_shared_material(new.clone());
+            *obj = Arc::from(updated);
+        }
+        Ok(())
+    }
+
+    /// takes the material out of the library, objects using it keep it
+    pub fn remove_material(&mut self, name: &str) -> Result<Arc<Material>> {
+        let i = self.materials.iter()
+            .position(|(n, _)| n == name)
+            .ok_or_else(|| RtcError::UnknownMaterial(name.to_string()))?;
+        Ok(self.materials.remove(i).1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{coord::Coord, matrix::Matrix, primitives::{cube::Cube, sphere::Sphere}, renderable::RenderableBase, tex::color::Color};
+
+    use super::*;
+
+    #[test]
+    fn test_shared_material() {
+        let mut w = World::new();
+        let clay = w.add_material("clay", Material::preset(Preset::MatteClay)).unwrap();
+        let a = w.add_obj(Arc::new(Sphere::new(Coord::point(0.0, 0.0, 0.0)).unwrap()));
+        let b = w.add_obj(Arc::new(Cube::new(Matrix::identity(4), Material::default()).unwrap()));
+        let mut own = Sphere::new(Coord::point(0.0, 0.0, 0.0)).unwrap();
+        own.set_material(Material::preset(Preset::MatteClay));
+        let c = w.add_obj(Arc::new(own));
+        w.assign_material(a, "clay").unwrap();
+        w.assign_material(b, "clay").unwrap();
+        assert!(Arc::ptr_eq(&w.get_obj(a).unwrap().get_shared_material(), &clay));
+        assert!(Arc::ptr_eq(&w.get_obj(b).unwrap().get_shared_material(), &clay));
+
+        let mut red = Material::preset(Preset::MatteClay);
+        red.set_color(Color::red());
+        w.update_material("clay", red).unwrap();
+        let shared = w.get_material("clay").unwrap();
+        for id in [a, b] {
+            let obj = w.get_obj(id).unwrap();
+            assert!(Arc::ptr_eq(&obj.get_shared_material(), &shared));
+            assert_eq!(obj.get_material().get_color(), Color::red());
+        }
+        // an object with a material of its own isn't touched, even an equal one
+        assert_eq!(w.get_obj(c).unwrap().get_material(), Material::preset(Preset::MatteClay));
+        assert_eq!(w.get_ids(), vec![a, b, c]);
+    }
+
+    #[test]
+    fn test_library() {
+        let mut w = World::new();
+        w.add_presets().unwrap();
+        assert_eq!(w.get_material_names(), ["glass", "water", "diamond", "chrome", "gold", "plastic", "matte_clay"]);
+        assert_eq!(w.get_material("water").unwrap().get_refractive_index(), 1.333);
+        assert_eq!(w.add_material("gold", Material::default()).err(), Some(RtcError::DuplicateMaterial("gold".to_string())));
+
+        let id = w.add_obj(Arc::new(Sphere::new(Coord::point(0.0, 0.0, 0.0)).unwrap()));
+        w.assign_material(id, "diamond").unwrap();
+        let diamond = w.remove_material("diamond").unwrap();
+        assert!(w.get_material("diamond").is_none());
+        assert!(Arc::ptr_eq(&w.get_obj(id).unwrap().get_shared_material(), &diamond));
+
+        let unknown = Some(RtcError::UnknownMaterial("diamond".to_string()));
+        assert_eq!(w.assign_material(id, "diamond").err(), unknown);
+        assert_eq!(w.update_material("diamond", Material::default()).err(), unknown);
+        assert_eq!(w.remove_material("diamond").err(), unknown);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::{coord::Coord, light::Light, material::beer_lambert, medium::{Medium, Volume}, ray::Ray, tex::color::Color};
+
+use super::World;
+
+/// how far a ray that hits nothing is followed through fog, once it is this thick nothing behind
+/// it would be seen anyway
+const FOG_CUTOFF: f32 = 1000.0;     // 1 / the transmittance at the cutoff
+const MAX_FOG_DISTANCE: f32 = 1000.0;
+
+/// a stretch of ray t where the same media overlap
+struct Segment {
+    start: f32,
+    end: f32,
+    media: Vec<Medium>,
+}
+
+#[allow(dead_code)]
+impl World {
+    pub fn get_fog(&self) -> Option<Medium> {
+        self.fog
+    }
+
+    /// a medium filling all the space outside of volumes, `None` for clear air
+    pub fn set_fog(&mut self, fog: Option<Medium>) {
+        self.fog = fog;
+    }
+
+    pub fn get_volumes(&self) -> &Vec<Volume> {
+        &self.volumes
+    }
+
+    pub fn add_volume(&mut self, volume: Volume) {
+        self.volumes.push(volume);
+    }
+
+    pub fn clear_volumes(&mut self) {
+        self.volumes.clear();
+    }
+
+    fn has_media(&self) -> bool {
+        self.fog.is_some() || !self.volumes.is_empty()
+    }
+
+    /// stretches of `ray` between t = 0 and `t_end` inside any media, split wherever media begin
+    /// or end. fog fills all of it, overlapping with any volumes
+    fn segments(&self, ray: Ray, t_end: f32) -> Vec<Segment> {
+        let mut spans: Vec<(f32, f32, Medium)> = Vec::new();
+        if let Some(fog) = self.fog {
+            spans.push((0.0, t_end, fog));
+        }
+        for volume in &self.volumes {
+            for (start, end) in volume.spans(ray) {
+                let (start, end) = (start.max(0.0), end.min(t_end));
+                if start < end {
+                    spans.push((start, end, volume.get_medium()));
+                }
+            }
+        }
+
+        let mut cuts: Vec<f32> = spans.iter().flat_map(|(start, end, _)| [*start, *end]).collect();
+        cuts.sort_by(f32::total_cmp);
+        cuts.dedup();
+        cuts.windows(2).filter_map(|w| {
+            let mid = (w[0] + w[1]) / 2.0;
+            let media: Vec<Medium> = spans.iter()
+                .filter(|(start, end, _)| *start <= mid && mid <= *end)
+                .map(|(_, _, medium)| *medium)
+                .collect();
+            (!media.is_empty()).then_some(Segment { start: w[0], end: w[1], media })
+        }).collect()
+    }
+
+    /// how far to follow a ray that hits nothing
+    fn far_t(&self, ray: Ray) -> f32 {
+        let speed = ray.get_direction().magnitude();
+        let fog_end = self.fog.map_or(0.0, |fog| {
+            let e = fog.extinction();
+            let thinnest = e.get_r().min(e.get_g()).min(e.get_b());
+            if thinnest > 0.0 { (FOG_CUTOFF.ln() / thinnest).min(MAX_FOG_DISTANCE) } else { MAX_FOG_DISTANCE }
+        }) / speed;
+        self.volumes.iter()
+            .flat_map(|v| v.spans(ray))
+            .map(|(_, end)| end)
+            .fold(fog_end, f32::max)
+    }
+
+    /// fraction of the light from `light` that makes it through the media to `p`, shadows from
+    /// surfaces aren't included
+    pub(crate) fn light_transmittance(&self, p: Coord, light: &Light) -> Color {
+        let mut out = Color::new(1.0, 1.0, 1.0, 0.0);
+        if !self.has_media() {
+            return out;
+        }
+        // t runs from 0 at p to 1 at the light
+        let ray = Ray::new(p, light.get_pos() - p);
+        let speed = ray.get_direction().magnitude();
+        for segment in self.segments(ray, 1.0) {
+            let extinction = segment.media.iter().fold(Color::black(), |acc, m| acc + m.extinction());
+            out = out * beer_lambert(extinction, (segment.end - segment.start) * speed);
+        }
+        out
+    }
+
+    /// `light` dimmed by the media between it and `p`
+    pub(crate) fn attenuated_light(&self, p: Coord, light: &Light) -> Light {
+        let mut out = *light;
+        if self.has_media() {
+            out.set_intensity(light.get_intensity() * self.light_transmittance(p, light))
+                .expect("attenuated intensity is never negative");
+        }
+        out
+    }
+
+    /// what the media along `ray` do to the light coming back along it from `t_hit` (or from
+    /// far away when it hits nothing): the fraction of that light left, and the light scattered
+    /// towards the ray's origin from the lights along the way
+    ///
+    /// single scattering only, each segment is ray marched in `volume_steps` steps with a shadow
+    /// ray to every light, so objects in the media cast shafts of shadow through them
+    pub(crate) fn media_along(&self, ray: Ray, t_hit: Option<f32>) -> (Color, Color) {
+        let mut transmittance = Color::new(1.0, 1.0, 1.0, 0.0);
+        let mut scattered = Color::black();
+        if !self.has_media() {
+            return (transmittance, scattered);
+        }
+        let speed = ray.get_direction().magnitude();
+        let dir = ray.get_direction().normalized();
+        let t_end = t_hit.unwrap_or_else(|| self.far_t(ray));
+        let steps = self.get_volume_steps().max(1);
+
+        for segment in self.segments(ray, t_end) {
+            let extinction = segment.media.iter().fold(Color::black(), |acc, m| acc + m.extinction());
+            let dt = (segment.end - segment.start) / steps as f32;
+            for i in 0..steps {
+                let t = segment.start + (i as f32 + 0.5) * dt;
+                let p = ray.position(t);
+                let to_here = transmittance * beer_lambert(extinction, (t - segment.start) * speed);
+                for light in self.get_light() {
+                    if self.in_shadow_of(p, light, ray.get_time()) {
+                        continue;
+                    }
+                    // light travels from the light to p, then back along the ray
+                    let cos = (light.get_pos() - p).normalized().dot(dir);
+                    let phase = segment.media.iter()
+                        .fold(Color::black(), |acc, m| acc + m.get_scattering() * m.get_phase().eval(cos));
+                    scattered = scattered + to_here * phase * light.get_intensity() * self.light_transmittance(p, light) * (dt * speed);
+                }
+            }
+            transmittance = transmittance * beer_lambert(extinction, (segment.end - segment.start) * speed);
+        }
+        (transmittance, scattered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f32::consts::{LN_2, PI}, sync::Arc};
+
+    use crate::{matrix::Matrix, medium::PhaseFunction, primitives::{cube::Cube, plane::Plane, sphere::Sphere}, renderable::{Renderable, RenderableBase}};
+
+    use super::*;
+
+    fn close(a: f32, b: f32, tolerance: f32) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn test_no_media() {
+        let w = World::default();
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        assert_eq!(w.media_along(r, Some(4.0)), (Color::new(1.0, 1.0, 1.0, 0.0), Color::black()));
+        let light = w.get_light()[0];
+        assert_eq!(w.attenuated_light(Coord::point(0.0, 0.0, -5.0), &light), light);
+    }
+
+    #[test]
+    fn test_fog_transmittance() {
+        let mut w = World::new();
+        w.set_fog(Some(Medium::new(Color::new(LN_2, 0.0, 0.0, 0.0), Color::black(), PhaseFunction::Isotropic).unwrap()));
+        let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 2.0));
+        let (t, s) = w.media_along(r, Some(1.0));
+        assert_eq!(t, Color::new(0.25, 1.0, 1.0, 0.0));
+        assert_eq!(s, Color::black());
+        // nothing to scatter without lights, and nothing comes back from infinitely far away
+        let (t, _) = w.media_along(r, None);
+        assert!(t.get_r() < 0.01);
+    }
+
+    #[test]
+    fn test_volume_transmittance() {
+        let mut w = World::new();
+        let mut cube = Cube::default();
+        cube.set_transformation(Matrix::translation(0.0, 0.0, 5.0)).unwrap();
+        w.add_volume(Volume::new(Arc::new(cube), Medium::fog(LN_2).unwrap()));
+        let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0));
+        // the cube is 2 thick
+        assert!(close(w.media_along(r, Some(10.0)).0.get_r(), 0.25, 0.0001));
+        // part of the way through
+        assert_eq!(w.media_along(r, Some(5.0)).0, Color::new(0.5, 0.5, 0.5, 0.0));
+        // the hit is in front of the volume
+        assert_eq!(w.media_along(r, Some(3.0)).0, Color::new(1.0, 1.0, 1.0, 0.0));
+        let light = Light::new(Coord::point(0.0, 0.0, 10.0), Color::white()).unwrap();
+        assert!(close(w.attenuated_light(Coord::point(0.0, 0.0, 0.0), &light).get_intensity().get_g(), 0.25, 0.0001));
+    }
+
+    #[test]
+    fn test_in_scattering() {
+        // thin isotropic fog over a short stretch, lit from the side at a distance of 1
+        let density = 0.01;
+        let mut w = World::new();
+        w.set_fog(Some(Medium::fog(density).unwrap()));
+        w.add_light(Light::new(Coord::point(0.0, 1000.0, 0.0), Color::white()).unwrap());
+        let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0));
+        let (_, s) = w.media_along(r, Some(1.0));
+        // about density * phase * length, less a little lost on the way from the light
+        let expected = density / (4.0 * PI) * (-density * 1000.0_f32).exp();
+        assert!(close(s.get_r(), expected, expected * 0.02), "{s:?} {expected}");
+    }
+
+    #[test]
+    fn test_forward_scattering() {
+        let mut w = World::new();
+        w.add_light(Light::new(Coord::point(0.0, 0.0, 10.0), Color::white()).unwrap());
+        let hg = Medium::new(Color::black(), Color::new(0.1, 0.1, 0.1, 0.0), PhaseFunction::HenyeyGreenstein { g: 0.8 }).unwrap();
+        w.set_fog(Some(hg));
+        let towards = w.media_along(Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0)), Some(1.0)).1;
+        let away = w.media_along(Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, -1.0)), Some(1.0)).1;
+        assert!(towards.get_r() > 10.0 * away.get_r());
+    }
+
+    #[test]
+    fn test_light_shaft() {
+        // a wall with fog on both sides, the light only reaches above it
+        let mut w = World::new();
+        w.set_fog(Some(Medium::fog(0.05).unwrap()));
+        w.add_light(Light::new(Coord::point(0.0, 0.0, -10.0), Color::white()).unwrap());
+        let mut wall = Cube::default();
+        wall.set_transformation(Matrix::translation(0.0, -2.0, -5.0) * Matrix::scaling(10.0, 2.0, 0.1)).unwrap();
+        w.add_obj(Arc::new(wall));
+        let lit = w.media_along(Ray::new(Coord::point(-1.0, 1.0, 0.0), Coord::vec(1.0, 0.0, 0.0)), Some(2.0)).1;
+        let dark = w.media_along(Ray::new(Coord::point(-1.0, -1.0, 0.0), Coord::vec(1.0, 0.0, 0.0)), Some(2.0)).1;
+        assert!(lit.get_r() > 0.0);
+        assert_eq!(dark, Color::black());
+    }
+
+    #[test]
+    fn test_color_at_through_fog() {
+        let mut backdrop_mat = crate::material::Material::default();
+        backdrop_mat.set_ambient(1.0).unwrap();
+        backdrop_mat.set_diffuse(0.0).unwrap();
+        backdrop_mat.set_specular(0.0).unwrap();
+        let backdrop = Plane::new(Matrix::translation(0.0, 0.0, 2.0) * Matrix::rotation_x(PI / 2.0), backdrop_mat).unwrap();
+        let mut w = World::new();
+        w.add_obj(Arc::new(backdrop));
+        w.add_light(Light::new(Coord::point(0.0, 10.0, 10.0), Color::white()).unwrap());
+        let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0));
+        let clear = w.color_at(r, 0);
+        // a slab of smoke 1 thick between the camera and the backdrop, but not in front of the light
+        let mut slab = Cube::default();
+        slab.set_transformation(Matrix::translation(0.0, 0.0, 1.0) * Matrix::scaling(1.0, 1.0, 0.5)).unwrap();
+        let smoke = Medium::new(Color::new(LN_2, LN_2, LN_2, 0.0), Color::black(), PhaseFunction::Isotropic).unwrap();
+        w.add_volume(Volume::new(Arc::new(slab), smoke));
+        let smoky = w.color_at(r, 0);
+        assert!(close(smoky.get_r(), clear.get_r() * 0.5, 0.001), "{clear:?} {smoky:?}");
+
+        // a fog filled glass ball seen against nothing glows from the scattered light
+        let mut w = World::new();
+        w.add_light(Light::new(Coord::point(0.0, 10.0, 0.0), Color::white()).unwrap());
+        w.add_volume(Volume::new(Arc::new(Sphere::default()), Medium::fog(0.5).unwrap()));
+        assert!(w.color_at(Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0)), 0).get_r() > 0.0);
+        assert_eq!(w.color_at(Ray::new(Coord::point(0.0, 5.0, -5.0), Coord::vec(0.0, 0.0, 1.0)), 0), Color::black());
+    }
+}
@@ -0,0 +1,266 @@
+use rayon::prelude::*;
+
+use crate::impl_getters;
+use crate::{camera::Camera, canvas::Canvas, renderable::Intersection, tex::color::Color};
+
+use super::{Comps, ObjectId, World};
+
+/// the beauty image and its auxiliary buffers (AOVs), all the same size and pixel aligned
+///
+/// depth and normals are stored as raw values (distance along the ray, world space normal in
+/// [-1, 1]) rather than remapped for display, so they may need scaling before being written to a
+/// file. `direct + reflected + refracted` is always `beauty`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderPasses {
+    beauty: Canvas,
+    depth: Canvas,
+    normals: Canvas,
+    albedo: Canvas,
+    object_id: Canvas,
+    direct: Canvas,
+    reflected: Canvas,
+    refracted: Canvas,
+    ids: Vec<Vec<Option<ObjectId>>>,
+}
+
+impl_getters!(RenderPasses,
+    beauty: Canvas,
+    depth: Canvas,
+    normals: Canvas,
+    albedo: Canvas,
+    object_id: Canvas,
+    direct: Canvas,
+    reflected: Canvas,
+    refracted: Canvas
+);
+
+/// everything recorded for one pixel, misses leave it all black with no id
+#[derive(Clone, Copy)]
+struct PassSample {
+    depth: f32,
+    normal: Color,
+    albedo: Color,
+    id: Option<ObjectId>,
+    direct: Color,
+    reflected: Color,
+    refracted: Color,
+}
+
+impl PassSample {
+    fn miss() -> Self {
+        Self {
+            depth: 0.0,
+            normal: Color::black(),
+            albedo: Color::black(),
+            id: None,
+            direct: Color::black(),
+            reflected: Color::black(),
+            refracted: Color::black(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl RenderPasses {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            beauty: Canvas::new(width, height),
+            depth: Canvas::new(width, height),
+            normals: Canvas::new(width, height),
+            albedo: Canvas::new(width, height),
+            object_id: Canvas::new(width, height),
+            direct: Canvas::new(width, height),
+            reflected: Canvas::new(width, height),
+            refracted: Canvas::new(width, height),
+            ids: vec![vec![None; width]; height],
+        }
+    }
+
+    fn set_sample(&mut self, x: usize, y: usize, sample: PassSample) {
+        self.beauty.set_pixel(x, y, sample.direct + sample.reflected + sample.refracted);
+        self.depth.set_pixel(x, y, Color::new(sample.depth, sample.depth, sample.depth, 0.0));
+        self.normals.set_pixel(x, y, sample.normal);
+        self.albedo.set_pixel(x, y, sample.albedo);
+        self.object_id.set_pixel(x, y, sample.id.map_or(Color::black(), id_color));
+        self.direct.set_pixel(x, y, sample.direct);
+        self.reflected.set_pixel(x, y, sample.reflected);
+        self.refracted.set_pixel(x, y, sample.refracted);
+        self.ids[y][x] = sample.id;
+    }
+
+    /// id of whatever is visible at (x, y)
+    pub fn get_id_at(&self, x: usize, y: usize) -> Option<ObjectId> {
+        self.ids[y][x]
+    }
+
+    /// white wherever the object `id` is visible, black everywhere else
+    pub fn object_mask(&self, id: ObjectId) -> Canvas {
+        let mut out = Canvas::new(self.beauty.get_width(), self.beauty.get_height());
+        for (y, row) in self.ids.iter().enumerate() {
+            for (x, val) in row.iter().enumerate() {
+                if *val == Some(id) {
+                    out.set_pixel(x, y, Color::white());
+                }
+            }
+        }
+        out
+    }
+
+    /// writes every pass to `{prefix}_{pass}.ppm`
+    pub fn to_files(&self, prefix: &str) -> std::io::Result<()> {
+        let passes = [
+            ("beauty", &self.beauty),
+            ("depth", &self.depth),
+            ("normals", &self.normals),
+            ("albedo", &self.albedo),
+            ("object_id", &self.object_id),
+            ("direct", &self.direct),
+            ("reflected", &self.reflected),
+            ("refracted", &self.refracted),
+        ];
+        for (name, canvas) in passes {
+            canvas.to_file(&format!("{prefix}_{name}.ppm"))?;
+        }
+        Ok(())
+    }
+}
+
+/// a distinct flat color per id, hues are spaced by the golden angle so neighbouring ids differ
+fn id_color(id: ObjectId) -> Color {
+    let hue = (id.0 as f32 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    match hue as usize {
+        0 => Color::new(1.0, x, 0.0, 0.0),
+        1 => Color::new(x, 1.0, 0.0, 0.0),
+        2 => Color::new(0.0, 1.0, x, 0.0),
+        3 => Color::new(0.0, x, 1.0, 0.0),
+        4 => Color::new(x, 0.0, 1.0, 0.0),
+        _ => Color::new(1.0, 0.0, x, 0.0),
+    }
+}
+
+#[allow(dead_code)]
+impl World {
+    /// renders the Whitted image along with its AOVs in a single pass over the pixels
+    pub fn render_passes(&self, cam: &Camera) -> RenderPasses {
+        let samples: Vec<(usize, usize, PassSample)> = (0..cam.get_vsize())
+            .into_par_iter()
+            .flat_map(|y| {
+                (0..cam.get_hsize())
+                    .into_par_iter()
+                    .map(move |x| (x, y, self.pass_sample(cam, x, y)))
+            })
+            .collect();
+        let mut out = RenderPasses::new(cam.get_hsize(), cam.get_vsize());
+        for (x, y, sample) in samples {
+            out.set_sample(x, y, sample);
+        }
+        out
+    }
+
+    fn pass_sample(&self, cam: &Camera, x: usize, y: usize) -> PassSample {
+        if self.get_max_depth() == 0 {
+            return PassSample::miss();
+        }
+        let ray = cam.ray_for_pixel(x, y);
+        let intersections = self.get_intersections(ray);
+        let hit = match Intersection::find_hit(&intersections) {
+            Some(val) => val.clone(),
+            None => return PassSample::miss(),
+        };
+        let comps = Comps::prepare_computations(hit.clone(), ray, intersections);
+        let object = comps.get_object();
+        let normal = comps.get_normalv();
+        let (direct, reflected, refracted) = self.shade_hit_parts(comps.clone(), 0);
+        PassSample {
+            depth: hit.get_time() * ray.get_direction().magnitude(),
+            normal: Color::new(normal.get_x(), normal.get_y(), normal.get_z(), 0.0),
+            albedo: object.get_color_at(comps.get_point()),
+            id: self.object_id(&comps.get_owner()),
+            direct,
+            reflected,
+            refracted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{coord::Coord, material::Material, matrix::Matrix, primitives::plane::Plane, world::Whitted};
+
+    use super::*;
+
+    fn camera() -> Camera {
+        let mut c = Camera::new(11, 11, std::f32::consts::PI / 2.0);
+        c.set_transformation(Matrix::view_transformation(
+            Coord::point(0.0, 0.0, -5.0),
+            Coord::point(0.0, 0.0, 0.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        )).unwrap();
+        c
+    }
+
+    #[test]
+    fn test_beauty_matches_whitted() {
+        let w = World::default();
+        let passes = w.render_passes(&camera());
+        assert_eq!(passes.get_beauty(), w.render_with(&camera(), &Whitted));
+    }
+
+    #[test]
+    fn test_center_pixel() {
+        let w = World::default();
+        let passes = w.render_passes(&camera());
+        assert_eq!(passes.get_depth().get_pixel(5, 5), Color::new(4.0, 4.0, 4.0, 0.0));
+        assert_eq!(passes.get_normals().get_pixel(5, 5), Color::new(0.0, 0.0, -1.0, 0.0));
+        assert_eq!(passes.get_albedo().get_pixel(5, 5), Color::new(0.8, 1.0, 0.6, 0.0));
+        assert_eq!(passes.get_id_at(5, 5), Some(w.get_ids()[0]));
+        assert_eq!(passes.get_object_id().get_pixel(5, 5), id_color(w.get_ids()[0]));
+        assert_eq!(passes.get_direct().get_pixel(5, 5), Color::new(0.38066125, 0.4758265, 0.28549594, 0.0));
+        assert_eq!(passes.get_reflected().get_pixel(5, 5), Color::black());
+        assert_eq!(passes.get_refracted().get_pixel(5, 5), Color::black());
+    }
+
+    #[test]
+    fn test_miss() {
+        let w = World::default();
+        let passes = w.render_passes(&camera());
+        assert_eq!(passes.get_id_at(0, 0), None);
+        assert_eq!(passes.get_depth().get_pixel(0, 0), Color::black());
+        let outer = w.get_ids()[0];
+        assert_eq!(passes.object_mask(outer).get_pixel(0, 0), Color::black());
+        assert_eq!(passes.object_mask(outer).get_pixel(5, 5), Color::white());
+    }
+
+    #[test]
+    fn test_reflected_pass() {
+        // same setup as world's test_reflection, the center pixel sees the mirror floor
+        let mut w = World::default();
+        let mut mat = Material::default();
+        mat.set_reflection(0.5).unwrap();
+        let floor = w.add_obj(Arc::new(Plane::new(Matrix::translation(0.0, -1.0, 0.0), mat).unwrap()));
+        let mut c = Camera::new(11, 11, std::f32::consts::PI / 2.0);
+        c.set_transformation(Matrix::view_transformation(
+            Coord::point(0.0, 0.0, -3.0),
+            Coord::point(0.0, -1.0, -2.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        )).unwrap();
+        let passes = w.render_passes(&c);
+        assert_eq!(passes.get_id_at(5, 5), Some(floor));
+        let reflected = passes.get_reflected().get_pixel(5, 5);
+        assert!((reflected.get_r() - 0.1911927).abs() < 0.0001);
+        assert!((reflected.get_g() - 0.23899086).abs() < 0.0001);
+        assert!((reflected.get_b() - 0.14339453).abs() < 0.0001);
+        assert_eq!(passes.get_refracted().get_pixel(5, 5), Color::black());
+    }
+
+    #[test]
+    fn test_id_colors_differ() {
+        for i in 0..8 {
+            assert_ne!(id_color(ObjectId(i)), id_color(ObjectId(i + 1)));
+            assert_ne!(id_color(ObjectId(i)), Color::black());
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use rand::{Rng, RngExt};
+
+use crate::impl_getters_setters;
+use crate::{camera::Camera, coord::Coord, light::Light, material::{Material, MaterialParam, ShadingModel}, microfacet, ray::Ray, renderable::{Intersection, Renderable}, tex::color::Color};
+
+use super::{Comps, EPSILON, Integrator, World};
+
+/// Monte Carlo path tracer, an alternative to the Whitted style `World::color_at`
+///
+/// diffuse bounces are cosine weighted, direct light is gathered at every hit with next event
+/// estimation (point lights can never be hit by chance) and paths are ended with Russian roulette
+/// once they've made `rr_depth` bounces. Existing `Material`s are used as is:
+/// * `diffuse` * texture color is the albedo of the diffuse lobe
+/// * `specular` and `shininess` give the Phong highlight for direct light only
+/// * `reflection` and `transparency` are perfect mirror and refraction lobes, weighted by Fresnel
+///   when both are set, the same way `shade_hit` does it
+/// * absorbing materials attenuate the path by the distance it travels inside them
+/// * fog and volumes add single scattered light along each segment of the path (the same ray
+///   marching as `World::color_at`) rather than scattering the path itself
+/// * emissive materials glow, and closed shapes that can be sampled (spheres, cubes, bounded
+///   cylinders) are also picked as area lights from diffuse surfaces
+/// * `ambient` is ignored, indirect light replaces it
+/// * `ShadingModel::MetallicRoughness` materials get a GGX importance sampled glossy lobe, their
+///   diffuse lobe is weighted by the Fresnel of the view direction rather than the half vector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathTracer {
+    samples_per_pixel: usize,
+    max_bounces: usize,
+    rr_depth: usize,
+}
+
+impl_getters_setters!(PathTracer, samples_per_pixel: usize, max_bounces: usize, rr_depth: usize);
+
+#[allow(dead_code)]
+impl PathTracer {
+    pub fn new(samples_per_pixel: usize, max_bounces: usize, rr_depth: usize) -> Self {
+        Self { samples_per_pixel, max_bounces, rr_depth }
+    }
+
+    /// average of `samples_per_pixel` paths through random points inside the pixel, sent at random
+    /// times during the camera's shutter
+    fn sample_pixel<R: Rng + ?Sized>(&self, world: &World, cam: &Camera, x: usize, y: usize, rng: &mut R) -> Color {
+        let samples = self.get_samples_per_pixel().max(1);
+        let emitters = sampled_emitters(world);
+        let mut color = Color::black();
+        for _ in 0..samples {
+            let ray = cam.ray_for_pixel_offset(x, y, rng.random::<f32>(), rng.random::<f32>())
+                .with_time(cam.shutter_time(rng.random::<f32>()));
+            color = color + self.trace(world, ray, &emitters, rng);
+        }
+        color * (1.0 / samples as f32)
+    }
+
+    /// follows a single path from `ray`, returning its radiance estimate. `emitters` is
+    /// `sampled_emitters(world)`, built by the caller so it isn't redone for every path
+    fn trace<R: Rng + ?Sized>(&self, world: &World, ray: Ray, emitters: &[Arc<dyn Renderable>], rng: &mut R) -> Color {
+        let mut radiance = Color::black();
+        let mut throughput = Color::white();
+        let mut ray = ray;
+        // off right after a diffuse bounce, the light of sampled emitters was already gathered
+        let mut count_emission = true;
+
+        for bounce in 0..self.get_max_bounces() {
+            let intersections = world.get_intersections(ray);
+            let hit = match Intersection::find_hit(&intersections) {
+                Some(val) => val.clone(),
+                None => {
+                    radiance = radiance + throughput * world.media_along(ray, None).1;
+                    break
+                },
+            };
+            let (fog, scattered) = world.media_along(ray, Some(hit.get_time()));
+            radiance = radiance + throughput * scattered;
+            let comps = Comps::prepare_computations(hit, ray, intersections);
+            throughput = throughput * comps.transmittance() * fog;
+            radiance = radiance + throughput * direct_light(world, &comps);
+
+            let object = comps.get_object();
+            let mat = object.get_shared_material();
+            if mat.is_emissive() && (count_emission || !emitters.iter().any(|e| Arc::ptr_eq(e, &comps.get_owner()))) {
+                radiance = radiance + throughput * mat.emitted();
+            }
+
+            // pick one lobe with probability proportional to its weight, then divide by that probability
+            let local_pos = object.get_inverse() * comps.get_point();
+            let param = |p| mat.get_param_at(p, local_pos);
+            let base = object.get_color_at(comps.get_point());
+            let (albedo, glossy) = match mat.get_model() {
+                ShadingModel::Phong => (base * param(MaterialParam::Diffuse), None),
+                ShadingModel::MetallicRoughness { .. } => {
+                    let (metallic, roughness) = (param(MaterialParam::Metallic), param(MaterialParam::Roughness));
+                    let f0 = microfacet::f0(base, metallic);
+                    let f = microfacet::fresnel(comps.get_eyev().dot(comps.get_normalv()), f0);
+                    ((Color::white() - f) * base * (1.0 - metallic), Some((microfacet::alpha(roughness), f0, f)))
+                },
+            };
+            let glossy_w = glossy.map_or(0.0, |(_, _, f)| max_channel(f));
+            let mut reflect_w = param(MaterialParam::Reflection);
+            let mut refract_w = param(MaterialParam::Transparency);
+            if reflect_w > 0.0 && refract_w > 0.0 {
+                let reflectance = comps.schlick();
+                reflect_w *= reflectance;
+                refract_w *= 1.0 - reflectance;
+            }
+            radiance = radiance + throughput * emitter_light(world, &comps, albedo, emitters, rng);
+            let diffuse_w = max_channel(albedo);
+            let total = diffuse_w + glossy_w + reflect_w + refract_w;
+            if total <= 0.0 {
+                break;
+            }
+
+            let pick = rng.random::<f32>() * total;
+            count_emission = pick >= diffuse_w;
+            if pick < diffuse_w {
+                // cosine weighted pdf cancels both the cos term and the 1/pi of the lambertian brdf
+                let dir = cosine_sample_hemisphere(comps.get_normalv(), rng.random::<f32>(), rng.random::<f32>());
+                throughput = throughput * albedo * (total / diffuse_w);
+                ray = Ray::new(comps.get_over_point(), dir).with_time(comps.get_ray_time());
+            } else if pick < diffuse_w + glossy_w {
+                // sampling the half vector by D(h) (n.h) leaves F G (v.h) / ((n.v) (n.h)) as the weight
+                let (alpha, f0, _) = glossy.unwrap();
+                let normalv = comps.get_normalv();
+                let eyev = comps.get_eyev();
+                let halfv = ggx_sample_half(normalv, alpha, rng.random::<f32>(), rng.random::<f32>());
+                let dir = (-eyev).reflect(halfv);
+                let (n_dot_l, n_dot_v) = (normalv.dot(dir), normalv.dot(eyev));
+                if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+                    break;
+                }
+                let v_dot_h = eyev.dot(halfv);
+                let weight = microfacet::fresnel(v_dot_h, f0)
+                    * (microfacet::geometry(n_dot_v, n_dot_l, alpha) * v_dot_h / (n_dot_v * normalv.dot(halfv)));
+                throughput = throughput * weight * (total / glossy_w);
+                ray = Ray::new(comps.get_over_point(), dir).with_time(comps.get_ray_time());
+            } else if pick < diffuse_w + glossy_w + reflect_w {
+                throughput = throughput * total;
+                ray = Ray::new(comps.get_over_point(), comps.get_reflectv()).with_time(comps.get_ray_time());
+            } else {
+                let dir = match comps.refracted_direction() {
+                    Some(val) => val,
+                    None => break, // full internal refraction, same as refracted_color
+                };
+                throughput = throughput * total;
+                ray = Ray::new(comps.get_under_point(), dir).with_time(comps.get_ray_time());
+            }
+
+            if bounce + 1 >= self.get_rr_depth() {
+                let survive = max_channel(throughput).min(0.95);
+                if survive <= 0.0 || rng.random::<f32>() >= survive {
+                    break;
+                }
+                throughput = throughput * (1.0 / survive);
+            }
+        }
+        radiance
+    }
+}
+
+impl Integrator for PathTracer {
+    /// a single path, use `pixel_color` for the averaged estimate
+    fn ray_color(&self, world: &World, ray: Ray) -> Color {
+        self.trace(world, ray, &sampled_emitters(world), &mut world.ray_rng(ray))
+    }
+
+    fn pixel_color(&self, world: &World, cam: &Camera, x: usize, y: usize) -> Color {
+        self.sample_pixel(world, cam, x, y, &mut world.pixel_rng(x, y))
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self { samples_per_pixel: 16, max_bounces: 8, rr_depth: 3 }
+    }
+}
+
+/// light arriving straight from every light source, without the ambient term of `lighting`
+///
+/// lights have no falloff so the diffuse part matches `lighting` for the same scene
+fn direct_light(world: &World, comps: &Comps) -> Color {
+    let object = comps.get_object();
+    let mat = object.get_shared_material();
+    let base = object.get_color_at(comps.get_point());
+    let mut out = Color::black();
+    for light in world.get_light() {
+        out = out + light_contribution(world, comps, &world.attenuated_light(comps.get_over_point(), light), base, &mat);
+    }
+    out
+}
+
+fn light_contribution(world: &World, comps: &Comps, light: &Light, base: Color, mat: &Material) -> Color {
+    let light_v = (light.get_pos() - comps.get_point()).normalized();
+    let light_dot_normal = light_v.dot(comps.get_normalv());
+    if light_dot_normal < 0.0 || world.in_shadow_of(comps.get_over_point(), light, comps.get_ray_time()) {
+        return Color::black();
+    }
+    let local_pos = comps.get_object().get_inverse() * comps.get_point();
+    let param = |p| mat.get_param_at(p, local_pos);
+    if let ShadingModel::MetallicRoughness { .. } = mat.get_model() {
+        let (metallic, roughness) = (param(MaterialParam::Metallic), param(MaterialParam::Roughness));
+        let reflected = microfacet::reflectance(base, metallic, roughness, comps.get_normalv(), comps.get_eyev(), light_v);
+        return reflected * light.get_intensity();
+    }
+    let diffuse = base * param(MaterialParam::Diffuse) * light.get_intensity() * light_dot_normal;
+    let reflect_dot_eye = (-light_v).reflect(comps.get_normalv()).dot(comps.get_eyev());
+    if reflect_dot_eye <= 0.0 {
+        return diffuse;
+    }
+    diffuse + light.get_intensity() * param(MaterialParam::Specular) * reflect_dot_eye.powf(param(MaterialParam::Shininess))
+}
+
+/// glowing objects that can be sampled as lights
+fn sampled_emitters(world: &World) -> Vec<Arc<dyn Renderable>> {
+    world.get_object().into_iter()
+        .filter(|obj| obj.get_shared_material().is_emissive() && obj.sample_surface([0.5; 3]).is_some())
+        .collect()
+}
+
+/// light arriving from a point picked on one random emitter, through the diffuse lobe only
+///
+/// glossy, mirror and refracted paths pick up emitters by hitting them instead
+fn emitter_light<R: Rng + ?Sized>(world: &World, comps: &Comps, albedo: Color, emitters: &[Arc<dyn Renderable>], rng: &mut R) -> Color {
+    if emitters.is_empty() || max_channel(albedo) <= 0.0 {
+        return Color::black();
+    }
+    let emitter = &emitters[((rng.random::<f32>() * emitters.len() as f32) as usize).min(emitters.len() - 1)];
+    let sample = match emitter.sample_surface([rng.random::<f32>(), rng.random::<f32>(), rng.random::<f32>()]) {
+        Some(val) => val,
+        None => return Color::black(),
+    };
+    let from = comps.get_over_point();
+    let dist = (sample.get_point() - from).magnitude();
+    let wi = (sample.get_point() - from) / dist;
+    let cos_surface = comps.get_normalv().dot(wi);
+    let cos_emitter = -sample.get_normal().dot(wi);
+    if cos_surface <= 0.0 || cos_emitter <= 0.0 {
+        return Color::black();
+    }
+    let intersections = world.get_intersections(Ray::new(from, wi).with_time(comps.get_ray_time()));
+    if Intersection::find_hit(&intersections).is_some_and(|hit| hit.get_time() < dist - 2.0 * EPSILON) {
+        return Color::black();
+    }
+    // lambertian brdf albedo / pi, and cos_emitter / dist^2 turns the pdf over area into one over directions
+    emitter.get_shared_material().emitted() * albedo
+        * (cos_surface * cos_emitter * emitters.len() as f32 / (PI * dist * dist * sample.get_pdf()))
+}
+
+fn max_channel(color: Color) -> f32 {
+    color.get_r().max(color.get_g()).max(color.get_b())
+}
+
+/// any two unit vecs perpendicular to `normal` and each other
+fn orthonormal_basis(normal: Coord) -> (Coord, Coord) {
+    let helper = if normal.get_x().abs() > 0.9 { Coord::vec(0.0, 1.0, 0.0) } else { Coord::vec(1.0, 0.0, 0.0) };
+    let tangent = helper.cross(&normal).normalized();
+    let bitangent = normal.cross(&tangent);
+    (tangent, bitangent)
+}
+
+/// maps two uniform numbers in [0, 1) to a direction in the hemisphere around `normal` with pdf cos/pi
+fn cosine_sample_hemisphere(normal: Coord, u1: f32, u2: f32) -> Coord {
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    (tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1.0 - u1).max(0.0).sqrt()).normalized()
+}
+
+/// half vector around `normal` distributed by GGX D(h) (n.h), from two uniform numbers in [0, 1)
+fn ggx_sample_half(normal: Coord, alpha: f32, u1: f32, u2: f32) -> Coord {
+    let cos_theta = ((1.0 - u1) / (1.0 + (alpha * alpha - 1.0) * u1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + normal * cos_theta).normalized()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use crate::{material::Material, matrix::Matrix, primitives::{plane::Plane, sphere::Sphere}, renderable::{Renderable, RenderableBase}, sampling::SampleRng};
+
+    use super::*;
+
+    #[test]
+    fn test_cosine_sample_in_hemisphere() {
+        let normals = [Coord::vec(0.0, 1.0, 0.0), Coord::vec(1.0, 0.0, 0.0), Coord::vec(0.0, -0.6, 0.8)];
+        for n in normals {
+            for i in 0..10 {
+                for j in 0..10 {
+                    let dir = cosine_sample_hemisphere(n, i as f32 / 10.0, j as f32 / 10.0);
+                    assert!((dir.magnitude() - 1.0).abs() < 0.0001);
+                    assert!(dir.dot(n) >= 0.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ggx_sample_half() {
+        let n = Coord::vec(0.0, 1.0, 0.0);
+        // smooth surfaces keep the half vector close to the normal
+        assert!(ggx_sample_half(n, 0.001, 0.9, 0.3).dot(n) > 0.999);
+        for i in 0..10 {
+            let h = ggx_sample_half(n, 0.5, i as f32 / 10.0, 0.7);
+            assert!((h.magnitude() - 1.0).abs() < 0.0001);
+            assert!(h.dot(n) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_pbr_direct_light_matches_lighting() {
+        let mut rng = SampleRng::seed_from_u64(7);
+        // without ambient and with the path ending at the first hit, both shade the same
+        let mut w = World::new();
+        w.add_light(Light::new(Coord::point(-10.0, 10.0, -10.0), Color::white()).unwrap());
+        let mut mat = Material::metallic_roughness(Color::new(0.8, 0.5, 0.2, 0.0), 0.3, 0.4).unwrap();
+        mat.set_ambient(0.0).unwrap();
+        let mut s = Sphere::default();
+        s.set_material(mat);
+        w.add_obj(Arc::new(s));
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        let traced = PathTracer::new(1, 1, 1).trace(&w, r, &sampled_emitters(&w), &mut rng);
+        let whitted = w.color_at(r, 0);
+        assert!((traced.get_r() - whitted.get_r()).abs() < 0.0001);
+        assert!((traced.get_g() - whitted.get_g()).abs() < 0.0001);
+        assert!(traced.get_r() > 0.0);
+    }
+
+    #[test]
+    fn test_empty_world_is_black() {
+        let w = World::new();
+        let cam = Camera::new(3, 3, std::f32::consts::PI / 2.0);
+        let canvas = w.render_with(&cam, &PathTracer::new(2, 4, 2));
+        for y in 0..3 {
+            for x in 0..3 {
+                assert_eq!(canvas.get_pixel(x, y), Color::black());
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_bounce_is_direct_light() {
+        let mut rng = SampleRng::seed_from_u64(7);
+        // whitted gives (0.38066, 0.47583, 0.2855), the difference is the ambient term
+        let w = World::default();
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        let c = PathTracer::new(1, 1, 1).trace(&w, r, &sampled_emitters(&w), &mut rng);
+        assert!((c.get_r() - 0.30066).abs() < 0.0001);
+        assert!((c.get_g() - 0.37583).abs() < 0.0001);
+        assert!((c.get_b() - 0.2255).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_color_bleeding() {
+        let mut rng = SampleRng::seed_from_u64(7);
+        // the underside of a white sphere faces away from the light, so the only light it gets
+        // has bounced off the red floor and should be red
+        let mut w = World::new();
+        w.add_light(Light::new(Coord::point(5.0, 5.0, 0.0), Color::white()).unwrap());
+        let mut floor = Plane::default();
+        let mut mat = Material::default();
+        mat.set_color(Color::red());
+        mat.set_specular(0.0).unwrap();
+        floor.set_material(mat);
+        w.add_obj(Arc::new(floor));
+        let mut orb = Sphere::default();
+        orb.set_transformation(Matrix::translation(0.0, 2.0, 0.0)).unwrap();
+        w.add_obj(Arc::new(orb));
+
+        let r = Ray::new(Coord::point(0.0, 0.5, 0.0), Coord::vec(0.0, 1.0, 0.0));
+        let tracer = PathTracer::new(1, 2, 2);
+        let mut c = Color::black();
+        let emitters = sampled_emitters(&w);
+        for _ in 0..64 {
+            c = c + tracer.trace(&w, r, &emitters, &mut rng);
+        }
+        assert!(c.get_r() > 0.0);
+        assert_eq!(c.get_g(), 0.0);
+        assert_eq!(c.get_b(), 0.0);
+    }
+
+    #[test]
+    fn test_russian_roulette_terminates() {
+        let mut rng = SampleRng::seed_from_u64(7);
+        // a ray between two facing mirrors never escapes, only roulette ends the path early
+        let mut w = World::new();
+        w.add_light(Light::new(Coord::point(0.0, 0.5, -5.0), Color::white()).unwrap());
+        let mut mat = Material::default();
+        mat.set_reflection(1.0).unwrap();
+        mat.set_diffuse(0.0).unwrap();
+        let mut floor = Plane::default();
+        floor.set_material(mat.clone());
+        let mut ceiling = Plane::default();
+        ceiling.set_material(mat);
+        ceiling.set_transformation(Matrix::translation(0.0, 1.0, 0.0)).unwrap();
+        w.add_obj(Arc::new(floor));
+        w.add_obj(Arc::new(ceiling));
+
+        let r = Ray::new(Coord::point(0.0, 0.5, 0.0), Coord::vec(1.0, 1.0, 0.0));
+        let c = PathTracer::new(1, usize::MAX, 1).trace(&w, r, &sampled_emitters(&w), &mut rng);
+        assert!(c.get_r().is_finite());
+    }
+
+    fn lamp_over_floor() -> World {
+        let mut w = World::new();
+        let mut floor = Plane::default();
+        let mut mat = Material::default();
+        mat.set_diffuse(1.0).unwrap();
+        mat.set_specular(0.0).unwrap();
+        floor.set_material(mat);
+        w.add_obj(Arc::new(floor));
+        let mut lamp = Sphere::default();
+        lamp.set_transformation(Matrix::translation(0.0, 4.0, 0.0)).unwrap();
+        lamp.set_material(Material::emissive(Color::white(), 1.0).unwrap());
+        w.add_obj(Arc::new(lamp));
+        w
+    }
+
+    #[test]
+    fn test_emitter_seen_directly() {
+        let mut rng = SampleRng::seed_from_u64(7);
+        let w = lamp_over_floor();
+        let r = Ray::new(Coord::point(0.0, 4.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        assert_eq!(PathTracer::new(1, 1, 1).trace(&w, r, &sampled_emitters(&w), &mut rng), Color::white());
+    }
+
+    #[test]
+    fn test_emitter_lights_floor() {
+        let mut rng = SampleRng::seed_from_u64(7);
+        // a sphere of radiance 1 and radius r at height h gives a white floor right below it
+        // irradiance pi (r / h)^2, so it reflects (r / h)^2 = 1 / 16
+        let w = lamp_over_floor();
+        let r = Ray::new(Coord::point(0.0, 1.0, -1.0), Coord::vec(0.0, -1.0, 1.0).normalized());
+        let emitters = sampled_emitters(&w);
+        for bounces in [1, 3] {
+            let tracer = PathTracer::new(1, bounces, 10);
+            let samples = 4000;
+            let mut sum = 0.0;
+            for _ in 0..samples {
+                sum += tracer.trace(&w, r, &emitters, &mut rng).get_r();
+            }
+            // more bounces only add light that bounced off the floor onto the (black) lamp, so
+            // hitting the lamp after a diffuse bounce mustn't count it a second time
+            let mean = sum / samples as f32;
+            assert!((mean - 0.0625).abs() < 0.006, "{bounces} bounces: {mean}");
+        }
+    }
+
+    #[test]
+    fn test_emitter_shadowed() {
+        let mut rng = SampleRng::seed_from_u64(7);
+        let mut w = lamp_over_floor();
+        let mut blocker = Plane::default();
+        blocker.set_transformation(Matrix::translation(0.0, 2.0, 0.0)).unwrap();
+        w.add_obj(Arc::new(blocker));
+        let r = Ray::new(Coord::point(0.0, 1.0, -1.0), Coord::vec(0.0, -1.0, 1.0).normalized());
+        let c = PathTracer::new(1, 1, 10).trace(&w, r, &sampled_emitters(&w), &mut rng);
+        assert_eq!(c, Color::black());
+    }
+}
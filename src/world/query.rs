@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use crate::impl_getters;
+use crate::{camera::Camera, coord::Coord, ray::Ray, renderable::{Intersection, Renderable}};
+
+use super::{Comps, ObjectId, World};
+
+/// the closest surface a ray hits, see `World::cast_ray`
+#[derive(Clone)]
+pub struct RayHit {
+    object: Arc<dyn Renderable>,
+    id: Option<ObjectId>,
+    point: Coord,
+    normal: Coord,
+    distance: f32,
+    uv: (f32, f32),
+}
+
+impl_getters!(RayHit, id: Option<ObjectId>, point: Coord, normal: Coord, distance: f32, uv: (f32, f32));
+
+impl RayHit {
+    pub fn get_object(&self) -> Arc<dyn Renderable> {
+        self.object.clone()
+    }
+}
+
+impl std::fmt::Debug for RayHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RayHit")
+            .field("object", &self.object.get_type())
+            .field("id", &self.id)
+            .field("point", &self.point)
+            .field("normal", &self.normal)
+            .field("distance", &self.distance)
+            .field("uv", &self.uv)
+            .finish()
+    }
+}
+
+#[allow(dead_code)]
+impl World {
+    /// closest hit in front of the ray's origin, without shading anything
+    ///
+    /// the normal faces back towards the ray (flipped when the origin is inside the object) and the
+    /// distance is in world units along the ray, whatever the length of its direction
+    pub fn cast_ray(&self, ray: Ray) -> Option<RayHit> {
+        let intersections = self.get_intersections(ray);
+        let hit = Intersection::find_hit(&intersections)?.clone();
+        let distance = hit.get_time() * ray.get_direction().magnitude();
+        let comps = Comps::prepare_computations(hit, ray, intersections);
+        let object = comps.get_object();
+        Some(RayHit {
+            id: self.object_id(&comps.get_owner()),
+            uv: object.uv_at(comps.get_point()),
+            point: comps.get_point(),
+            normal: comps.get_normalv(),
+            distance,
+            object,
+        })
+    }
+
+    /// whatever is visible through the center of pixel (x, y) of `cam`
+    pub fn pick(&self, cam: &Camera, x: usize, y: usize) -> Option<RayHit> {
+        self.cast_ray(cam.ray_for_pixel(x, y))
+    }
+
+    /// id of a hit object, found by identity so it has to be the world's own `Arc`
+    pub(crate) fn object_id(&self, object: &Arc<dyn Renderable>) -> Option<ObjectId> {
+        let i = self.objects.iter().position(|o| Arc::ptr_eq(o, object))?;
+        Some(self.ids[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{material::Material, matrix::Matrix, motion::Motion, primitives::{moving::Moving, plane::Plane, sphere::Sphere}, renderable::RenderableType};
+
+    use super::*;
+
+    #[test]
+    fn test_cast_ray() {
+        let w = World::default();
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        let hit = w.cast_ray(r).unwrap();
+        assert_eq!(hit.get_id(), Some(w.get_ids()[0]));
+        assert_eq!(hit.get_object().get_type(), RenderableType::Sphere);
+        assert_eq!(hit.get_point(), Coord::point(0.0, 0.0, -1.0));
+        assert_eq!(hit.get_normal(), Coord::vec(0.0, 0.0, -1.0));
+        assert_eq!(hit.get_distance(), 4.0);
+        assert_eq!(hit.get_uv(), (0.0, 0.5));
+    }
+
+    #[test]
+    fn test_cast_ray_moving_id() {
+        // the hit is on a snapshot of the wrapped sphere but the id is still the moving object's
+        let mut w = World::new();
+        let id = w.add_obj(Arc::new(Moving::new(Arc::new(Sphere::default()), Motion::default())));
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        assert_eq!(w.cast_ray(r).unwrap().get_id(), Some(id));
+    }
+
+    #[test]
+    fn test_cast_ray_miss() {
+        let w = World::default();
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 1.0, 0.0));
+        assert!(w.cast_ray(r).is_none());
+    }
+
+    #[test]
+    fn test_cast_ray_inside() {
+        // starting inside the inner sphere, only hits in front count and the normal faces the ray
+        let w = World::default();
+        let r = Ray::new(Coord::point(0.0, 0.0, 0.0), Coord::vec(0.0, 0.0, 1.0));
+        let hit = w.cast_ray(r).unwrap();
+        assert_eq!(hit.get_id(), Some(w.get_ids()[1]));
+        assert_eq!(hit.get_point(), Coord::point(0.0, 0.0, 0.5));
+        assert_eq!(hit.get_normal(), Coord::vec(0.0, 0.0, -1.0));
+        assert_eq!(hit.get_distance(), 0.5);
+    }
+
+    #[test]
+    fn test_distance_ignores_direction_length() {
+        let w = World::default();
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 2.0));
+        assert_eq!(w.cast_ray(r).unwrap().get_distance(), 4.0);
+    }
+
+    #[test]
+    fn test_pick() {
+        let mut w = World::default();
+        let floor = w.add_obj(Arc::new(Plane::new(Matrix::translation(0.0, -1.0, 0.0), Material::default()).unwrap()));
+        let mut c = Camera::new(11, 11, std::f32::consts::PI / 2.0);
+        c.set_transformation(Matrix::view_transformation(
+            Coord::point(0.0, 0.0, -5.0),
+            Coord::point(0.0, 0.0, 0.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        )).unwrap();
+        assert_eq!(w.pick(&c, 5, 5).unwrap().get_id(), Some(w.get_ids()[0]));
+        assert_eq!(w.pick(&c, 5, 10).unwrap().get_id(), Some(floor));
+        assert!(w.pick(&c, 5, 0).is_none());
+    }
+}
@@ -0,0 +1,77 @@
+use crate::ray::Ray;
+use crate::sampling::{SampleRng, stream};
+
+use super::World;
+
+#[allow(dead_code)]
+impl World {
+    /// stream of random numbers for pixel (x, y), it only depends on the seed and the pixel
+    pub fn pixel_rng(&self, x: usize, y: usize) -> SampleRng {
+        stream(self.get_seed(), &[x as u64, y as u64])
+    }
+
+    /// stream for a ray that isn't tied to a pixel, eg `Integrator::ray_color`, the same ray always
+    /// gets the same numbers
+    pub(crate) fn ray_rng(&self, ray: Ray) -> SampleRng {
+        let (o, d) = (ray.get_origin(), ray.get_direction());
+        let keys = [o.get_x(), o.get_y(), o.get_z(), d.get_x(), d.get_y(), d.get_z(), ray.get_time()]
+            .map(|v| v.to_bits() as u64);
+        // the key count is mixed in too, so these never line up with a pixel's stream
+        stream(self.get_seed(), &keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngExt;
+
+    use crate::{camera::Camera, coord::Coord, matrix::Matrix, world::PathTracer};
+
+    use super::*;
+
+    fn camera() -> Camera {
+        let mut c = Camera::new(8, 8, std::f32::consts::PI / 2.0);
+        c.set_transformation(Matrix::view_transformation(
+            Coord::point(0.0, 0.0, -5.0),
+            Coord::point(0.0, 0.0, 0.0),
+            Coord::vec(0.0, 1.0, 0.0)
+        )).unwrap();
+        c
+    }
+
+    #[test]
+    fn test_pixel_streams() {
+        let mut w = World::new();
+        let first = w.pixel_rng(3, 4).random::<u64>();
+        assert_eq!(w.pixel_rng(3, 4).random::<u64>(), first);
+        assert_ne!(w.pixel_rng(4, 3).random::<u64>(), first);
+        w.set_seed(1);
+        assert_ne!(w.pixel_rng(3, 4).random::<u64>(), first);
+    }
+
+    #[test]
+    fn test_ray_streams() {
+        let w = World::new();
+        let r = Ray::new(Coord::point(0.0, 0.0, -5.0), Coord::vec(0.0, 0.0, 1.0));
+        let first = w.ray_rng(r).random::<u64>();
+        assert_eq!(w.ray_rng(r).random::<u64>(), first);
+        assert_ne!(w.ray_rng(r.with_time(0.5)).random::<u64>(), first);
+    }
+
+    #[test]
+    fn test_render_is_reproducible() {
+        let mut w = World::default();
+        w.set_seed(42);
+        let tracer = PathTracer::new(2, 3, 1);
+        let render = |threads: usize| {
+            rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap()
+                .install(|| w.render_with(&camera(), &tracer))
+        };
+        let single = render(1);
+        assert_eq!(render(4), single);
+        assert_eq!(render(1), single);
+
+        w.set_seed(43);
+        assert_ne!(w.render_with(&camera(), &tracer), single);
+    }
+}